@@ -3,7 +3,7 @@ mod ui;
 use {
     crate::{
         config::Config,
-        packages::{Dbs, LoadRecv, PkgCache},
+        packages::{Dbs, LoadRecv, LoadReport, PkgCache},
     },
     eframe::egui,
     egui_colors::{Colorix, tokens::ThemeColor},
@@ -14,6 +14,7 @@ use {
 pub struct AlpackaApp {
     pkgs: PkgCache,
     dbs: Option<Arc<Dbs>>,
+    load_report: LoadReport,
     ui: UiState,
     cfg: Config,
     load_recv: LoadRecv,
@@ -25,6 +26,7 @@ impl AlpackaApp {
         Self {
             pkgs: PkgCache::default(),
             dbs: None,
+            load_report: LoadReport::default(),
             ui: UiState::default(),
             cfg: Config::load_or_default(),
             load_recv: crate::packages::spawn_load_thread(),
@@ -38,6 +40,10 @@ impl AlpackaApp {
                 color_theme.map(ThemeColor::Custom),
             ));
         }
+        self.ui.shared.saved_queries = self.cfg.saved_queries.clone();
+        self.ui.shared.table_layouts = self.cfg.table_layouts.clone();
+        self.ui.shared.lost_files = self.cfg.lost_files.clone();
+        self.ui.open_pinned_queries();
     }
     fn sync_to_config(&mut self) {
         self.cfg.color_theme = self
@@ -46,6 +52,13 @@ impl AlpackaApp {
             .colorix
             .as_ref()
             .map(|colorix| colorix.theme().map(|theme| theme.rgb()));
+        self.cfg
+            .saved_queries
+            .clone_from(&self.ui.shared.saved_queries);
+        self.cfg
+            .table_layouts
+            .clone_from(&self.ui.shared.table_layouts);
+        self.cfg.lost_files.clone_from(&self.ui.shared.lost_files);
     }
 }
 
@@ -56,14 +69,14 @@ impl eframe::App for AlpackaApp {
         ui::cmd::process_cmds(self, ui);
         ui::modals(self, ui);
         if ui.input(|i| i.viewport().close_requested()) {
-            if self.ui.is_pacman_running() {
+            if self.ui.is_pacman_busy() {
                 ui.send_viewport_cmd(egui::ViewportCommand::CancelClose);
             }
         }
     }
     fn logic(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if let Some(handler) = &mut self.ui.shared.pac_handler {
-            handler.update(ctx);
+        if self.ui.shared.ops.update(ctx) {
+            self.load_recv = crate::packages::spawn_load_thread();
         }
     }
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
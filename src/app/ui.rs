@@ -1,24 +1,28 @@
 use {
     super::AlpackaApp,
-    ansi_term_buf::Term,
+    crate::{
+        config::{LostFilesConfig, SavedQuery, TableLayouts},
+        packages::LoadReport,
+    },
     cmd::CmdBuf,
-    eframe::egui::{self, TextBuffer},
+    eframe::egui,
     egui_colors::Colorix,
     egui_dock::{DockArea, DockState},
-    nonblock::NonBlockingReader,
-    pty_process::blocking::{Command as PtyCommand, Pty},
-    std::{
-        io::Write,
-        process::{Child, ExitStatus},
-        sync::{Arc, mpsc::TryRecvError},
+    op_queue::OpQueue,
+    std::sync::{Arc, mpsc::TryRecvError},
+    tabs::{
+        Tab, TabViewState, cache, file_owner, history, lost_files, orphans,
+        saved_query::{ManageState, SavedQueryTab},
+        upgrade_list, verify,
     },
-    tabs::{Tab, TabViewState, upgrade_list},
 };
 
 pub mod cmd;
 mod ico;
+pub mod op_queue;
 mod paint_util;
 mod tabs;
+mod transaction_dialog;
 
 pub(super) struct UiState {
     dock_state: DockState<Tab>,
@@ -29,8 +33,14 @@ pub(super) struct UiState {
 pub struct SharedUiState {
     cmd: CmdBuf,
     pub colorix: Option<Colorix>,
-    pub pac_handler: Option<PacChildHandler>,
+    pub ops: OpQueue,
     pub error_popup: Option<String>,
+    pub saved_queries: Vec<SavedQuery>,
+    pub table_layouts: TableLayouts,
+    pub lost_files: LostFilesConfig,
+    pub pacman_log: history::LogCache,
+    /// Transaction waiting for confirmation
+    pub pending_transaction: Option<transaction_dialog::PendingTransaction>,
 }
 
 impl Default for UiState {
@@ -43,8 +53,17 @@ impl Default for UiState {
 }
 
 impl UiState {
-    pub fn is_pacman_running(&self) -> bool {
-        self.shared.pac_handler.is_some()
+    pub const fn is_pacman_busy(&self) -> bool {
+        self.shared.ops.is_busy()
+    }
+    /// Open a tab for each pinned saved query
+    pub fn open_pinned_queries(&mut self) {
+        for query in &self.shared.saved_queries {
+            if query.pinned {
+                self.dock_state
+                    .push_to_first_leaf(Tab::SavedQuery(SavedQueryTab::new(query.name.clone())));
+            }
+        }
     }
 }
 
@@ -60,13 +79,8 @@ pub fn top_panel_ui(app: &mut AlpackaApp, ui: &mut egui::Ui) {
                 ui.separator();
                 ui.menu_button("⟳ Sync", |ui| {
                     if ui.button("🔁 Sync databases (pacman -Sy)").clicked() {
-                        if let Err(e) =
-                            spawn_pacman_cmd_root_pkexec(&mut app.ui.shared.pac_handler, &["-Sy"])
-                        {
-                            app.ui.shared.error_popup = Some(e.to_string());
-                        } else {
-                            app.open_upgrade_window = true;
-                        }
+                        app.ui.shared.ops.enqueue(&["-Sy"]);
+                        app.open_upgrade_window = true;
                     }
                     if ui.button("Upgrade list").clicked() {
                         app.ui
@@ -77,10 +91,50 @@ pub fn top_panel_ui(app: &mut AlpackaApp, ui: &mut egui::Ui) {
                         app.load_recv = crate::packages::spawn_load_thread();
                     }
                 });
+                ui.menu_button("🔧 Tools", |ui| {
+                    if ui.button("⏳ Operations").clicked() {
+                        app.ui.dock_state.push_to_first_leaf(Tab::Operations);
+                    }
+                    if ui.button("📜 History").clicked() {
+                        app.ui
+                            .dock_state
+                            .push_to_first_leaf(Tab::History(history::State::default()));
+                    }
+                    if ui.button("🗄 Package cache").clicked() {
+                        app.ui
+                            .dock_state
+                            .push_to_first_leaf(Tab::Cache(cache::State::default()));
+                    }
+                    if ui.button("🔎 Find file owner").clicked() {
+                        app.ui
+                            .dock_state
+                            .push_to_first_leaf(Tab::FileOwner(file_owner::State::default()));
+                    }
+                    if ui.button("✔ Verify files").clicked() {
+                        app.ui
+                            .dock_state
+                            .push_to_first_leaf(Tab::Verify(verify::State::default()));
+                    }
+                    if ui.button("👻 Lost files").clicked() {
+                        app.ui
+                            .dock_state
+                            .push_to_first_leaf(Tab::LostFiles(lost_files::State::default()));
+                    }
+                    if ui.button("🧹 Orphans").clicked() {
+                        app.ui
+                            .dock_state
+                            .push_to_first_leaf(Tab::Orphans(orphans::State::default()));
+                    }
+                });
                 ui.menu_button("☰ Preferences", |ui| {
                     if ui.button("🎨 Color theme").clicked() {
                         app.ui.dock_state.push_to_first_leaf(Tab::ColorTheme);
                     }
+                    if ui.button("⭐ Saved queries").clicked() {
+                        app.ui
+                            .dock_state
+                            .push_to_first_leaf(Tab::SavedQueries(ManageState::default()));
+                    }
                     match crate::config::cfg_dir() {
                         Some(dir) => {
                             if ui.button("Open config dir").clicked() {
@@ -101,38 +155,89 @@ pub fn top_panel_ui(app: &mut AlpackaApp, ui: &mut egui::Ui) {
                     }
                 });
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                    match app.load_recv.try_recv() {
-                        Ok(result) => match result {
-                            Ok((pkgs, dbs)) => {
-                                app.pkgs = pkgs;
-                                app.dbs = Some(Arc::new(dbs));
-                                if app.open_upgrade_window {
-                                    app.ui.dock_state.push_to_focused_leaf(Tab::UpgradeList(
-                                        upgrade_list::State::default(),
-                                    ));
-                                }
-                            }
-                            Err(e) => {
-                                log::error!("Failed to load pacman dbs: {e}");
-                            }
-                        },
-                        Err(e) => match e {
-                            TryRecvError::Empty => {
-                                ui.spinner();
-                                ui.label("Loading pacman dbs...");
-                            }
-                            TryRecvError::Disconnected => {}
-                        },
-                    }
-                    if app.ui.shared.pac_handler.is_some() {
-                        ui.spinner();
-                        ui.label("running pacman...");
-                    }
+                    status_ui(app, ui);
                 });
             });
         });
 }
 
+/// Database loading and pacman activity
+fn status_ui(app: &mut AlpackaApp, ui: &mut egui::Ui) {
+    match app.load_recv.try_recv() {
+        Ok(result) => match result {
+            Ok((pkgs, dbs, report)) => {
+                for (repo, err) in &report.failed_repos {
+                    log::warn!("Failed to load [{repo}]: {err}");
+                }
+                app.pkgs = pkgs;
+                app.dbs = Some(Arc::new(dbs));
+                app.load_report = report;
+                if app.open_upgrade_window {
+                    app.ui
+                        .dock_state
+                        .push_to_focused_leaf(Tab::UpgradeList(upgrade_list::State::default()));
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to load pacman dbs: {e}");
+            }
+        },
+        Err(e) => match e {
+            TryRecvError::Empty => {
+                ui.spinner();
+                ui.label("Loading pacman dbs...");
+            }
+            TryRecvError::Disconnected => {}
+        },
+    }
+    let ops = &app.ui.shared.ops;
+    if ops.is_busy() {
+        ui.spinner();
+        let text = match ops.pending.len() {
+            0 => "running pacman...".to_owned(),
+            n => format!("running pacman... ({n} queued)"),
+        };
+        if ui.link(text).clicked() {
+            app.ui.dock_state.push_to_first_leaf(Tab::Operations);
+        }
+    } else if ops.paused && !ops.pending.is_empty() {
+        let text = egui::RichText::new(format!("⏸ {} operations paused", ops.pending.len()))
+            .color(ui.visuals().warn_fg_color);
+        if ui.link(text).clicked() {
+            app.ui.dock_state.push_to_first_leaf(Tab::Operations);
+        }
+    }
+    load_report_ui(ui, &app.load_report);
+}
+
+fn load_report_ui(ui: &mut egui::Ui, report: &LoadReport) {
+    let n_problems = report.failed_repos.len() + usize::from(report.conf_error.is_some());
+    if n_problems == 0 {
+        return;
+    }
+    let text = egui::RichText::new(format!("⚠ {n_problems}")).color(egui::Color32::YELLOW);
+    ui.menu_button(text, |ui| {
+        ui.heading("Some databases failed to load");
+        ui.separator();
+        egui::Grid::new("load_report_grid")
+            .striped(true)
+            .show(ui, |ui| {
+                if let Some(err) = &report.conf_error {
+                    ui.label(egui::RichText::new("pacman.conf").strong());
+                    ui.label(format!("{err}. Using the default repositories."));
+                    ui.end_row();
+                }
+                for (repo, err) in &report.failed_repos {
+                    ui.label(egui::RichText::new(repo.as_str()).strong());
+                    ui.label(err);
+                    ui.end_row();
+                }
+            });
+    })
+    .response
+    .on_hover_text("Some databases failed to load");
+}
+
 pub fn central_panel_ui(app: &mut AlpackaApp, ui: &mut egui::Ui) {
     DockArea::new(&mut app.ui.dock_state)
         .show_leaf_collapse_buttons(false)
@@ -147,123 +252,16 @@ pub fn central_panel_ui(app: &mut AlpackaApp, ui: &mut egui::Ui) {
         );
 }
 
-pub struct PacChildHandler {
-    child: Child,
-    pty: Pty,
-    term: Term,
-    exit_status: Option<ExitStatus>,
-    input_buf: String,
-}
-
-impl PacChildHandler {
-    fn new(child: Child, pty: Pty) -> Self {
-        Self {
-            child,
-            pty,
-            term: Term::new(100),
-            exit_status: None,
-            input_buf: String::new(),
-        }
-    }
-    pub fn update(&mut self, ctx: &egui::Context) {
-        if self.exit_status.is_some() {
-            return;
-        }
-        let mut buf = Vec::new();
-        let mut nbr = match NonBlockingReader::from_fd(&self.pty) {
-            Ok(nbr) => nbr,
-            Err(e) => {
-                log::error!("Failed to create non-blocking reader: {e}");
-                return;
-            }
-        };
-        match nbr.read_available(&mut buf) {
-            Ok(n_read) => {
-                if n_read != 0 {
-                    self.term.feed(&buf);
-                }
-            }
-            Err(e) => {
-                log::error!("error reading from pacman: {e}");
-            }
-        }
-        match self.child.try_wait() {
-            Ok(Some(status)) => self.exit_status = Some(status),
-            Ok(None) => {}
-            Err(e) => {
-                log::error!("Error waiting for pacman: {e}");
-            }
-        }
-        // Required to keep "polling" the output of pacman
-        ctx.request_repaint();
-    }
-}
-
-fn spawn_pacman_cmd_root_pkexec(
-    pac_handler: &mut Option<PacChildHandler>,
-    args: &[&str],
-) -> anyhow::Result<()> {
-    let (pty, the_pts) = pty_process::blocking::open()?;
-    let child = PtyCommand::new("pkexec")
-        .args([["pacman"].as_slice(), args].concat())
-        .spawn(the_pts)?;
-    *pac_handler = Some(PacChildHandler::new(child, pty));
-    Ok(())
-}
-
 pub fn modals(app: &mut AlpackaApp, ctx: &egui::Context) {
-    let mut close_handler = false;
-    if let Some(handler) = &mut app.ui.shared.pac_handler {
-        let out = handler.term.contents_to_string();
-        egui::Modal::new(egui::Id::new("pacman output modal")).show(ctx, |ui| {
-            ui.heading("Pacman output");
-            ui.separator();
-            let avail_rect = ui.ctx().content_rect();
-            let w = (avail_rect.width() * 0.5).round();
-            ui.set_width(w);
-            egui::ScrollArea::both()
-                .max_height((avail_rect.height() * 0.5).round())
-                .max_width(w)
-                .stick_to_bottom(true)
-                .show(ui, |ui| {
-                    ui.set_width(1000.0);
-                    ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
-                    ui.add(
-                        egui::TextEdit::multiline(&mut out.as_str())
-                            .code_editor()
-                            .desired_width(f32::INFINITY),
-                    );
-                });
-            ui.separator();
-            ui.add(
-                egui::TextEdit::singleline(&mut handler.input_buf)
-                    .hint_text("pacman input")
-                    .desired_width(f32::INFINITY),
-            );
-            if ui.input(|inp| inp.key_pressed(egui::Key::Enter)) {
-                let mut buf = handler.input_buf.take();
-                buf.push('\n');
-                if let Err(e) = handler.pty.write_all(buf.as_bytes()) {
-                    log::error!("Error writing input: {e}");
-                }
-            }
-            ui.separator();
-            if let Some(status) = &handler.exit_status {
-                ui.label(format!("Pacman exited ({status})"));
-                if ui.button("Close").clicked()
-                    || ui.input(|inp| {
-                        inp.key_pressed(egui::Key::Enter) || inp.key_pressed(egui::Key::Escape)
-                    })
-                {
-                    close_handler = true;
-                    app.load_recv = crate::packages::spawn_load_thread();
-                }
-            }
-        });
-    }
-    if close_handler {
-        app.ui.shared.pac_handler = None;
+    let ops = &mut app.ui.shared.ops;
+    let n_pending = ops.pending.len();
+    if let Some(running) = &mut ops.running
+        && op_queue::output_window(ctx, running, n_pending)
+    {
+        ops.finish_running();
+        app.load_recv = crate::packages::spawn_load_thread();
     }
+    transaction_dialog::modal(ctx, &mut app.ui.shared, app.dbs.as_ref());
     if let Some(err) = &app.ui.shared.error_popup {
         let mut close = false;
         egui::Modal::new("error_modal".into()).show(ctx, |ui| {
@@ -1,11 +1,17 @@
 use {
-    super::{Tab, tabs::package::PkgTab},
-    crate::{
-        app::{AlpackaApp, ui::spawn_pacman_cmd_root_pkexec},
-        packages::PkgRef,
+    super::{
+        Tab,
+        tabs::{
+            package::PkgTab,
+            saved_query::{ManageState, SavedQueryTab},
+        },
+        transaction_dialog::PendingTransaction,
     },
+    crate::{app::AlpackaApp, packages::PkgRef, transaction::Op},
     eframe::egui,
     egui_dock::{LeafNode, Node, NodeIndex, TabIndex},
+    smol_str::SmolStr,
+    std::path::PathBuf,
 };
 
 #[derive(Default)]
@@ -21,9 +27,28 @@ impl CmdBuf {
 
 pub enum Cmd {
     OpenPkgTab(PkgRef),
-    Rscn(smol_str::SmolStr),
-    AsDep(smol_str::SmolStr),
-    AsExplicit(smol_str::SmolStr),
+    // The following act on multiple packages at once, so there's only one pkexec prompt.
+    // The ones changing the installed packages are previewed for confirmation first.
+    Rscn(Vec<SmolStr>),
+    Rns(Vec<SmolStr>),
+    AsDep(Vec<SmolStr>),
+    AsExplicit(Vec<SmolStr>),
+    Reinstall(Vec<SmolStr>),
+    /// Install `repo/pkg` targets, or names that pacman resolves in repo order
+    Install(Vec<SmolStr>),
+    /// Like `Install`, but with `--asdeps`
+    InstallAsDeps(Vec<SmolStr>),
+    /// `pacman -Su`
+    SysUpgrade,
+    /// `pacman -U` on a package file, e.g. to downgrade from the cache
+    InstallFile {
+        path: PathBuf,
+        name: SmolStr,
+        version: SmolStr,
+    },
+    /// Open the tab of a pinned saved query
+    OpenSavedQuery(String),
+    ManageSavedQueries,
 }
 
 pub fn process_cmds(app: &mut AlpackaApp, _ctx: &egui::Context) {
@@ -60,39 +85,87 @@ pub fn process_cmds(app: &mut AlpackaApp, _ctx: &egui::Context) {
                         .iter_mut()
                         .rfind(|node| node.is_leaf())
                     {
-                        tabs.push(Tab::Pkg(PkgTab::new(id)));
+                        tabs.push(Tab::Pkg(Box::new(PkgTab::new(id))));
                         *active = TabIndex(tabs.len().saturating_sub(1));
                     } else {
                         app.ui
                             .dock_state
-                            .push_to_first_leaf(Tab::Pkg(PkgTab::new(id)));
+                            .push_to_first_leaf(Tab::Pkg(Box::new(PkgTab::new(id))));
                     }
                 }
             }
-            Cmd::Rscn(pkg_name) => {
-                if let Err(e) = spawn_pacman_cmd_root_pkexec(
-                    &mut app.ui.shared.pac_handler,
-                    &["-Rscn", pkg_name.as_str()],
-                ) {
-                    app.ui.shared.error_popup = Some(e.to_string());
-                }
+            Cmd::Rscn(names) => confirm_transaction(
+                app,
+                Op::Remove {
+                    names,
+                    cascade: true,
+                },
+            ),
+            Cmd::Rns(names) => confirm_transaction(
+                app,
+                Op::Remove {
+                    names,
+                    cascade: false,
+                },
+            ),
+            Cmd::AsDep(pkg_names) => pacman_with_names(app, &["-D", "--asdeps"], &pkg_names),
+            Cmd::AsExplicit(pkg_names) => {
+                pacman_with_names(app, &["-D", "--asexplicit"], &pkg_names);
             }
-            Cmd::AsDep(pkg_name) => {
-                if let Err(e) = spawn_pacman_cmd_root_pkexec(
-                    &mut app.ui.shared.pac_handler,
-                    &["-D", pkg_name.as_str(), "--asdeps"],
-                ) {
-                    app.ui.shared.error_popup = Some(e.to_string());
-                }
+            Cmd::Reinstall(targets) | Cmd::Install(targets) => confirm_transaction(
+                app,
+                Op::Install {
+                    targets,
+                    as_deps: false,
+                },
+            ),
+            Cmd::InstallAsDeps(targets) => confirm_transaction(
+                app,
+                Op::Install {
+                    targets,
+                    as_deps: true,
+                },
+            ),
+            Cmd::SysUpgrade => confirm_transaction(app, Op::SysUpgrade),
+            Cmd::InstallFile {
+                path,
+                name,
+                version,
+            } => confirm_transaction(
+                app,
+                Op::InstallFile {
+                    path: path.to_string_lossy().into_owned(),
+                    name,
+                    version,
+                },
+            ),
+            Cmd::OpenSavedQuery(name) => {
+                app.ui
+                    .dock_state
+                    .push_to_first_leaf(Tab::SavedQuery(SavedQueryTab::new(name)));
             }
-            Cmd::AsExplicit(pkg_name) => {
-                if let Err(e) = spawn_pacman_cmd_root_pkexec(
-                    &mut app.ui.shared.pac_handler,
-                    &["-D", pkg_name.as_str(), "--asexplicit"],
-                ) {
-                    app.ui.shared.error_popup = Some(e.to_string());
-                }
+            Cmd::ManageSavedQueries => {
+                app.ui
+                    .dock_state
+                    .push_to_first_leaf(Tab::SavedQueries(ManageState::default()));
             }
         }
     }
 }
+
+/// Show the preview of `op`, which runs it when confirmed
+fn confirm_transaction(app: &mut AlpackaApp, op: Op) {
+    match &app.dbs {
+        Some(dbs) => {
+            app.ui.shared.pending_transaction = Some(PendingTransaction::new(op, dbs));
+        }
+        None => app.ui.shared.error_popup = Some("The databases aren't loaded yet".into()),
+    }
+}
+
+/// Queue pacman with `args` followed by the package names
+fn pacman_with_names(app: &mut AlpackaApp, args: &[&str], pkg_names: &[SmolStr]) {
+    let names = pkg_names.iter().map(SmolStr::as_str);
+    let args: Vec<&str> = args.iter().copied().chain(names).collect();
+    app.ui.shared.ops.enqueue(&args);
+}
@@ -1,3 +1,4 @@
 pub const PKG: &str = "📦";
 pub const TRASH: &str = "🗑";
 pub const NOTE: &str = "🗊";
+pub const INSTALL: &str = "📥";
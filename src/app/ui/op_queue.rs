@@ -0,0 +1,253 @@
+//! Operations run as root, queued and run one after another
+
+use {
+    ansi_term_buf::Term,
+    eframe::egui::{self, TextBuffer},
+    nonblock::NonBlockingReader,
+    pty_process::blocking::{Command as PtyCommand, Pty},
+    std::{
+        io::Write,
+        process::{Child, ExitStatus},
+    },
+};
+
+#[derive(Default)]
+pub struct OpQueue {
+    /// Operations that haven't started yet, in the order they will run
+    pub pending: Vec<QueuedOp>,
+    pub running: Option<RunningOp>,
+    /// Finished operations, oldest first
+    pub history: Vec<FinishedOp>,
+    /// Set when an operation fails while others are pending.
+    /// Pending operations don't start until it's cleared.
+    pub paused: bool,
+}
+
+pub struct QueuedOp {
+    /// The program to run, usually `pacman`
+    pub program: &'static str,
+    pub args: Vec<String>,
+}
+
+pub struct RunningOp {
+    pub program: &'static str,
+    pub args: Vec<String>,
+    handler: PacChildHandler,
+}
+
+impl RunningOp {
+    pub const fn exit_status(&self) -> Option<ExitStatus> {
+        self.handler.exit_status
+    }
+}
+
+pub struct FinishedOp {
+    pub program: &'static str,
+    pub args: Vec<String>,
+    /// `Err` if the program couldn't be run
+    pub status: Result<ExitStatus, String>,
+    pub output: String,
+    /// Unix timestamp
+    pub finished_at: i64,
+}
+
+impl OpQueue {
+    /// Queue pacman with `args`
+    pub fn enqueue(&mut self, args: &[&str]) {
+        self.enqueue_program("pacman", args);
+    }
+    pub fn enqueue_program(&mut self, program: &'static str, args: &[&str]) {
+        self.pending.push(QueuedOp {
+            program,
+            args: args.iter().map(ToString::to_string).collect(),
+        });
+    }
+    /// Whether an operation is running or waiting to run
+    pub const fn is_busy(&self) -> bool {
+        self.running.is_some() || !self.paused && !self.pending.is_empty()
+    }
+    /// Poll the running operation, and start the next one when it's done.
+    ///
+    /// Returns whether an operation finished, meaning the databases should be reloaded.
+    pub fn update(&mut self, ctx: &egui::Context) -> bool {
+        let mut finished = false;
+        if let Some(running) = &mut self.running {
+            let was_running = running.handler.exit_status.is_none();
+            running.handler.update(ctx);
+            match running.handler.exit_status {
+                // The output of a failed operation stays up until it's dismissed,
+                // and the rest of the queue waits until it's resumed
+                Some(status) if !status.success() => {
+                    self.paused |= was_running && !self.pending.is_empty();
+                }
+                // So does the output of the last operation
+                Some(_) if !self.pending.is_empty() => {
+                    self.finish_running();
+                    finished = true;
+                }
+                _ => {}
+            }
+        }
+        if self.running.is_none() && !self.paused && !self.pending.is_empty() {
+            let op = self.pending.remove(0);
+            let args: Vec<&str> = op.args.iter().map(String::as_str).collect();
+            match spawn_cmd_root_pkexec(op.program, &args) {
+                Ok(handler) => {
+                    self.running = Some(RunningOp {
+                        program: op.program,
+                        args: op.args,
+                        handler,
+                    });
+                }
+                Err(e) => {
+                    log::error!("Failed to run {}: {e}", op.program);
+                    self.paused = !self.pending.is_empty();
+                    self.history.push(FinishedOp {
+                        program: op.program,
+                        args: op.args,
+                        status: Err(e.to_string()),
+                        output: String::new(),
+                        finished_at: chrono::Local::now().timestamp(),
+                    });
+                }
+            }
+        }
+        finished
+    }
+    /// Move the running operation to the history
+    pub fn finish_running(&mut self) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        // Nothing is held back, so the next enqueued operation can start right away
+        if self.pending.is_empty() {
+            self.paused = false;
+        }
+        self.history.push(FinishedOp {
+            program: running.program,
+            args: running.args,
+            status: running
+                .handler
+                .exit_status
+                .ok_or_else(|| "Didn't finish".to_owned()),
+            output: running.handler.term.contents_to_string(),
+            finished_at: chrono::Local::now().timestamp(),
+        });
+    }
+}
+
+struct PacChildHandler {
+    child: Child,
+    pty: Pty,
+    term: Term,
+    exit_status: Option<ExitStatus>,
+    input_buf: String,
+}
+
+impl PacChildHandler {
+    fn new(child: Child, pty: Pty) -> Self {
+        Self {
+            child,
+            pty,
+            term: Term::new(100),
+            exit_status: None,
+            input_buf: String::new(),
+        }
+    }
+    fn update(&mut self, ctx: &egui::Context) {
+        if self.exit_status.is_some() {
+            return;
+        }
+        let mut buf = Vec::new();
+        let mut nbr = match NonBlockingReader::from_fd(&self.pty) {
+            Ok(nbr) => nbr,
+            Err(e) => {
+                log::error!("Failed to create non-blocking reader: {e}");
+                return;
+            }
+        };
+        match nbr.read_available(&mut buf) {
+            Ok(n_read) => {
+                if n_read != 0 {
+                    self.term.feed(&buf);
+                }
+            }
+            Err(e) => {
+                log::error!("error reading from pacman: {e}");
+            }
+        }
+        match self.child.try_wait() {
+            Ok(Some(status)) => self.exit_status = Some(status),
+            Ok(None) => {}
+            Err(e) => {
+                log::error!("Error waiting for pacman: {e}");
+            }
+        }
+        // Required to keep "polling" the output of pacman
+        ctx.request_repaint();
+    }
+}
+
+fn spawn_cmd_root_pkexec(program: &str, args: &[&str]) -> anyhow::Result<PacChildHandler> {
+    let (pty, the_pts) = pty_process::blocking::open()?;
+    let child = PtyCommand::new("pkexec")
+        .args([[program].as_slice(), args].concat())
+        .spawn(the_pts)?;
+    Ok(PacChildHandler::new(child, pty))
+}
+
+/// Output of the running operation, with input for pacman's prompts.
+///
+/// It's a window rather than a modal, so the queue can be managed while the operation runs.
+/// Returns whether a finished operation was dismissed.
+pub fn output_window(ctx: &egui::Context, running: &mut RunningOp, n_pending: usize) -> bool {
+    let handler = &mut running.handler;
+    let mut dismissed = false;
+    let out = handler.term.contents_to_string();
+    egui::Window::new(format!("{} output", running.program))
+        .id(egui::Id::new("pacman output window"))
+        .show(ctx, |ui| {
+            ui.label(
+                egui::RichText::new(format!("{} {}", running.program, running.args.join(" ")))
+                    .monospace(),
+            );
+            ui.separator();
+            let avail_rect = ui.ctx().content_rect();
+            let w = (avail_rect.width() * 0.5).round();
+            ui.set_width(w);
+            egui::ScrollArea::both()
+                .max_height((avail_rect.height() * 0.5).round())
+                .max_width(w)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    ui.set_width(1000.0);
+                    ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+                    // A label rather than a text edit, so only the prompt input takes focus
+                    ui.label(egui::RichText::new(out).monospace());
+                });
+            ui.separator();
+            let re = ui.add(
+                egui::TextEdit::singleline(&mut handler.input_buf)
+                    .hint_text("input")
+                    .desired_width(f32::INFINITY),
+            );
+            if re.lost_focus() && ui.input(|inp| inp.key_pressed(egui::Key::Enter)) {
+                let mut buf = handler.input_buf.take();
+                buf.push('\n');
+                if let Err(e) = handler.pty.write_all(buf.as_bytes()) {
+                    log::error!("Error writing input: {e}");
+                }
+            }
+            ui.separator();
+            if n_pending != 0 {
+                ui.label(format!("{n_pending} more operations queued"));
+            }
+            if let Some(status) = &handler.exit_status {
+                ui.label(format!("{} exited ({status})", running.program));
+                if ui.button("Close").clicked() {
+                    dismissed = true;
+                }
+            }
+        });
+    dismissed
+}
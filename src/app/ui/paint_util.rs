@@ -34,3 +34,13 @@ pub fn draw_logo(painter: &egui::Painter, center: egui::Pos2, radius: f32) {
         egui::Stroke::new(1.0, egui::Color32::BLACK),
     );
 }
+
+/// Line from `from` to `to` with an arrow head of a fixed size at `to`
+pub fn arrow_to(painter: &egui::Painter, from: egui::Pos2, to: egui::Pos2, stroke: egui::Stroke) {
+    let dir = (to - from).normalized();
+    let tip_len = 4.0f32.mul_add(stroke.width, 4.0);
+    let rot = egui::emath::Rot2::from_angle(FRAC_PI_4 / 2.0);
+    painter.line_segment([from, to], stroke);
+    painter.line_segment([to, to - tip_len * (rot * dir)], stroke);
+    painter.line_segment([to, to - tip_len * (rot.inverse() * dir)], stroke);
+}
@@ -2,20 +2,36 @@ use {
     super::SharedUiState,
     crate::{
         app::{PkgCache, ui::ico},
+        config::PkgListKind,
         packages::Dbs,
-        query_syntax::PkgListQuery,
+        query_syntax::{ParseError, PkgListQuery},
+        text_match::MatchMode,
     },
     eframe::egui,
     egui_dock::TabViewer,
     package::PkgTab,
-    std::sync::Arc,
+    std::{
+        cmp::Reverse,
+        ops::Range,
+        sync::{Arc, Weak},
+    },
 };
 
+pub mod cache;
 mod color_theme;
+pub mod file_owner;
+pub mod history;
 pub mod local_pkg_list;
+pub mod lost_files;
+mod operations;
+pub mod orphans;
 pub mod package;
+mod pkg_table;
+mod query_edit;
 pub mod remote_pkg_list;
+pub mod saved_query;
 pub mod upgrade_list;
+pub mod verify;
 
 pub struct TabViewState<'pkgs, 'dbs, 'ui> {
     pub pkgs: &'pkgs mut PkgCache,
@@ -38,7 +54,23 @@ impl TabViewer for TabViewState<'_, '_, '_> {
             )
             .into(),
             Tab::UpgradeList(_) => "Upgrade list".into(),
+            Tab::Orphans(_) => "🧹 Orphans".into(),
+            Tab::History(_) => "📜 History".into(),
+            Tab::Cache(_) => "🗄 Package cache".into(),
+            Tab::FileOwner(_) => "🔎 Find file owner".into(),
+            Tab::Verify(_) => "✔ Verify files".into(),
+            Tab::LostFiles(_) => "👻 Lost files".into(),
+            Tab::Operations => {
+                let ops = &self.ui.ops;
+                let n = ops.pending.len() + usize::from(ops.running.is_some());
+                format!("⏳ Operations ({n})").into()
+            }
             Tab::Pkg(pkg) => format!("{} {}", ico::PKG, pkg.id.display(dbs)).into(),
+            Tab::SavedQuery(tab) => match tab.count(&self.ui.saved_queries, dbs) {
+                Some(count) => format!("📌 {} ({count})", tab.name).into(),
+                None => format!("📌 {}", tab.name).into(),
+            },
+            Tab::SavedQueries(_) => "⭐ Saved queries".into(),
             Tab::ColorTheme => "🎨 Color theme".into(),
             Tab::LoggerUi => "Log".into(),
         }
@@ -53,7 +85,16 @@ impl TabViewer for TabViewState<'_, '_, '_> {
             Tab::LocalPkgList(state) => local_pkg_list::ui(ui, self.pkgs, dbs, self.ui, state),
             Tab::RemotePkgList(state) => remote_pkg_list::ui(ui, self.pkgs, dbs, self.ui, state),
             Tab::UpgradeList(state) => upgrade_list::ui(ui, dbs, self.ui, state),
+            Tab::Orphans(state) => orphans::ui(ui, dbs, self.ui, state),
+            Tab::History(state) => history::ui(ui, dbs, self.ui, state),
+            Tab::Cache(state) => cache::ui(ui, dbs, self.ui, state),
+            Tab::FileOwner(state) => file_owner::ui(ui, dbs, self.ui, state),
+            Tab::Verify(state) => verify::ui(ui, dbs, self.ui, state),
+            Tab::LostFiles(state) => lost_files::ui(ui, dbs, self.ui, state),
+            Tab::Operations => operations::ui(ui, &mut self.ui.ops),
             Tab::Pkg(tab) => package::ui(ui, dbs, self.ui, tab),
+            Tab::SavedQuery(tab) => saved_query::ui(ui, dbs, self.ui, tab),
+            Tab::SavedQueries(state) => saved_query::manage_ui(ui, self.ui, state),
             Tab::ColorTheme => color_theme::ui(ui, &mut self.ui.colorix),
             Tab::LoggerUi => egui_logger::logger_ui().show(ui),
         }
@@ -62,14 +103,30 @@ impl TabViewer for TabViewState<'_, '_, '_> {
     fn is_closeable(&self, tab: &Self::Tab) -> bool {
         #[expect(clippy::match_like_matches_macro)]
         match tab {
-            Tab::LocalPkgList(_) | Tab::RemotePkgList(_) => false,
+            Tab::LocalPkgList(_) | Tab::RemotePkgList(_) | Tab::SavedQuery(_) => false,
             _ => true,
         }
     }
 
     fn force_close(&mut self, tab: &mut Self::Tab) -> bool {
         match tab {
-            Tab::LocalPkgList(_) | Tab::RemotePkgList(_) | Tab::ColorTheme | Tab::LoggerUi => false,
+            Tab::LocalPkgList(_)
+            | Tab::RemotePkgList(_)
+            | Tab::Orphans(_)
+            | Tab::History(_)
+            | Tab::Cache(_)
+            | Tab::FileOwner(_)
+            | Tab::Verify(_)
+            | Tab::LostFiles(_)
+            | Tab::Operations
+            | Tab::SavedQueries(_)
+            | Tab::ColorTheme
+            | Tab::LoggerUi => false,
+            Tab::SavedQuery(tab) => !self
+                .ui
+                .saved_queries
+                .iter()
+                .any(|q| q.name == tab.name && q.pinned),
             Tab::UpgradeList(state) => state.force_close,
             Tab::Pkg(pkg_tab) => pkg_tab.force_close,
         }
@@ -80,7 +137,16 @@ pub enum Tab {
     LocalPkgList(PkgListState),
     RemotePkgList(PkgListState),
     UpgradeList(upgrade_list::State),
-    Pkg(PkgTab),
+    Orphans(orphans::State),
+    History(history::State),
+    Cache(cache::State),
+    FileOwner(file_owner::State),
+    Verify(verify::State),
+    LostFiles(lost_files::State),
+    Operations,
+    Pkg(Box<PkgTab>),
+    SavedQuery(saved_query::SavedQueryTab),
+    SavedQueries(saved_query::ManageState),
     ColorTheme,
     LoggerUi,
 }
@@ -97,24 +163,108 @@ impl Tab {
 pub struct PkgListState {
     query_src: String,
     query: PkgListQuery,
+    query_error: Option<ParseError>,
+    match_mode: MatchMode,
+    completion: query_edit::CompletionState,
+    /// Name for saving the current query
+    save_name: String,
+    /// Only used by the local package list
+    selection: local_pkg_list::Selection,
+    /// The databases the list was last filtered for
+    listed_for: Weak<Dbs>,
 }
 
-fn query_focus(ui: &egui::Ui, re: &egui::Response) {
-    let [ctrl, shift, num2, f] = ui.input(|inp| {
-        [
-            inp.modifiers.ctrl,
-            inp.modifiers.shift,
-            inp.key_pressed(egui::Key::Num2),
-            inp.key_pressed(egui::Key::F),
-        ]
-    });
-    if shift && num2 || ctrl && f {
-        re.request_focus();
+impl PkgListState {
+    /// Filter the list again on the next frame, e.g. to get back to relevance order
+    fn refilter(&mut self, ctx: &egui::Context) {
+        self.listed_for = Weak::new();
+        ctx.request_repaint();
+    }
+}
+
+/// Query edit box along with any parse error.
+///
+/// Returns whether the list has to be filtered again, because the query was changed and
+/// successfully compiled, or because the databases were reloaded.
+fn query_ui(
+    ui: &mut egui::Ui,
+    state: &mut PkgListState,
+    dbs: &Arc<Dbs>,
+    ui_state: &mut SharedUiState,
+    list: PkgListKind,
+) -> bool {
+    let mut recompiled = false;
+    let mut changed = query_edit::query_edit(ui, state, dbs).changed();
+    changed |= saved_query::dropdown_ui(ui, state, ui_state, list);
+    egui::ComboBox::from_id_salt("match_mode")
+        .selected_text(state.match_mode.label())
+        .show_ui(ui, |ui| {
+            for mode in MatchMode::ALL {
+                changed |= ui
+                    .selectable_value(&mut state.match_mode, mode, mode.label())
+                    .changed();
+            }
+        });
+    if changed {
+        match PkgListQuery::compile(&state.query_src, state.match_mode) {
+            Ok(query) => {
+                state.query = query;
+                state.query_error = None;
+                recompiled = true;
+            }
+            Err(e) => state.query_error = Some(e),
+        }
     }
+    if let Some(err) = &state.query_error {
+        ui.label(egui::RichText::new(format!("⚠ {err}")).color(ui.visuals().error_fg_color));
+    }
+    let reloaded = !Weak::ptr_eq(&state.listed_for, &Arc::downgrade(dbs));
+    state.listed_for = Arc::downgrade(dbs);
+    recompiled || reloaded
+}
+
+/// Drop the scores of the filtered items. In fuzzy mode, sort them by score first.
+fn ranked<T>(mut scored: Vec<(T, u32)>, mode: MatchMode) -> Vec<T> {
+    if mode == MatchMode::Fuzzy {
+        scored.sort_by_key(|(_, score)| Reverse(*score));
+    }
+    scored.into_iter().map(|(item, _)| item).collect()
 }
 
-fn query_edit(ui: &mut egui::Ui, s: &mut String) -> egui::Response {
-    let re = ui.add(egui::TextEdit::singleline(s).hint_text("🔍 Query (ctrl+f, @)"));
-    query_focus(ui, &re);
-    re
+/// Lay out `text` with the byte `ranges` highlighted
+fn highlighted(
+    ui: &egui::Ui,
+    text: &str,
+    mut ranges: Vec<Range<usize>>,
+    color: egui::Color32,
+) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let normal = egui::TextFormat::simple(font_id.clone(), color);
+    let highlight = egui::TextFormat {
+        background: ui.visuals().selection.bg_fill,
+        ..egui::TextFormat::simple(font_id, ui.visuals().strong_text_color())
+    };
+    ranges.sort_by_key(|range| range.start);
+    let mut job = egui::text::LayoutJob::default();
+    let mut pos = 0;
+    for range in ranges {
+        // Overlapping ranges are possible if multiple terms match the same text
+        let start = range.start.max(pos);
+        if start >= range.end {
+            continue;
+        }
+        job.append(
+            text.get(pos..start).unwrap_or_default(),
+            0.0,
+            normal.clone(),
+        );
+        job.append(
+            text.get(start..range.end).unwrap_or_default(),
+            0.0,
+            highlight.clone(),
+        );
+        pos = range.end;
+    }
+    job.append(text.get(pos..).unwrap_or_default(), 0.0, normal);
+    job
 }
@@ -0,0 +1,298 @@
+//! Package files in the cache directories, with `paccache`-like cleanups
+
+use {
+    crate::{
+        app::ui::{SharedUiState, cmd::Cmd, ico},
+        packages::{Dbs, PkgRef},
+        pkg_cache::{self, CachedPkg},
+        vercmp::{AbCmp, vercmp},
+    },
+    eframe::egui,
+    egui_extras::{Column, TableBuilder},
+    humansize::format_size_i,
+    smol_str::SmolStr,
+    std::{
+        ops::Range,
+        path::PathBuf,
+        sync::{Arc, Weak, mpsc::Receiver},
+    },
+};
+
+pub struct State {
+    /// Sorted with [`pkg_cache::sort`]
+    pkgs: Vec<CachedPkg>,
+    groups: Vec<Group>,
+    /// The databases the installed versions were looked up in
+    scanned_for: Option<Weak<Dbs>>,
+    recv: Option<Receiver<Vec<CachedPkg>>>,
+    /// How many versions "keep last" keeps of each package
+    keep: usize,
+    /// Cleanup waiting for confirmation
+    cleanup: Option<Cleanup>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            pkgs: Vec::new(),
+            groups: Vec::new(),
+            scanned_for: None,
+            recv: None,
+            // Same default as paccache
+            keep: 3,
+            cleanup: None,
+        }
+    }
+}
+
+/// The cached files of one package
+struct Group {
+    name: SmolStr,
+    /// Range of the files in `State::pkgs`
+    files: Range<usize>,
+    size: u64,
+    /// Version of the installed package, if installed
+    installed: Option<SmolStr>,
+    /// Files older than the installed version
+    n_old: usize,
+}
+
+struct Cleanup {
+    title: String,
+    /// Paths, with their sizes, and the paths of their signatures
+    files: Vec<(PathBuf, u64, Option<PathBuf>)>,
+}
+
+impl State {
+    fn spawn_scan(&mut self, dbs: &Arc<Dbs>) {
+        self.recv = Some(pkg_cache::spawn_scan(dbs.conf.cache_dirs.clone()));
+        self.scanned_for = Some(Arc::downgrade(dbs));
+    }
+    fn set_pkgs(&mut self, pkgs: Vec<CachedPkg>, dbs: &Dbs) {
+        self.groups.clear();
+        let mut start = 0;
+        while let Some(first) = pkgs.get(start) {
+            let len = pkgs
+                .get(start..)
+                .unwrap_or_default()
+                .iter()
+                .take_while(|pkg| pkg.name == first.name)
+                .count();
+            let files = start..start + len;
+            let group_pkgs = pkgs.get(files.clone()).unwrap_or_default();
+            let installed = dbs
+                .local_pkg_by_name(&first.name)
+                .map(|(_, local)| local.desc.version.clone());
+            let n_old = installed.as_ref().map_or(0, |installed| {
+                group_pkgs
+                    .iter()
+                    .filter(|pkg| vercmp(&pkg.version, installed) == AbCmp::BNewer)
+                    .count()
+            });
+            self.groups.push(Group {
+                name: first.name.clone(),
+                size: group_pkgs.iter().map(|pkg| pkg.size).sum(),
+                files,
+                installed,
+                n_old,
+            });
+            start += len;
+        }
+        self.pkgs = pkgs;
+        self.cleanup = None;
+    }
+    /// Cleanup of the files at `indices` of `pkgs`
+    fn cleanup(&self, title: String, indices: &[usize]) -> Cleanup {
+        let files = indices
+            .iter()
+            .filter_map(|&i| self.pkgs.get(i))
+            .map(|pkg| (pkg.path.clone(), pkg.size, pkg.sig_path()))
+            .collect();
+        Cleanup { title, files }
+    }
+    fn uninstalled(&self) -> Vec<usize> {
+        self.groups
+            .iter()
+            .filter(|group| group.installed.is_none())
+            .flat_map(|group| group.files.clone())
+            .collect()
+    }
+}
+
+pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, state: &mut State) {
+    // Not while a cleanup is waiting for confirmation. Once it ran, the reload rescans.
+    if state.cleanup.is_none()
+        && !state
+            .scanned_for
+            .as_ref()
+            .is_some_and(|weak| Weak::ptr_eq(weak, &Arc::downgrade(dbs)))
+    {
+        state.spawn_scan(dbs);
+    }
+    if let Some(recv) = &state.recv
+        && let Ok(pkgs) = recv.try_recv()
+    {
+        state.set_pkgs(pkgs, dbs);
+        state.recv = None;
+    }
+    egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
+        top_panel_ui(ui, dbs, state);
+        ui.add_space(4.0);
+    });
+    cleanup_modal(ui.ctx(), ui_state, state);
+    TableBuilder::new(ui)
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::remainder())
+        .auto_shrink(false)
+        .striped(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .header(18.0, |mut row| {
+            for label in ["Name", "Files", "Size", "Installed", "Older than installed"] {
+                row.col(|ui| {
+                    ui.label(label);
+                });
+            }
+        })
+        .body(|mut body| {
+            body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+            body.rows(22.0, state.groups.len(), |mut row| {
+                let Some(group) = state.groups.get(row.index()) else {
+                    return;
+                };
+                row.col(|ui| match dbs.local_pkg_by_name(&group.name) {
+                    Some((idx, _)) => {
+                        if ui.link(group.name.as_str()).clicked() {
+                            ui_state.cmd.push(Cmd::OpenPkgTab(PkgRef::local(idx)));
+                        }
+                    }
+                    None => {
+                        ui.label(group.name.as_str());
+                    }
+                });
+                row.col(|ui| {
+                    let versions: Vec<&str> = state
+                        .pkgs
+                        .get(group.files.clone())
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|pkg| pkg.version.as_str())
+                        .collect();
+                    ui.label(versions.len().to_string())
+                        .on_hover_text(versions.join("\n"));
+                });
+                row.col(|ui| {
+                    ui.label(format_size_i(group.size, humansize::BINARY));
+                });
+                row.col(|ui| match &group.installed {
+                    Some(ver) => {
+                        ui.label(ver.as_str());
+                    }
+                    None => {
+                        ui.label(
+                            egui::RichText::new("not installed").color(ui.visuals().warn_fg_color),
+                        );
+                    }
+                });
+                row.col(|ui| {
+                    if group.n_old != 0 {
+                        ui.label(group.n_old.to_string());
+                    }
+                });
+            });
+        });
+}
+
+fn top_panel_ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, state: &mut State) {
+    ui.horizontal(|ui| {
+        if state.recv.is_some() {
+            ui.spinner();
+            ui.label("Scanning the package cache...");
+            return;
+        }
+        if ui.button("⟳ Rescan").clicked() {
+            state.spawn_scan(dbs);
+        }
+        let total: u64 = state.groups.iter().map(|group| group.size).sum();
+        ui.label(format!(
+            "{} files of {} packages, {}",
+            state.pkgs.len(),
+            state.groups.len(),
+            format_size_i(total, humansize::BINARY)
+        ));
+        ui.separator();
+        ui.label("Keep last");
+        ui.add(egui::DragValue::new(&mut state.keep).range(0..=100));
+        ui.label("versions");
+        if ui.button(format!("{} Preview", ico::TRASH)).clicked() {
+            let files = pkg_cache::old_versions(&state.pkgs, state.keep);
+            state.cleanup =
+                Some(state.cleanup(format!("Keep the last {} versions", state.keep), &files));
+        }
+        ui.separator();
+        if ui
+            .button(format!("{} Remove uninstalled", ico::TRASH))
+            .clicked()
+        {
+            let files = state.uninstalled();
+            state.cleanup = Some(state.cleanup("Remove uninstalled packages".into(), &files));
+        }
+    });
+}
+
+fn cleanup_modal(ctx: &egui::Context, ui_state: &mut SharedUiState, state: &mut State) {
+    let Some(cleanup) = &state.cleanup else {
+        return;
+    };
+    let files = &cleanup.files;
+    let mut proceed = false;
+    let mut cancel = false;
+    egui::Modal::new("cache_cleanup_modal".into()).show(ctx, |ui| {
+        ui.heading(&cleanup.title);
+        ui.separator();
+        if files.is_empty() {
+            ui.label("Nothing to remove");
+        } else {
+            let avail_height = ui.ctx().content_rect().height();
+            egui::ScrollArea::vertical()
+                .max_height((avail_height * 0.5).round())
+                .show_rows(ui, 18.0, files.len(), |ui, range| {
+                    for (path, _, _) in files.get(range).unwrap_or_default() {
+                        ui.label(path.display().to_string());
+                    }
+                });
+            ui.separator();
+            let freed: u64 = files.iter().map(|(_, size, _)| size).sum();
+            ui.label(format!(
+                "{} files, {} freed",
+                files.len(),
+                format_size_i(freed, humansize::BINARY)
+            ));
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            proceed = ui
+                .add_enabled(!files.is_empty(), egui::Button::new("Proceed"))
+                .clicked();
+            cancel =
+                ui.button("Cancel").clicked() || ui.input(|inp| inp.key_pressed(egui::Key::Escape));
+        });
+    });
+    if proceed {
+        let paths: Vec<String> = files
+            .iter()
+            .flat_map(|(path, _, sig)| std::iter::once(path).chain(sig))
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        let args: Vec<&str> = ["-f", "--"]
+            .into_iter()
+            .chain(paths.iter().map(String::as_str))
+            .collect();
+        ui_state.ops.enqueue_program("rm", &args);
+    }
+    if proceed || cancel {
+        state.cleanup = None;
+    }
+}
@@ -0,0 +1,219 @@
+//! Finding the packages that own a file, like `pacman -Qo` and `pacman -F`
+
+use {
+    crate::{
+        app::ui::{SharedUiState, cmd::Cmd},
+        file_owner::{self, FileQuery, FilesDb, Hit, Owner},
+        packages::{Dbs, PkgRef},
+    },
+    eframe::egui,
+    egui_extras::{Column, TableBuilder},
+    std::{
+        sync::{Arc, Weak, mpsc::Receiver},
+        thread,
+    },
+};
+
+#[derive(Default)]
+pub struct State {
+    input: String,
+    error: Option<String>,
+    /// Also search the `.files` databases of the sync repositories
+    search_sync: bool,
+    files_dbs: Arc<Vec<FilesDb>>,
+    /// The databases the `.files` databases were loaded for.
+    ///
+    /// They are reloaded along with the databases, e.g. after `pacman -Fy` finishes.
+    loaded_for: Option<Weak<Dbs>>,
+    recv: Option<Receiver<Vec<FilesDb>>>,
+    hits: Vec<Hit>,
+    /// Whether there were more hits than listed
+    truncated: bool,
+    /// Search again once the `.files` databases are loaded
+    search_pending: bool,
+    /// Hits of the running search, and whether they were truncated
+    search_recv: Option<Receiver<(Vec<Hit>, bool)>>,
+}
+
+impl State {
+    fn is_loaded(&self, dbs: &Arc<Dbs>) -> bool {
+        self.loaded_for
+            .as_ref()
+            .is_some_and(|weak| Weak::ptr_eq(weak, &Arc::downgrade(dbs)))
+    }
+    fn spawn_load(&mut self, dbs: &Arc<Dbs>) {
+        let (send, recv) = std::sync::mpsc::channel();
+        let dbs2 = dbs.clone();
+        thread::spawn(move || {
+            if let Err(e) = send.send(file_owner::read_files_dbs(&dbs2.conf)) {
+                log::error!("Failed to send files databases: {e}");
+            }
+        });
+        self.recv = Some(recv);
+        self.loaded_for = Some(Arc::downgrade(dbs));
+    }
+    fn search(&mut self, dbs: &Arc<Dbs>) {
+        self.error = None;
+        // A search that's still running is superseded
+        self.search_recv = None;
+        match FileQuery::parse(&self.input) {
+            Ok(Some(query)) => {
+                let (send, recv) = std::sync::mpsc::channel();
+                let dbs2 = dbs.clone();
+                let files_dbs = if self.search_sync {
+                    self.files_dbs.clone()
+                } else {
+                    Arc::default()
+                };
+                thread::spawn(move || {
+                    // Fails if the search was superseded, which is fine
+                    if send
+                        .send(file_owner::search(&query, &dbs2, &files_dbs))
+                        .is_err()
+                    {
+                        log::debug!("File owner search was superseded");
+                    }
+                });
+                self.search_recv = Some(recv);
+            }
+            Ok(None) => {
+                self.hits.clear();
+                self.truncated = false;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+}
+
+pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, state: &mut State) {
+    if state.search_sync && !state.is_loaded(dbs) {
+        state.spawn_load(dbs);
+    }
+    if let Some(recv) = &state.recv
+        && let Ok(files_dbs) = recv.try_recv()
+    {
+        state.files_dbs = Arc::new(files_dbs);
+        state.recv = None;
+        if state.search_pending {
+            state.search_pending = false;
+            state.search(dbs);
+        }
+    }
+    if let Some(recv) = &state.search_recv
+        && let Ok((hits, truncated)) = recv.try_recv()
+    {
+        (state.hits, state.truncated) = (hits, truncated);
+        state.search_recv = None;
+    }
+    egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
+        top_panel_ui(ui, dbs, ui_state, state);
+        ui.add_space(4.0);
+    });
+    TableBuilder::new(ui)
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::remainder())
+        .auto_shrink(false)
+        .striped(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .header(18.0, |mut row| {
+            for label in ["Package", "Repository", "File"] {
+                row.col(|ui| {
+                    ui.label(label);
+                });
+            }
+        })
+        .body(|mut body| {
+            body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+            body.rows(22.0, state.hits.len(), |mut row| {
+                let Some(hit) = state.hits.get(row.index()) else {
+                    return;
+                };
+                let (pkg_ref, name, repo) = match &hit.owner {
+                    Owner::Local(idx) => (
+                        Some(PkgRef::local(*idx)),
+                        dbs.resolve_local(*idx).map(|pkg| pkg.desc.name.clone()),
+                        "local",
+                    ),
+                    Owner::Sync { repo, name } => (
+                        dbs.sync_pkg_in(repo, name),
+                        Some(name.clone()),
+                        repo.as_str(),
+                    ),
+                };
+                row.col(|ui| match (pkg_ref, name) {
+                    (Some(pkg_ref), Some(name)) => {
+                        if ui.link(name.as_str()).clicked() {
+                            ui_state.cmd.push(Cmd::OpenPkgTab(pkg_ref));
+                        }
+                    }
+                    // Sync package missing from the regular sync db, e.g. it's out of date
+                    (None, Some(name)) => {
+                        ui.label(name.as_str());
+                    }
+                    (_, None) => {
+                        ui.label("<unresolved package>");
+                    }
+                });
+                row.col(|ui| {
+                    ui.label(repo);
+                });
+                row.col(|ui| {
+                    ui.label(format!("/{}", hit.path));
+                });
+            });
+        });
+}
+
+fn top_panel_ui(
+    ui: &mut egui::Ui,
+    dbs: &Arc<Dbs>,
+    ui_state: &mut SharedUiState,
+    state: &mut State,
+) {
+    ui.horizontal(|ui| {
+        let re = ui.add(
+            egui::TextEdit::singleline(&mut state.input).hint_text("/usr/bin/ls, ls or libz.so*"),
+        );
+        let submitted = re.lost_focus() && ui.input(|inp| inp.key_pressed(egui::Key::Enter));
+        let mut search = ui.button("🔍 Find owner").clicked() || submitted;
+        search |= ui
+            .checkbox(&mut state.search_sync, "Search repositories")
+            .on_hover_text("Also search the file lists of the sync repositories (pacman -F)")
+            .changed();
+        if ui.button("🔁 Sync file databases (pacman -Fy)").clicked() {
+            ui_state.ops.enqueue(&["-Fy"]);
+        }
+        if search {
+            if state.search_sync && !state.is_loaded(dbs) {
+                state.spawn_load(dbs);
+            }
+            if state.search_sync && state.recv.is_some() {
+                state.search_pending = true;
+            } else {
+                state.search(dbs);
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        if let Some(err) = &state.error {
+            ui.label(egui::RichText::new(format!("⚠ {err}")).color(ui.visuals().error_fg_color));
+            return;
+        }
+        if state.recv.is_some() {
+            ui.spinner();
+            ui.label("Loading file databases...");
+            return;
+        }
+        if state.search_recv.is_some() {
+            ui.spinner();
+            ui.label("Searching...");
+            return;
+        }
+        if state.search_sync && state.files_dbs.is_empty() {
+            ui.label("No file databases found, sync them with pacman -Fy");
+        }
+        let more = if state.truncated { "more than " } else { "" };
+        ui.label(format!("{more}{} matching files", state.hits.len()));
+    });
+}
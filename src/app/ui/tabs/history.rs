@@ -0,0 +1,342 @@
+//! Transaction history from `pacman.log`
+
+use {
+    crate::{
+        app::ui::{
+            SharedUiState,
+            cmd::{Cmd, CmdBuf},
+        },
+        packages::{Dbs, PkgRef},
+        pacman_log::{self, Change, ChangeKind, Outcome, Transaction},
+    },
+    eframe::egui,
+    egui_extras::{Column, TableBuilder},
+    std::{
+        sync::{Arc, Weak, mpsc::Receiver},
+        thread,
+    },
+};
+
+type LogResult = Result<Vec<Transaction>, String>;
+
+/// The parsed log, reloaded along with the databases
+#[derive(Default)]
+pub struct LogCache {
+    /// The databases the log is being or was read for
+    loaded_for: Option<Weak<Dbs>>,
+    result: Option<LogResult>,
+    recv: Option<Receiver<LogResult>>,
+    /// Incremented on every reload, so views can tell when to recompute
+    generation: u64,
+}
+
+impl LogCache {
+    /// Read the log on a background thread, unless it was already read since the databases
+    /// were loaded. The previous result is kept until the new one is ready.
+    fn refresh(&mut self, dbs: &Arc<Dbs>) {
+        if !self
+            .loaded_for
+            .as_ref()
+            .is_some_and(|weak| Weak::ptr_eq(weak, &Arc::downgrade(dbs)))
+        {
+            let (send, recv) = std::sync::mpsc::channel();
+            let path = dbs.conf.log_file.clone();
+            thread::spawn(move || {
+                let result = pacman_log::read(&path)
+                    .map_err(|e| format!("Failed to read {}: {e}", path.display()));
+                if let Err(e) = send.send(result) {
+                    log::error!("Failed to send pacman log: {e}");
+                }
+            });
+            self.recv = Some(recv);
+            self.loaded_for = Some(Arc::downgrade(dbs));
+        }
+        if let Some(recv) = &self.recv
+            && let Ok(result) = recv.try_recv()
+        {
+            self.result = Some(result);
+            self.recv = None;
+            self.generation += 1;
+        }
+    }
+}
+
+/// The transactions of the log, or a spinner or error if there are none to show
+fn transactions_or_status_ui<'log>(
+    ui: &mut egui::Ui,
+    log: &'log LogCache,
+) -> Option<&'log [Transaction]> {
+    match &log.result {
+        Some(Ok(transactions)) => Some(transactions),
+        Some(Err(e)) => {
+            ui.label(egui::RichText::new(format!("⚠ {e}")).color(ui.visuals().error_fg_color));
+            None
+        }
+        None => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Reading pacman.log...");
+            });
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    pkg_filter: String,
+    /// `YYYY-MM-DD`, or a prefix of it
+    from: String,
+    to: String,
+    /// Transaction and change indices of the listed rows, newest first.
+    ///
+    /// The change is `None` for transactions without changes.
+    rows: Vec<(usize, Option<usize>)>,
+    /// Generation of the log the rows were computed for
+    rows_generation: Option<u64>,
+}
+
+impl State {
+    fn filter(&mut self, transactions: &[Transaction]) {
+        let pkg_filter = self.pkg_filter.trim().to_ascii_lowercase();
+        let (from, to) = (self.from.trim(), self.to.trim());
+        self.rows.clear();
+        for (i, transaction) in transactions.iter().enumerate().rev() {
+            let date = transaction.date();
+            let in_range = (from.is_empty() || date >= from)
+                && (to.is_empty() || date.get(..to.len()).is_some_and(|date| date <= to));
+            let has_pkg = pkg_filter.is_empty()
+                || transaction
+                    .changes
+                    .iter()
+                    .any(|change| change.pkg.to_ascii_lowercase().contains(&pkg_filter));
+            if !in_range || !has_pkg {
+                continue;
+            }
+            if transaction.changes.is_empty() {
+                self.rows.push((i, None));
+            }
+            self.rows
+                .extend((0..transaction.changes.len()).map(|j| (i, Some(j))));
+        }
+    }
+}
+
+pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, state: &mut State) {
+    ui_state.pacman_log.refresh(dbs);
+    let log = &ui_state.pacman_log;
+    let Some(transactions) = transactions_or_status_ui(ui, log) else {
+        return;
+    };
+    let mut changed = state.rows_generation != Some(log.generation);
+    egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut state.pkg_filter)
+                        .hint_text("🔍 Package")
+                        .desired_width(160.0),
+                )
+                .changed();
+            ui.label("From");
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut state.from)
+                        .hint_text("YYYY-MM-DD")
+                        .desired_width(90.0),
+                )
+                .changed();
+            ui.label("to");
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut state.to)
+                        .hint_text("YYYY-MM-DD")
+                        .desired_width(90.0),
+                )
+                .changed();
+            ui.label(format!("{} transactions", transactions.len()));
+        });
+        ui.add_space(4.0);
+    });
+    if changed {
+        state.filter(transactions);
+        state.rows_generation = Some(log.generation);
+    }
+    TableBuilder::new(ui)
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::remainder())
+        .auto_shrink(false)
+        .striped(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .header(18.0, |mut row| {
+            for label in ["Date", "Command", "Action", "Package", "Version"] {
+                row.col(|ui| {
+                    ui.label(label);
+                });
+            }
+        })
+        .body(|mut body| {
+            body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+            body.rows(22.0, state.rows.len(), |mut row| {
+                let Some(&(tx_idx, change_idx)) = state.rows.get(row.index()) else {
+                    return;
+                };
+                let Some(transaction) = transactions.get(tx_idx) else {
+                    return;
+                };
+                // Only the first row of a transaction shows its date and command
+                let first = change_idx.is_none_or(|idx| idx == 0);
+                row.col(|ui| {
+                    if first {
+                        ui.label(transaction.date_time());
+                    }
+                });
+                row.col(|ui| {
+                    if first {
+                        command_label(ui, transaction);
+                    }
+                });
+                let Some(change) = change_idx.and_then(|idx| transaction.changes.get(idx)) else {
+                    row.col(|ui| {
+                        ui.weak("<no changes>");
+                    });
+                    return;
+                };
+                row.col(|ui| {
+                    change_kind_label(ui, change.kind);
+                });
+                row.col(|ui| {
+                    pkg_link(ui, &mut ui_state.cmd, dbs, &change.pkg);
+                });
+                row.col(|ui| {
+                    ui.label(versions_text(change));
+                });
+            });
+        });
+}
+
+/// History of a single package, for the package tab
+#[derive(Default)]
+pub struct PkgHistory {
+    /// Transaction and change indices of the changes of the package, newest first
+    rows: Vec<(usize, usize)>,
+    /// Generation of the log the rows were computed for
+    rows_generation: Option<u64>,
+}
+
+pub fn pkg_history_ui(
+    ui: &mut egui::Ui,
+    dbs: &Arc<Dbs>,
+    ui_state: &mut SharedUiState,
+    state: &mut PkgHistory,
+    name: &str,
+) {
+    ui_state.pacman_log.refresh(dbs);
+    let log = &ui_state.pacman_log;
+    let Some(transactions) = transactions_or_status_ui(ui, log) else {
+        return;
+    };
+    if state.rows_generation != Some(log.generation) {
+        state.rows = transactions
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(i, transaction)| {
+                let j = transaction
+                    .changes
+                    .iter()
+                    .position(|change| change.pkg == name)?;
+                Some((i, j))
+            })
+            .collect();
+        state.rows_generation = Some(log.generation);
+    }
+    if state.rows.is_empty() {
+        ui.label("No changes of this package in the log");
+        return;
+    }
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            egui::Grid::new("pkg_history_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for &(i, j) in &state.rows {
+                        let Some(transaction) = transactions.get(i) else {
+                            continue;
+                        };
+                        let Some(change) = transaction.changes.get(j) else {
+                            continue;
+                        };
+                        ui.label(transaction.date_time());
+                        change_kind_label(ui, change.kind);
+                        ui.label(versions_text(change));
+                        let n_others = transaction.changes.len() - 1;
+                        if n_others == 0 {
+                            ui.label("");
+                        } else {
+                            ui.label(format!("with {n_others} other packages"))
+                                .on_hover_ui(|ui| {
+                                    for other in &transaction.changes {
+                                        if other.pkg != name {
+                                            ui.label(other.pkg.as_str());
+                                        }
+                                    }
+                                });
+                        }
+                        command_label(ui, transaction);
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+fn command_label(ui: &mut egui::Ui, transaction: &Transaction) {
+    let cmd = transaction
+        .command
+        .as_deref()
+        .unwrap_or("<unknown command>");
+    let outcome = match transaction.outcome {
+        Outcome::Completed => "",
+        Outcome::Failed => " (failed)",
+        Outcome::Interrupted => " (interrupted)",
+        Outcome::Unfinished => " (unfinished)",
+    };
+    ui.label(egui::RichText::new(format!("{cmd}{outcome}")).monospace());
+}
+
+fn change_kind_label(ui: &mut egui::Ui, kind: ChangeKind) {
+    let color = match kind {
+        ChangeKind::Installed => egui::Color32::LIGHT_GREEN,
+        ChangeKind::Upgraded => egui::Color32::LIGHT_BLUE,
+        ChangeKind::Downgraded => egui::Color32::ORANGE,
+        ChangeKind::Reinstalled => ui.visuals().weak_text_color(),
+        ChangeKind::Removed => ui.visuals().error_fg_color,
+    };
+    ui.label(egui::RichText::new(kind.label()).color(color));
+}
+
+fn versions_text(change: &Change) -> String {
+    match (&change.old_ver, &change.new_ver) {
+        (Some(old), Some(new)) if old != new => format!("{old} ➡ {new}"),
+        (_, Some(ver)) | (Some(ver), None) => ver.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+/// Links to the package if it's installed
+fn pkg_link(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, name: &str) {
+    match dbs.local_pkg_by_name(name) {
+        Some((idx, _)) => {
+            if ui.link(name).clicked() {
+                cmd.push(Cmd::OpenPkgTab(PkgRef::local(idx)));
+            }
+        }
+        None => {
+            ui.label(name);
+        }
+    }
+}
@@ -1,76 +1,187 @@
 use {
-    super::{PkgListQuery, PkgListState},
+    super::{
+        PkgListState,
+        pkg_table::{self, pkg_list_table_builder},
+    },
     crate::{
         app::{
             PkgCache,
             ui::{SharedUiState, cmd::Cmd, ico},
         },
+        config::PkgListKind,
         packages::{Dbs, PkgIdx, PkgRef},
+        pkg_column::PkgColumn,
+        pkg_filter::PkgFilter,
+        query_syntax::{PkgListQuery, StrField},
     },
-    alpacka::InstallReason,
+    alpacka::{InstallReason, Pkg},
     eframe::egui,
-    egui_extras::{Column, TableBody, TableBuilder},
+    egui_extras::TableBody,
+    smol_str::SmolStr,
+    std::{collections::HashSet, sync::Arc},
 };
 
+/// Selected packages of the local package list
+#[derive(Default)]
+pub struct Selection {
+    /// Names are kept instead of indices, since they stay valid when the databases are reloaded
+    names: HashSet<SmolStr>,
+    /// Row where shift-click ranges start
+    anchor: Option<usize>,
+}
+
+impl Selection {
+    /// Update the selection for a click on `row`, according to the held modifiers
+    fn click(&mut self, row: usize, modifiers: egui::Modifiers, filtered: &[PkgIdx], dbs: &Dbs) {
+        let name_of = |row: usize| {
+            let idx = filtered.get(row)?;
+            dbs.resolve_local(*idx).map(|pkg| pkg.desc.name.clone())
+        };
+        if modifiers.shift
+            && let Some(anchor) = self.anchor
+        {
+            let range = anchor.min(row)..=anchor.max(row);
+            self.names.extend(range.filter_map(name_of));
+            return;
+        }
+        let Some(name) = name_of(row) else {
+            return;
+        };
+        if modifiers.command {
+            if !self.names.remove(&name) {
+                self.names.insert(name);
+            }
+        } else {
+            self.names = HashSet::from([name]);
+        }
+        self.anchor = Some(row);
+    }
+    /// Right-clicking a package outside the selection makes it the only selected one
+    fn secondary_click(&mut self, row: usize, name: &SmolStr) {
+        if !self.names.contains(name) {
+            self.names = HashSet::from([name.clone()]);
+            self.anchor = Some(row);
+        }
+    }
+    /// Deselect packages that are filtered out
+    fn retain_listed(&mut self, filtered: &[PkgIdx], dbs: &Dbs) {
+        let listed: HashSet<&str> = filtered
+            .iter()
+            .filter_map(|idx| dbs.resolve_local(*idx))
+            .map(|pkg| pkg.desc.name.as_str())
+            .collect();
+        self.names.retain(|name| listed.contains(name.as_str()));
+        self.anchor = None;
+    }
+    fn select_all(&mut self, filtered: &[PkgIdx], dbs: &Dbs) {
+        self.names = filtered
+            .iter()
+            .filter_map(|idx| dbs.resolve_local(*idx))
+            .map(|pkg| pkg.desc.name.clone())
+            .collect();
+    }
+    fn clear(&mut self) {
+        self.names.clear();
+        self.anchor = None;
+    }
+    /// Names of the selected packages that `keep` accepts, in alphabetical order
+    fn sorted_names(&self, dbs: &Dbs, keep: impl Fn(&Pkg) -> bool) -> Vec<SmolStr> {
+        let mut names: Vec<SmolStr> = self
+            .names
+            .iter()
+            .filter(|name| {
+                dbs.local_pkg_by_name(name)
+                    .is_some_and(|(_, pkg)| keep(pkg))
+            })
+            .cloned()
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
 pub fn ui(
     ui: &mut egui::Ui,
     pkgs: &mut PkgCache,
-    dbs: &Dbs,
+    dbs: &Arc<Dbs>,
     ui_state: &mut SharedUiState,
     tab_state: &mut PkgListState,
 ) {
+    let mut layout = ui_state.table_layouts.local.clone();
+    let mut resort = false;
+    let mut sort_changed = false;
     egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
         ui.horizontal(|ui| {
-            if super::query_edit(ui, &mut tab_state.query_src).changed() {
-                tab_state.query = PkgListQuery::compile(&tab_state.query_src);
-                pkgs.filt_local_pkgs =
-                    dbs.local_pkgs()
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(i, pkg)| {
-                            if tab_state.query.flags.explicitly_installed
-                                && !matches!(pkg.desc.install_reason, InstallReason::Explicit)
-                            {
-                                return None;
-                            }
-                            let filt_lo = tab_state.query.string.to_ascii_lowercase();
-                            (pkg.desc.name.contains(&filt_lo)
-                                || pkg.desc.desc.as_ref().is_some_and(|desc| {
-                                    desc.to_ascii_lowercase().contains(&filt_lo)
-                                })
-                                || pkg
-                                    .desc
-                                    .provides
-                                    .iter()
-                                    .any(|dep| dep.name.contains(&filt_lo)))
-                            .then_some(PkgIdx::from_usize(i))
-                        })
-                        .collect();
+            if super::query_ui(ui, tab_state, dbs, ui_state, PkgListKind::Local) {
+                let filter = PkgFilter::new(dbs);
+                let scored = (0..dbs.local_pkgs().len())
+                    .map(PkgIdx::from_usize)
+                    .filter_map(|idx| {
+                        let score = filter.score(&tab_state.query, PkgRef::local(idx))?;
+                        Some((idx, score))
+                    })
+                    .collect();
+                pkgs.filt_local_pkgs = super::ranked(scored, tab_state.match_mode);
+                tab_state
+                    .selection
+                    .retain_listed(&pkgs.filt_local_pkgs, dbs);
+                resort = true;
             }
             ui.spacing();
             ui.label(format!("{} packages listed", pkgs.filt_local_pkgs.len()));
+            pkg_table::column_chooser_ui(ui, &mut layout);
+            selection_ui(ui, &mut tab_state.selection, &pkgs.filt_local_pkgs, dbs);
         });
         ui.add_space(4.0);
     });
-    pkg_list_table_builder(ui)
+    pkg_list_table_builder(ui, &layout.columns)
+        .sense(egui::Sense::click())
         .header(18.0, |mut row| {
-            row.col(|ui| {
-                ui.label("Name");
-            });
-            row.col(|ui| {
-                ui.label("Version");
-            });
-            row.col(|ui| {
-                ui.label("Description");
-            });
+            sort_changed = pkg_table::header_ui(&mut row, &mut layout);
         })
-        .body(|body| table_body_ui(body, pkgs, dbs, ui_state));
+        .body(|body| {
+            table_body_ui(body, pkgs, dbs, ui_state, tab_state, &layout.columns);
+        });
+    if sort_changed && layout.sort.is_none() {
+        // Back to relevance order
+        tab_state.refilter(ui.ctx());
+    } else if resort || sort_changed {
+        pkg_table::sort_pkgs(&mut pkgs.filt_local_pkgs, dbs, &layout, PkgRef::local);
+        tab_state.selection.anchor = None;
+    }
+    ui_state.table_layouts.local = layout;
+}
+
+fn selection_ui(ui: &mut egui::Ui, selection: &mut Selection, filtered: &[PkgIdx], dbs: &Dbs) {
+    ui.separator();
+    ui.label(format!("{} selected", selection.names.len()))
+        .on_hover_text("Ctrl+click to toggle, shift+click to select a range");
+    if ui.button("Select all").clicked() {
+        selection.select_all(filtered, dbs);
+    }
+    if ui
+        .add_enabled(
+            !selection.names.is_empty(),
+            egui::Button::new("Clear selection"),
+        )
+        .clicked()
+    {
+        selection.clear();
+    }
 }
 
-fn table_body_ui(mut body: TableBody, pkgs: &PkgCache, dbs: &Dbs, ui_state: &mut SharedUiState) {
+fn table_body_ui(
+    mut body: TableBody,
+    pkgs: &PkgCache,
+    dbs: &Dbs,
+    ui_state: &mut SharedUiState,
+    tab_state: &mut PkgListState,
+    columns: &[PkgColumn],
+) {
     body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
     body.rows(22.0, pkgs.filt_local_pkgs.len(), |mut row| {
-        let Some(idx) = pkgs.filt_local_pkgs.get(row.index()) else {
+        let row_idx = row.index();
+        let Some(idx) = pkgs.filt_local_pkgs.get(row_idx) else {
             row.col(|ui| {
                 ui.label("<Unresolved package index>");
             });
@@ -82,66 +193,141 @@ fn table_body_ui(mut body: TableBody, pkgs: &PkgCache, dbs: &Dbs, ui_state: &mut
             });
             return;
         };
-        row.col(|ui| {
-            let mut text = egui::RichText::new(ico::PKG);
-            let hover_text;
-            if matches!(pkg.desc.install_reason, InstallReason::Explicit) {
-                hover_text = "Explicitly installed";
-                text = text.strong();
-            } else {
-                hover_text = "Installed as a depdenency";
-                text = text.weak();
-            }
-            ui.label(text)
-                .on_hover_text(hover_text)
-                .on_hover_cursor(egui::CursorIcon::Help);
-            let re = ui.link(pkg.desc.name.as_str());
-            re.context_menu(|ui| {
-                ui.label([ico::PKG, " ", pkg.desc.name.as_str()].concat());
-                ui.separator();
-                if ui.button([ico::TRASH, " Remove"].concat()).clicked() {
-                    ui_state.cmd.push(Cmd::Rscn(pkg.desc.name.clone()));
-                }
-                match pkg.desc.install_reason {
-                    InstallReason::Explicit => {
-                        if ui
-                            .button(
-                                [ico::NOTE, " Change install reason to \"dependency\""].concat(),
-                            )
-                            .clicked()
-                        {
-                            ui_state.cmd.push(Cmd::AsDep(pkg.desc.name.clone()));
-                        }
-                    }
-                    InstallReason::Dep => {
-                        if ui
-                            .button([ico::NOTE, " Change install reason to \"explicit\""].concat())
-                            .clicked()
-                        {
-                            ui_state.cmd.push(Cmd::AsExplicit(pkg.desc.name.clone()));
-                        }
-                    }
+        let selection = &mut tab_state.selection;
+        row.set_selected(selection.names.contains(&pkg.desc.name));
+        let pkg_ref = PkgRef::local(*idx);
+        let mut link_re = None;
+        for &col in columns {
+            row.col(|ui| {
+                if col == PkgColumn::Name {
+                    link_re = Some(name_cell_ui(ui, ui_state, pkg, *idx, &tab_state.query));
+                } else {
+                    pkg_table::cell_ui(ui, col, dbs, pkg_ref, pkg, &tab_state.query);
                 }
             });
-            if re.clicked() {
-                ui_state.cmd.push(Cmd::OpenPkgTab(PkgRef::local(*idx)));
+        }
+        let re = row.response();
+        if re.clicked() {
+            let modifiers = re.ctx.input(|inp| inp.modifiers);
+            selection.click(row_idx, modifiers, &pkgs.filt_local_pkgs, dbs);
+        }
+        // The name link covers part of the row, so it gets the context menu too
+        for re in std::iter::once(re).chain(link_re) {
+            if re.secondary_clicked() {
+                selection.secondary_click(row_idx, &pkg.desc.name);
             }
-        });
-        row.col(|ui| {
-            ui.label(pkg.desc.version.as_str());
-        });
-        row.col(|ui| {
-            ui.label(pkg.desc.desc.as_deref().unwrap_or("<missing description>"));
-        });
+            re.context_menu(|ui| context_menu_ui(ui, ui_state, selection, dbs));
+        }
     });
 }
 
-pub fn pkg_list_table_builder(ui: &'_ mut egui::Ui) -> TableBuilder<'_> {
-    TableBuilder::new(ui)
-        .column(Column::auto())
-        .column(Column::auto())
-        .column(Column::remainder())
-        .auto_shrink(false)
-        .striped(true)
-        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+/// Returns the response of the name link
+fn name_cell_ui(
+    ui: &mut egui::Ui,
+    ui_state: &mut SharedUiState,
+    pkg: &Pkg,
+    idx: PkgIdx,
+    query: &PkgListQuery,
+) -> egui::Response {
+    let mut text = egui::RichText::new(ico::PKG);
+    let hover_text;
+    if matches!(pkg.desc.install_reason, InstallReason::Explicit) {
+        hover_text = "Explicitly installed";
+        text = text.strong();
+    } else {
+        hover_text = "Installed as a depdenency";
+        text = text.weak();
+    }
+    ui.label(text)
+        .on_hover_text(hover_text)
+        .on_hover_cursor(egui::CursorIcon::Help);
+    let name = pkg.desc.name.as_str();
+    let re = ui.link(super::highlighted(
+        ui,
+        name,
+        query.highlights(StrField::Name, name),
+        ui.visuals().hyperlink_color,
+    ));
+    if re.clicked() {
+        ui_state.cmd.push(Cmd::OpenPkgTab(PkgRef::local(idx)));
+    }
+    re
+}
+
+/// Actions on all selected packages
+fn context_menu_ui(
+    ui: &mut egui::Ui,
+    ui_state: &mut SharedUiState,
+    selection: &mut Selection,
+    dbs: &Dbs,
+) {
+    let names = selection.sorted_names(dbs, |_| true);
+    match names.as_slice() {
+        [name] => ui.label([ico::PKG, " ", name.as_str()].concat()),
+        _ => ui.label(format!("{} {} packages", ico::PKG, names.len())),
+    };
+    ui.separator();
+    let mut cmd = None;
+    if ui.button([ico::TRASH, " Remove"].concat()).clicked() {
+        cmd = Some(Cmd::Rscn(names.clone()));
+    }
+    let explicit = selection.sorted_names(dbs, |pkg| {
+        matches!(pkg.desc.install_reason, InstallReason::Explicit)
+    });
+    if !explicit.is_empty()
+        && ui
+            .button([ico::NOTE, " Change install reason to \"dependency\""].concat())
+            .clicked()
+    {
+        cmd = Some(Cmd::AsDep(explicit));
+    }
+    let deps = selection.sorted_names(dbs, |pkg| {
+        matches!(pkg.desc.install_reason, InstallReason::Dep)
+    });
+    if !deps.is_empty()
+        && ui
+            .button([ico::NOTE, " Change install reason to \"explicit\""].concat())
+            .clicked()
+    {
+        cmd = Some(Cmd::AsExplicit(deps));
+    }
+    // Foreign packages have nothing to be reinstalled from
+    let reinstallable = selection.sorted_names(dbs, |pkg| dbs.sync_db_of(&pkg.desc.name).is_some());
+    if !reinstallable.is_empty() {
+        let skipped = names.len() - reinstallable.len();
+        let re = ui.button("⟳ Reinstall");
+        let re = if skipped == 0 {
+            re
+        } else {
+            re.on_hover_text(format!(
+                "{skipped} selected packages aren't in any sync database, and are skipped"
+            ))
+        };
+        if re.clicked() {
+            cmd = Some(Cmd::Reinstall(reinstallable));
+        }
+    }
+    if let Some(cmd) = cmd {
+        ui_state.cmd.push(cmd);
+        // The packages change, so the selection would be stale
+        selection.clear();
+        ui.close();
+    }
+}
+
+/// Description label, with the parts matching the query highlighted
+pub fn desc_label(ui: &mut egui::Ui, pkg: &Pkg, query: &PkgListQuery) {
+    match pkg.desc.desc.as_deref() {
+        Some(desc) => {
+            ui.label(super::highlighted(
+                ui,
+                desc,
+                query.highlights(StrField::Desc, desc),
+                ui.visuals().text_color(),
+            ));
+        }
+        None => {
+            ui.label("<missing description>");
+        }
+    }
 }
@@ -0,0 +1,154 @@
+//! Files under system directories that no installed package owns
+
+use {
+    crate::{
+        app::ui::SharedUiState,
+        config::LostFilesConfig,
+        lost_files::{self, LostDir, ScanJob},
+        packages::Dbs,
+    },
+    eframe::egui,
+    humansize::format_size,
+    std::{cmp::Reverse, path::PathBuf, sync::Arc},
+};
+
+#[derive(Default)]
+pub struct State {
+    /// The configured roots, one per line, while being edited
+    roots_text: Option<String>,
+    /// The configured exclusions, one per line, while being edited
+    exclusions_text: Option<String>,
+    error: Option<String>,
+    job: Option<ScanJob>,
+    dirs: Vec<LostDir>,
+    cancelled: bool,
+    sort_by_size: bool,
+}
+
+impl State {
+    fn sort(&mut self) {
+        if self.sort_by_size {
+            self.dirs.sort_by_key(|dir| Reverse(dir.size));
+        } else {
+            self.dirs.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+    }
+}
+
+pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, state: &mut State) {
+    if let Some(job) = &state.job
+        && let Some(dirs) = job.poll()
+    {
+        state.cancelled = job.is_cancelled();
+        state.dirs = dirs;
+        state.job = None;
+        state.sort();
+    }
+    egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
+        egui::CollapsingHeader::new("Settings").show(ui, |ui| {
+            settings_ui(ui, &mut ui_state.lost_files, state);
+        });
+        top_panel_ui(ui, dbs, &ui_state.lost_files, state);
+        ui.add_space(4.0);
+    });
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            for dir in &state.dirs {
+                dir_ui(ui, dir);
+            }
+        });
+}
+
+fn settings_ui(ui: &mut egui::Ui, cfg: &mut LostFilesConfig, state: &mut State) {
+    ui.columns(2, |cols| {
+        let [roots_ui, exclusions_ui] = cols else {
+            return;
+        };
+        roots_ui.label("Directories to scan");
+        let roots = state.roots_text.get_or_insert_with(|| {
+            let roots: Vec<_> = cfg.roots.iter().map(|p| p.display().to_string()).collect();
+            roots.join("\n")
+        });
+        if roots_ui.text_edit_multiline(roots).changed() {
+            cfg.roots = lines(roots).map(PathBuf::from).collect();
+        }
+        exclusions_ui
+            .label("Exclusions")
+            .on_hover_text("Paths to skip, with everything under them. * and ? are wildcards.");
+        let exclusions = state
+            .exclusions_text
+            .get_or_insert_with(|| cfg.exclusions.join("\n"));
+        if exclusions_ui.text_edit_multiline(exclusions).changed() {
+            cfg.exclusions = lines(exclusions).map(String::from).collect();
+        }
+    });
+    if ui.button("Reset to defaults").clicked() {
+        *cfg = LostFilesConfig::default();
+        state.roots_text = None;
+        state.exclusions_text = None;
+    }
+}
+
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty())
+}
+
+fn top_panel_ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, cfg: &LostFilesConfig, state: &mut State) {
+    ui.horizontal(|ui| {
+        if let Some(job) = &state.job {
+            let (scanned, found) = job.progress();
+            ui.spinner();
+            ui.label(format!("Scanned {scanned} files, {found} lost"));
+            if ui.button("Cancel").clicked() {
+                job.cancel();
+            }
+            return;
+        }
+        if ui.button("🔍 Scan").clicked() {
+            match lost_files::compile_exclusions(&cfg.exclusions) {
+                Ok(exclusions) => {
+                    state.error = None;
+                    state.job = Some(ScanJob::spawn(dbs.clone(), cfg.roots.clone(), exclusions));
+                }
+                Err(e) => state.error = Some(format!("Invalid exclusion: {e}")),
+            }
+        }
+        if ui
+            .checkbox(&mut state.sort_by_size, "Largest first")
+            .changed()
+        {
+            state.sort();
+        }
+        if let Some(err) = &state.error {
+            ui.label(egui::RichText::new(format!("⚠ {err}")).color(ui.visuals().error_fg_color));
+            return;
+        }
+        let n_files: usize = state.dirs.iter().map(|dir| dir.files.len()).sum();
+        let size: u64 = state.dirs.iter().map(|dir| dir.size).sum();
+        let cancelled = if state.cancelled { " (cancelled)" } else { "" };
+        ui.label(format!(
+            "{n_files} lost files in {} directories, {}{cancelled}",
+            state.dirs.len(),
+            format_size(size, humansize::BINARY)
+        ));
+    });
+}
+
+fn dir_ui(ui: &mut egui::Ui, dir: &LostDir) {
+    let id = ui.make_persistent_id(("lost_dir", &dir.path));
+    egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+        .show_header(ui, |ui| {
+            ui.label(format!("📁 {}", dir.path.display()));
+            ui.weak(format!("{} files", dir.files.len()));
+            ui.weak(format_size(dir.size, humansize::BINARY));
+        })
+        .body(|ui| {
+            for (name, size) in &dir.files {
+                ui.horizontal(|ui| {
+                    ui.label(&**name);
+                    ui.weak(format_size(*size, humansize::BINARY));
+                });
+            }
+        });
+}
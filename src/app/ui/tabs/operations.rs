@@ -0,0 +1,137 @@
+//! Running, pending and finished root operations
+
+use {
+    crate::{
+        app::ui::{ico, op_queue::OpQueue},
+        util::format_timestamp,
+    },
+    eframe::egui,
+};
+
+enum Action {
+    Swap(usize, usize),
+    Cancel(usize),
+}
+
+pub fn ui(ui: &mut egui::Ui, ops: &mut OpQueue) {
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            ui.heading("Running");
+            match &ops.running {
+                Some(running) => {
+                    ui.horizontal(|ui| {
+                        match running.exit_status() {
+                            Some(status) => ui.label(format!("Exited ({status})")),
+                            None => ui.spinner(),
+                        };
+                        ui.label(cmd_text(running.program, &running.args));
+                    });
+                }
+                None => {
+                    ui.label("<none>");
+                }
+            }
+            ui.separator();
+            ui.heading(format!("Pending ({})", ops.pending.len()));
+            if ops.paused {
+                paused_ui(ui, ops);
+            }
+            pending_ui(ui, ops);
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.heading(format!("History ({})", ops.history.len()));
+                if !ops.history.is_empty() && ui.button("Clear").clicked() {
+                    ops.history.clear();
+                }
+            });
+            history_ui(ui, ops);
+        });
+}
+
+fn paused_ui(ui: &mut egui::Ui, ops: &mut OpQueue) {
+    ui.horizontal(|ui| {
+        ui.label(
+            egui::RichText::new("⏸ Paused after an operation failed")
+                .color(ui.visuals().warn_fg_color),
+        );
+        if ui
+            .add_enabled(ops.running.is_none(), egui::Button::new("▶ Resume"))
+            .on_disabled_hover_text("Dismiss the output of the failed operation first")
+            .clicked()
+        {
+            ops.paused = false;
+        }
+    });
+}
+
+fn pending_ui(ui: &mut egui::Ui, ops: &mut OpQueue) {
+    if ops.pending.is_empty() {
+        ui.label("<none>");
+        return;
+    }
+    let mut action = None;
+    let len = ops.pending.len();
+    egui::Grid::new("pending_ops_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            for (i, op) in ops.pending.iter().enumerate() {
+                ui.label(cmd_text(op.program, &op.args));
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                        action = Some(Action::Swap(i, i - 1));
+                    }
+                    if ui
+                        .add_enabled(i + 1 < len, egui::Button::new("⏷"))
+                        .clicked()
+                    {
+                        action = Some(Action::Swap(i, i + 1));
+                    }
+                    if ui.button(ico::TRASH).on_hover_text("Cancel").clicked() {
+                        action = Some(Action::Cancel(i));
+                    }
+                });
+                ui.end_row();
+            }
+        });
+    match action {
+        Some(Action::Swap(a, b)) => ops.pending.swap(a, b),
+        Some(Action::Cancel(i)) => {
+            ops.pending.remove(i);
+        }
+        None => {}
+    }
+}
+
+fn history_ui(ui: &mut egui::Ui, ops: &OpQueue) {
+    if ops.history.is_empty() {
+        ui.label("<none>");
+        return;
+    }
+    for (i, op) in ops.history.iter().enumerate().rev() {
+        let (icon, status) = match &op.status {
+            Ok(status) if status.success() => ("✔", status.to_string()),
+            Ok(status) => ("✖", status.to_string()),
+            Err(e) => ("✖", e.clone()),
+        };
+        let header = format!(
+            "{icon} {} {} {} ({status})",
+            format_timestamp(op.finished_at),
+            op.program,
+            op.args.join(" ")
+        );
+        egui::CollapsingHeader::new(header)
+            .id_salt(("op_history", i))
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut op.output.as_str())
+                        .code_editor()
+                        .desired_width(f32::INFINITY),
+                );
+            });
+    }
+}
+
+fn cmd_text(program: &str, args: &[String]) -> egui::RichText {
+    egui::RichText::new(format!("{program} {}", args.join(" "))).monospace()
+}
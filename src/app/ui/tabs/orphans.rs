@@ -0,0 +1,226 @@
+//! Dependency-installed packages that nothing needs anymore, like `pacman -Qdt`
+
+use {
+    super::package::{calc_required_by, pkgs_that_optionally_depend_on},
+    crate::{
+        app::ui::{SharedUiState, cmd::Cmd, ico},
+        packages::{Dbs, PkgIdx, PkgRef},
+    },
+    alpacka::InstallReason,
+    eframe::egui,
+    egui_extras::{Column, TableBuilder},
+    humansize::format_size_i,
+    std::{
+        sync::{Arc, Weak, mpsc::Receiver},
+        thread,
+    },
+};
+
+#[derive(Default)]
+pub struct State {
+    /// Also find packages only needed by other orphans
+    deep: bool,
+    orphans: Vec<Orphan>,
+    /// The databases and mode the orphans were computed for
+    computed_for: Option<(Weak<Dbs>, bool)>,
+    recv: Option<Receiver<Vec<PkgIdx>>>,
+}
+
+struct Orphan {
+    idx: PkgIdx,
+    selected: bool,
+}
+
+impl State {
+    fn is_current(&self, dbs: &Arc<Dbs>) -> bool {
+        self.computed_for.as_ref().is_some_and(|(weak, deep)| {
+            Weak::ptr_eq(weak, &Arc::downgrade(dbs)) && *deep == self.deep
+        })
+    }
+    fn spawn_compute(&mut self, dbs: &Arc<Dbs>) {
+        let (send, recv) = std::sync::mpsc::channel();
+        let (dbs2, deep) = (dbs.clone(), self.deep);
+        thread::spawn(move || {
+            if let Err(e) = send.send(find_orphans(&dbs2, deep)) {
+                log::error!("Failed to send orphans: {e}");
+            }
+        });
+        self.recv = Some(recv);
+        self.computed_for = Some((Arc::downgrade(dbs), self.deep));
+    }
+}
+
+/// Installed packages that were installed as dependencies, and aren't required or
+/// optionally required by any other installed package.
+///
+/// In deep mode, packages only needed by other orphans are orphans too, which also
+/// catches dependency cycles that nothing outside the cycle needs.
+fn find_orphans(dbs: &Dbs, deep: bool) -> Vec<PkgIdx> {
+    let pkgs = dbs.local_pkgs();
+    // needed_by[i]: local packages that (optionally) depend on package i
+    let needed_by: Vec<Vec<usize>> = pkgs
+        .iter()
+        .map(|pkg| {
+            let mut needed_by: Vec<usize> = calc_required_by(pkg, dbs, true)
+                .into_iter()
+                .chain(pkgs_that_optionally_depend_on(pkg, dbs, true))
+                .map(|(pkg_ref, _)| pkg_ref.into_components().1.to_usize())
+                .collect();
+            needed_by.sort_unstable();
+            needed_by.dedup();
+            needed_by
+        })
+        .collect();
+    let is_dep = |pkg: &alpacka::Pkg| matches!(pkg.desc.install_reason, InstallReason::Dep);
+    if !deep {
+        return pkgs
+            .iter()
+            .zip(&needed_by)
+            .enumerate()
+            .filter(|(_, (pkg, needed_by))| is_dep(pkg) && needed_by.is_empty())
+            .map(|(i, _)| PkgIdx::from_usize(i))
+            .collect();
+    }
+    // Packages reachable from explicitly installed ones through dependency edges are needed
+    let mut deps_of: Vec<Vec<usize>> = vec![Vec::new(); pkgs.len()];
+    for (dep, needed_by) in needed_by.iter().enumerate() {
+        for &pkg in needed_by {
+            if let Some(deps) = deps_of.get_mut(pkg) {
+                deps.push(dep);
+            }
+        }
+    }
+    let mut needed: Vec<bool> = pkgs.iter().map(|pkg| !is_dep(pkg)).collect();
+    let mut stack: Vec<usize> = (0..pkgs.len())
+        .filter(|&i| needed.get(i) == Some(&true))
+        .collect();
+    while let Some(i) = stack.pop() {
+        for &dep in deps_of.get(i).into_iter().flatten() {
+            if let Some(needed @ false) = needed.get_mut(dep) {
+                *needed = true;
+                stack.push(dep);
+            }
+        }
+    }
+    needed
+        .iter()
+        .enumerate()
+        .filter(|(_, needed)| !**needed)
+        .map(|(i, _)| PkgIdx::from_usize(i))
+        .collect()
+}
+
+pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, state: &mut State) {
+    if !state.is_current(dbs) {
+        state.spawn_compute(dbs);
+    }
+    if let Some(recv) = &state.recv
+        && let Ok(orphans) = recv.try_recv()
+    {
+        state.orphans = orphans
+            .into_iter()
+            .map(|idx| Orphan {
+                idx,
+                selected: true,
+            })
+            .collect();
+        state.recv = None;
+    }
+    egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
+        top_panel_ui(ui, dbs, ui_state, state);
+        ui.add_space(4.0);
+    });
+    TableBuilder::new(ui)
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::remainder())
+        .auto_shrink(false)
+        .striped(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .header(18.0, |mut row| {
+            for label in ["", "Name", "Version", "Installed size", "Description"] {
+                row.col(|ui| {
+                    ui.label(label);
+                });
+            }
+        })
+        .body(|mut body| {
+            body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+            body.rows(22.0, state.orphans.len(), |mut row| {
+                let Some(orphan) = state.orphans.get_mut(row.index()) else {
+                    return;
+                };
+                let Some(pkg) = dbs.resolve_local(orphan.idx) else {
+                    row.col(|ui| {
+                        ui.label("<unresolved package>");
+                    });
+                    return;
+                };
+                row.col(|ui| {
+                    ui.checkbox(&mut orphan.selected, "");
+                });
+                row.col(|ui| {
+                    if ui.link(pkg.desc.name.as_str()).clicked() {
+                        ui_state
+                            .cmd
+                            .push(Cmd::OpenPkgTab(PkgRef::local(orphan.idx)));
+                    }
+                });
+                row.col(|ui| {
+                    ui.label(pkg.desc.version.as_str());
+                });
+                row.col(|ui| {
+                    ui.label(format_size_i(pkg.desc.size, humansize::BINARY));
+                });
+                row.col(|ui| {
+                    ui.label(pkg.desc.desc.as_deref().unwrap_or("<missing description>"));
+                });
+            });
+        });
+}
+
+fn top_panel_ui(ui: &mut egui::Ui, dbs: &Dbs, ui_state: &mut SharedUiState, state: &mut State) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut state.deep, "Deep")
+            .on_hover_text("Also list packages that are only needed by other orphans");
+        if state.recv.is_some() {
+            ui.spinner();
+            ui.label("Finding orphans...");
+            return;
+        }
+        let size_of =
+            |orphan: &&Orphan| dbs.resolve_local(orphan.idx).map_or(0, |pkg| pkg.desc.size);
+        let total: i64 = state.orphans.iter().map(|o| size_of(&o)).sum();
+        let selected: Vec<&Orphan> = state.orphans.iter().filter(|o| o.selected).collect();
+        let selected_size: i64 = selected.iter().map(size_of).sum();
+        ui.label(format!(
+            "{} orphans ({}), {} selected ({} reclaimable)",
+            state.orphans.len(),
+            format_size_i(total, humansize::BINARY),
+            selected.len(),
+            format_size_i(selected_size, humansize::BINARY),
+        ));
+        if ui
+            .add_enabled(
+                !selected.is_empty(),
+                egui::Button::new(format!("{} Remove selected (pacman -Rns)", ico::TRASH)),
+            )
+            .clicked()
+        {
+            let names = selected
+                .iter()
+                .filter_map(|o| dbs.resolve_local(o.idx))
+                .map(|pkg| pkg.desc.name.clone())
+                .collect();
+            ui_state.cmd.push(Cmd::Rns(names));
+        }
+        if ui.button("Select all").clicked() {
+            state.orphans.iter_mut().for_each(|o| o.selected = true);
+        }
+        if ui.button("Select none").clicked() {
+            state.orphans.iter_mut().for_each(|o| o.selected = false);
+        }
+    });
+}
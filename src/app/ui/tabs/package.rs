@@ -1,26 +1,63 @@
 use {
-    super::remote_pkg_list::installed_label_for_remote_pkg,
+    super::{
+        history::{PkgHistory, pkg_history_ui},
+        remote_pkg_list::{install_buttons_ui, installed_label_for_remote_pkg},
+        verify,
+    },
     crate::{
         app::ui::{
             SharedUiState,
             cmd::{Cmd, CmdBuf},
+            ico,
         },
         packages::{DbIdx, Dbs, PkgIdx, PkgRef},
+        pacman_conf::Repo,
+        pkg_cache::{self, CachedPkg},
+        resolver,
         util::deduped_files,
+        vercmp::{AbCmp, vercmp},
     },
     alpacka::{InstallReason, Pkg},
     eframe::egui,
-    humansize::format_size_i,
-    std::process::Command,
+    file_tree::FileTree,
+    humansize::{format_size, format_size_i},
+    smol_str::SmolStr,
+    std::{
+        collections::HashSet,
+        process::Command,
+        sync::{Arc, Weak, mpsc::Receiver},
+    },
 };
 
+mod dep_graph;
+mod dep_tree;
+mod file_tree;
+mod why_installed;
+
 pub struct PkgTab {
     pub id: PkgRef,
     tab: PkgTabTab,
     pub force_close: bool,
     files_filt_string: String,
+    files_view: FilesView,
+    /// The file tree, with sizes from when it was built
+    file_tree: Option<(Weak<Dbs>, FileTree)>,
+    /// The file tree being built, and the databases it's built for
+    file_tree_recv: Option<(Weak<Dbs>, Receiver<FileTree>)>,
     /// Only do local-only dependency resolution
     pub local_only: bool,
+    /// Optional dependencies selected for installation
+    opt_dep_selection: HashSet<SmolStr>,
+    /// What the dependencies resolve to, and the databases they were resolved in
+    resolved: Option<(Weak<Dbs>, Resolved)>,
+    /// Versions of this package in the package cache, scanned when first shown
+    cached_versions: Option<Vec<CachedPkg>>,
+    cache_scan: Option<Receiver<Vec<CachedPkg>>>,
+    dep_tree: dep_tree::State,
+    why_installed: why_installed::State,
+    dep_graph: dep_graph::State,
+    verify: verify::PkgState,
+    history: PkgHistory,
 }
 
 impl PkgTab {
@@ -30,7 +67,19 @@ impl PkgTab {
             tab: PkgTabTab::default(),
             force_close: false,
             files_filt_string: String::new(),
+            files_view: FilesView::default(),
+            file_tree: None,
+            file_tree_recv: None,
             local_only: true,
+            opt_dep_selection: HashSet::new(),
+            resolved: None,
+            cached_versions: None,
+            cache_scan: None,
+            dep_tree: dep_tree::State::default(),
+            why_installed: why_installed::State::default(),
+            dep_graph: dep_graph::State::default(),
+            verify: verify::PkgState::default(),
+            history: PkgHistory::default(),
         }
     }
 }
@@ -39,10 +88,23 @@ impl PkgTab {
 enum PkgTabTab {
     #[default]
     General,
+    DepTree,
+    DepGraph,
+    WhyInstalled,
     Files,
+    History,
+    Cache,
 }
 
-pub fn ui(ui: &mut egui::Ui, dbs: &Dbs, ui_state: &mut SharedUiState, pkg_tab: &mut PkgTab) {
+#[derive(PartialEq, Default)]
+enum FilesView {
+    #[default]
+    Tree,
+    Largest,
+    List,
+}
+
+pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, pkg_tab: &mut PkgTab) {
     if ui.input(|inp| {
         let esc = inp.key_pressed(egui::Key::Escape);
         let ctrl_w = inp.modifiers.ctrl && inp.key_pressed(egui::Key::W);
@@ -53,20 +115,7 @@ pub fn ui(ui: &mut egui::Ui, dbs: &Dbs, ui_state: &mut SharedUiState, pkg_tab: &
     pkg_ui(ui, ui_state, pkg_tab, dbs);
 }
 
-fn db_name_is_arch(name: &str) -> bool {
-    [
-        "core",
-        "extra",
-        "core-testing",
-        "extra-testing",
-        "multilib",
-        "multilib-testing",
-    ]
-    .into_iter()
-    .any(|repo| name == repo)
-}
-
-fn pkg_ui(ui: &mut egui::Ui, ui_state: &mut SharedUiState, pkg_tab: &mut PkgTab, dbs: &Dbs) {
+fn pkg_ui(ui: &mut egui::Ui, ui_state: &mut SharedUiState, pkg_tab: &mut PkgTab, dbs: &Arc<Dbs>) {
     let (db, pkg) = dbs.resolve(pkg_tab.id);
     let Some(db) = db else {
         ui.label("Unresolved database");
@@ -84,38 +133,280 @@ fn pkg_ui(ui: &mut egui::Ui, ui_state: &mut SharedUiState, pkg_tab: &mut PkgTab,
         ui.label(pkg.desc.version.as_str());
         if remote {
             installed_label_for_remote_pkg(ui, ui_state, &pkg.desc, dbs);
+            install_buttons_ui(ui, &mut ui_state.cmd, db_name, &pkg.desc.name, false);
         }
     });
     ui.separator();
     ui.horizontal(|ui| {
         ui.selectable_value(&mut pkg_tab.tab, PkgTabTab::General, "General");
+        ui.selectable_value(&mut pkg_tab.tab, PkgTabTab::DepTree, "Dependency tree");
+        ui.selectable_value(&mut pkg_tab.tab, PkgTabTab::DepGraph, "Graph");
+        if !remote {
+            ui.selectable_value(&mut pkg_tab.tab, PkgTabTab::WhyInstalled, "Why installed");
+        }
         ui.selectable_value(&mut pkg_tab.tab, PkgTabTab::Files, "File list");
+        ui.selectable_value(&mut pkg_tab.tab, PkgTabTab::History, "History");
+        ui.selectable_value(&mut pkg_tab.tab, PkgTabTab::Cache, "Cached versions");
     });
     ui.separator();
     match pkg_tab.tab {
         PkgTabTab::General => general_tab_ui(ui, &mut ui_state.cmd, dbs, pkg, db_name, pkg_tab),
-        PkgTabTab::Files => files_tab_ui(ui, ui_state, pkg_tab, pkg),
+        PkgTabTab::DepTree => dep_tree_tab_ui(ui, &mut ui_state.cmd, dbs, pkg_tab),
+        PkgTabTab::DepGraph => dep_graph::ui(ui, ui_state, dbs, pkg_tab.id, &mut pkg_tab.dep_graph),
+        PkgTabTab::WhyInstalled => {
+            let idx = pkg_tab.id.into_components().1;
+            why_installed::ui(
+                ui,
+                &mut ui_state.cmd,
+                dbs,
+                pkg,
+                idx,
+                &mut pkg_tab.why_installed,
+            );
+        }
+        PkgTabTab::Files => files_tab_ui(ui, ui_state, dbs, pkg_tab, pkg),
+        PkgTabTab::History => {
+            pkg_history_ui(ui, dbs, ui_state, &mut pkg_tab.history, &pkg.desc.name);
+        }
+        PkgTabTab::Cache => cache_tab_ui(ui, &mut ui_state.cmd, dbs, pkg, pkg_tab),
+    }
+}
+
+fn dep_tree_tab_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Arc<Dbs>, pkg_tab: &mut PkgTab) {
+    let state = &mut pkg_tab.dep_tree;
+    let building = state.update(dbs, pkg_tab.id);
+    ui.checkbox(&mut state.with_opt, "Include optional dependencies");
+    if building {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Building the dependency tree...");
+        });
     }
+    // The previous tree stays up while one with other options is built
+    let Some(tree) = &state.tree else {
+        return;
+    };
+    if !building {
+        ui.label(format!(
+            "{} packages, total installed size {}",
+            tree.n_pkgs,
+            format_size_i(tree.closure_size, humansize::BINARY)
+        ));
+    }
+    ui.separator();
+    dep_tree::ui(ui, cmd, dbs, tree);
+}
+
+fn cache_tab_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, pkg: &Pkg, pkg_tab: &mut PkgTab) {
+    if let Some(recv) = &pkg_tab.cache_scan
+        && let Ok(mut cached) = recv.try_recv()
+    {
+        cached.retain(|cached| cached.name == pkg.desc.name);
+        pkg_tab.cached_versions = Some(cached);
+        pkg_tab.cache_scan = None;
+    }
+    let scanning = pkg_tab.cache_scan.is_some();
+    ui.horizontal(|ui| {
+        let rescan = ui
+            .add_enabled(!scanning, egui::Button::new("⟳ Rescan"))
+            .clicked();
+        if rescan || !scanning && pkg_tab.cached_versions.is_none() {
+            pkg_tab.cache_scan = Some(pkg_cache::spawn_scan(dbs.conf.cache_dirs.clone()));
+        }
+        if pkg_tab.cache_scan.is_some() {
+            ui.spinner();
+            ui.label("Scanning the package cache...");
+        }
+    });
+    let Some(versions) = &pkg_tab.cached_versions else {
+        return;
+    };
+    if versions.is_empty() {
+        ui.label("No cached versions of this package");
+        return;
+    }
+    let installed = dbs
+        .local_pkg_by_name(&pkg.desc.name)
+        .map(|(_, local)| local.desc.version.as_str());
+    egui::Grid::new("cached_versions_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            for cached in versions {
+                ui.label(cached.version.as_str());
+                ui.label(cached.arch.as_str());
+                ui.label(format_size_i(cached.size, humansize::BINARY));
+                if cached.has_sig {
+                    ui.label("🔏 signed");
+                } else {
+                    ui.label(
+                        egui::RichText::new("⚠ no signature").color(ui.visuals().warn_fg_color),
+                    );
+                }
+                let action = match installed.map(|ver| vercmp(&cached.version, ver)) {
+                    None => "Install",
+                    Some(AbCmp::Same) => "Reinstall",
+                    Some(AbCmp::ANewer) => "Upgrade",
+                    Some(AbCmp::BNewer) => "Downgrade",
+                };
+                if ui
+                    .button(action)
+                    .on_hover_text(cached.path.display().to_string())
+                    .clicked()
+                {
+                    cmd.push(Cmd::InstallFile {
+                        path: cached.path.clone(),
+                        name: cached.name.clone(),
+                        version: cached.version.clone(),
+                    });
+                }
+                if installed == Some(cached.version.as_str()) {
+                    ui.label("[installed]");
+                }
+                ui.end_row();
+            }
+        });
 }
 
-fn files_tab_ui(ui: &mut egui::Ui, ui_state: &mut SharedUiState, pkg_tab: &mut PkgTab, pkg: &Pkg) {
+fn files_tab_ui(
+    ui: &mut egui::Ui,
+    ui_state: &mut SharedUiState,
+    dbs: &Arc<Dbs>,
+    pkg_tab: &mut PkgTab,
+    pkg: &Pkg,
+) {
+    if let Some((_, idx)) = pkg_tab.id.is_local().then(|| pkg_tab.id.into_components())
+        && verify::pkg_ui(ui, &mut ui_state.cmd, dbs, &mut pkg_tab.verify, idx)
+    {
+        return;
+    }
+    // Only installed packages have their files on disk, so remote ones have no sizes
+    let local = pkg_tab.id.is_local();
+    if !local && pkg_tab.files_view == FilesView::Largest {
+        pkg_tab.files_view = FilesView::Tree;
+    }
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut pkg_tab.files_view, FilesView::Tree, "Tree");
+        if local {
+            ui.selectable_value(&mut pkg_tab.files_view, FilesView::Largest, "Largest files");
+        }
+        ui.selectable_value(&mut pkg_tab.files_view, FilesView::List, "List");
+    });
+    let current = |weak: &Weak<Dbs>| Weak::ptr_eq(weak, &Arc::downgrade(dbs));
+    if let Some((weak, recv)) = &pkg_tab.file_tree_recv
+        && let Ok(tree) = recv.try_recv()
+    {
+        pkg_tab.file_tree = Some((weak.clone(), tree));
+        pkg_tab.file_tree_recv = None;
+    }
+    let tree = pkg_tab
+        .file_tree
+        .as_ref()
+        .filter(|(weak, _)| current(weak))
+        .map(|(_, tree)| tree);
+    if tree.is_none()
+        && !pkg_tab
+            .file_tree_recv
+            .as_ref()
+            .is_some_and(|(weak, _)| current(weak))
+    {
+        let root = local.then(|| dbs.conf.root_dir.clone());
+        let recv = file_tree::spawn_build(root, pkg.files.clone());
+        pkg_tab.file_tree_recv = Some((Arc::downgrade(dbs), recv));
+    }
+    if pkg_tab.files_view == FilesView::Tree {
+        let n_files = pkg.files.iter().filter(|file| !file.ends_with('/')).count();
+        let Some(tree) = tree else {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Building the file tree...");
+            });
+            return;
+        };
+        if tree.sized {
+            ui.label(format!(
+                "{n_files} files, {} on disk",
+                format_size(tree.total_size(), humansize::BINARY)
+            ));
+        } else {
+            ui.label(format!("{n_files} files"));
+        }
+        ui.separator();
+        file_tree::tree_ui(ui, &mut ui_state.error_popup, tree);
+        return;
+    }
     let re = ui.add(
         egui::TextEdit::singleline(&mut pkg_tab.files_filt_string).hint_text("🔍 Filter (ctrl+f)"),
     );
     if ui.input(|inp| inp.key_pressed(egui::Key::F) && inp.modifiers.ctrl) {
         re.request_focus();
     }
-    let files = &pkg.files;
-    let deduped_files = deduped_files(files).filter(|file| {
-        file.to_ascii_lowercase()
-            .contains(&pkg_tab.files_filt_string.to_ascii_lowercase())
-    });
-    for file in deduped_files {
-        let name = format!("/{file}");
-        if ui.link(&name).clicked()
-            && let Err(e) = Command::new("xdg-open").arg(name).status()
-        {
-            ui_state.error_popup = Some(e.to_string());
+    let filt = pkg_tab.files_filt_string.to_ascii_lowercase();
+    let matches = |file: &str| file.to_ascii_lowercase().contains(&filt);
+    if pkg_tab.files_view == FilesView::Largest {
+        match tree {
+            Some(tree) => file_tree::largest_ui(ui, &mut ui_state.error_popup, tree, matches),
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Building the file tree...");
+                });
+            }
+        }
+        return;
+    }
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            for file in deduped_files(&pkg.files).filter(|file| matches(file)) {
+                let path = format!("/{file}");
+                xdg_open_link(ui, &mut ui_state.error_popup, &path, &path);
+            }
+        });
+}
+
+/// A link that opens `path` with `xdg-open`
+fn xdg_open_link(ui: &mut egui::Ui, error_popup: &mut Option<String>, label: &str, path: &str) {
+    if ui.link(label).clicked()
+        && let Err(e) = Command::new("xdg-open").arg(path).status()
+    {
+        *error_popup = Some(e.to_string());
+    }
+}
+
+/// Dependency resolution results of the general tab, which are too slow to redo every frame
+struct Resolved {
+    /// Candidates of each dependency
+    deps: Vec<Vec<resolver::Candidate>>,
+    /// Packages matching each entry of `conflicts`
+    conflicts: Vec<Vec<PkgRef>>,
+    /// Packages matching each entry of `replaces`. Those only match by name.
+    replaces: Vec<Vec<PkgRef>>,
+    installed_conflicts: Vec<PkgIdx>,
+}
+
+impl Resolved {
+    fn new(pkg: &Pkg, dbs: &Dbs) -> Self {
+        let matches = |deps: &[alpacka::Depend], by_name_only: bool| {
+            deps.iter()
+                .map(|dep| {
+                    resolver::candidates(dep, dbs)
+                        .into_iter()
+                        .filter(|c| !(by_name_only && c.via_provides))
+                        .map(|c| c.pkg_ref)
+                        .collect()
+                })
+                .collect()
+        };
+        Self {
+            deps: pkg
+                .desc
+                .depends
+                .iter()
+                .map(|dep| resolver::candidates(dep, dbs))
+                .collect(),
+            conflicts: matches(&pkg.desc.conflicts, false),
+            replaces: matches(&pkg.desc.replaces, true),
+            installed_conflicts: installed_conflicts(pkg, dbs),
         }
     }
 }
@@ -123,11 +414,16 @@ fn files_tab_ui(ui: &mut egui::Ui, ui_state: &mut SharedUiState, pkg_tab: &mut P
 fn general_tab_ui(
     ui: &mut egui::Ui,
     cmd: &mut CmdBuf,
-    dbs: &Dbs,
+    dbs: &Arc<Dbs>,
     pkg: &Pkg,
     db_name: &str,
     pkg_tab: &mut PkgTab,
 ) {
+    let resolved = match pkg_tab.resolved.take() {
+        Some((weak, resolved)) if Weak::ptr_eq(&weak, &Arc::downgrade(dbs)) => resolved,
+        _ => Resolved::new(pkg, dbs),
+    };
+    conflicts_banner_ui(ui, cmd, dbs, &resolved.installed_conflicts);
     ui.label(pkg.desc.desc.as_deref().unwrap_or("<no description>"));
     if let Some(url) = pkg.desc.url.as_deref() {
         ui.horizontal(|ui| {
@@ -135,7 +431,7 @@ fn general_tab_ui(
             ui.hyperlink(url);
         });
     }
-    if db_name_is_arch(db_name) {
+    if dbs.conf.repo(db_name).is_some_and(Repo::is_arch) {
         ui.horizontal(|ui| {
             ui.label("Arch package URL");
             ui.hyperlink(format!(
@@ -159,11 +455,116 @@ fn general_tab_ui(
         InstallReason::Explicit => ui.label("Explicitly installed"),
         InstallReason::Dep => ui.label("Installed as a dependency"),
     };
-    deps_ui(ui, cmd, dbs, pkg);
-    opt_deps_ui(ui, cmd, dbs.local_pkgs(), pkg, dbs);
+    deps_ui(ui, cmd, dbs, pkg, &resolved.deps);
+    opt_deps_ui(ui, cmd, pkg, dbs, &mut pkg_tab.opt_dep_selection);
     required_by_ui(ui, cmd, pkg, dbs, pkg_tab);
     optional_for_ui(ui, cmd, pkg, dbs, pkg_tab.local_only);
     provides_ui(ui, pkg);
+    resolved_deps_ui(
+        ui,
+        cmd,
+        dbs,
+        "Conflicts",
+        &pkg.desc.conflicts,
+        &resolved.conflicts,
+        &pkg.desc.name,
+    );
+    resolved_deps_ui(
+        ui,
+        cmd,
+        dbs,
+        "Replaces",
+        &pkg.desc.replaces,
+        &resolved.replaces,
+        &pkg.desc.name,
+    );
+    pkg_tab.resolved = Some((Arc::downgrade(dbs), resolved));
+}
+
+/// Installed packages, other than this package itself, that conflict with it.
+///
+/// Like pacman, this goes both ways: either of them can list the other in `conflicts`.
+fn installed_conflicts(pkg: &Pkg, dbs: &Dbs) -> Vec<PkgIdx> {
+    dbs.local_pkgs()
+        .iter()
+        .enumerate()
+        .filter(|(_, local)| {
+            local.desc.name != pkg.desc.name
+                && (pkg
+                    .desc
+                    .conflicts
+                    .iter()
+                    .any(|c| resolver::pkg_satisfies(local, c))
+                    || local
+                        .desc
+                        .conflicts
+                        .iter()
+                        .any(|c| resolver::pkg_satisfies(pkg, c)))
+        })
+        .map(|(i, _)| PkgIdx::from_usize(i))
+        .collect()
+}
+
+fn conflicts_banner_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, conflicts: &[PkgIdx]) {
+    if conflicts.is_empty() {
+        return;
+    }
+    ui.horizontal_wrapped(|ui| {
+        ui.label(
+            egui::RichText::new("⚠ Conflicts with installed packages:")
+                .color(ui.visuals().warn_fg_color)
+                .strong(),
+        );
+        for &idx in conflicts {
+            let Some(local) = dbs.resolve_local(idx) else {
+                continue;
+            };
+            if ui.link(local.desc.name.as_str()).clicked() {
+                cmd.push(Cmd::OpenPkgTab(PkgRef::local(idx)));
+            }
+        }
+    });
+    ui.separator();
+}
+
+/// A list of dependency-like entries, each with the packages in `refs` it matches.
+///
+/// Matches named `skip_name`, the package itself, aren't listed.
+fn resolved_deps_ui(
+    ui: &mut egui::Ui,
+    cmd: &mut CmdBuf,
+    dbs: &Dbs,
+    title: &str,
+    deps: &[alpacka::Depend],
+    refs: &[Vec<PkgRef>],
+    skip_name: &str,
+) {
+    ui.heading(format!("{title} ({})", deps.len()));
+    if deps.is_empty() {
+        ui.label("<none>");
+        return;
+    }
+    for (dep, refs) in deps.iter().zip(refs) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label(resolver::dep_string(dep));
+            for &pkg_ref in refs {
+                let (_, Some(matched)) = dbs.resolve(pkg_ref) else {
+                    continue;
+                };
+                if matched.desc.name == skip_name {
+                    continue;
+                }
+                let label = if pkg_ref.is_local() {
+                    format!("{} [installed]", matched.desc.name)
+                } else {
+                    pkg_ref.display(dbs).to_string()
+                };
+                if ui.link(label).clicked() {
+                    cmd.push(Cmd::OpenPkgTab(pkg_ref));
+                }
+            }
+        });
+    }
 }
 
 fn required_by_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, pkg: &Pkg, dbs: &Dbs, pkg_tab: &mut PkgTab) {
@@ -190,7 +591,11 @@ fn required_by_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, pkg: &Pkg, dbs: &Dbs, pkg
     }
 }
 
-fn calc_required_by<'db>(pkg: &Pkg, dbs: &'db Dbs, local_only: bool) -> Vec<(PkgRef, &'db Pkg)> {
+pub fn calc_required_by<'db>(
+    pkg: &Pkg,
+    dbs: &'db Dbs,
+    local_only: bool,
+) -> Vec<(PkgRef, &'db Pkg)> {
     let mut reqs = Vec::new();
     if local_only {
         calc_required_by_inner(pkg, &mut reqs, DbIdx::LOCAL, dbs.local_pkgs());
@@ -249,7 +654,7 @@ fn optional_for_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, pkg: &Pkg, dbs: &Dbs, lo
     }
 }
 
-fn pkgs_that_optionally_depend_on<'db>(
+pub fn pkgs_that_optionally_depend_on<'db>(
     dependency: &Pkg,
     dbs: &'db Dbs,
     local_only: bool,
@@ -285,9 +690,31 @@ fn pkg_optionally_depends_on(pkg: &Pkg, dependency: &Pkg) -> bool {
     alpacka::dep::pkg_matches_opt_dep(&dependency.desc, &pkg.desc)
 }
 
-fn opt_deps_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, local_list: &[Pkg], pkg: &Pkg, dbs: &Dbs) {
+fn opt_deps_ui(
+    ui: &mut egui::Ui,
+    cmd: &mut CmdBuf,
+    pkg: &Pkg,
+    dbs: &Dbs,
+    selection: &mut HashSet<SmolStr>,
+) {
+    let local_list = dbs.local_pkgs();
     let opt_deps = &pkg.desc.opt_depends;
-    ui.heading(format!("Optional dependencies ({})", opt_deps.len()));
+    ui.horizontal(|ui| {
+        ui.heading(format!("Optional dependencies ({})", opt_deps.len()));
+        if !selection.is_empty()
+            && ui
+                .button(format!(
+                    "{} Install {} selected as dependencies",
+                    ico::INSTALL,
+                    selection.len()
+                ))
+                .clicked()
+        {
+            let mut names: Vec<SmolStr> = selection.drain().collect();
+            names.sort_unstable();
+            cmd.push(Cmd::InstallAsDeps(names));
+        }
+    });
     if opt_deps.is_empty() {
         ui.label("<none>");
     } else {
@@ -296,6 +723,17 @@ fn opt_deps_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, local_list: &[Pkg], pkg: &Pk
                 let installed = local_list.iter().enumerate().find_map(|(i, pkg)| {
                     (pkg.desc.name == opt_dep.dep.name).then_some(PkgIdx::from_usize(i))
                 });
+                let name = &opt_dep.dep.name;
+                if installed.is_none() && dbs.remote_pkgs_for_name(name).next().is_some() {
+                    let mut selected = selection.contains(name);
+                    if ui.checkbox(&mut selected, "").changed() {
+                        if selected {
+                            selection.insert(name.clone());
+                        } else {
+                            selection.remove(name);
+                        }
+                    }
+                }
                 if let Some(ref_) = installed {
                     if ui.link(opt_dep.dep.name.as_str()).clicked() {
                         cmd.push(Cmd::OpenPkgTab(PkgRef::local(ref_)));
@@ -330,22 +768,47 @@ fn opt_deps_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, local_list: &[Pkg], pkg: &Pk
     }
 }
 
-fn deps_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, pkg: &Pkg) {
+/// Every package satisfying a dependency, with the chosen one marked
+fn candidates_ui(ui: &mut egui::Ui, dbs: &Dbs, candidates: &[resolver::Candidate]) {
+    ui.label("Satisfied by");
+    for c in candidates {
+        let mark = if c.chosen { "✔" } else { "  " };
+        let provides = if c.via_provides { " (provides)" } else { "" };
+        ui.label(format!("{mark} {}{provides}", c.pkg_ref.display(dbs)));
+    }
+}
+
+/// Dependencies, with `candidates` being the candidates of each of them
+fn deps_ui(
+    ui: &mut egui::Ui,
+    cmd: &mut CmdBuf,
+    dbs: &Dbs,
+    pkg: &Pkg,
+    candidates: &[Vec<resolver::Candidate>],
+) {
     let deps = &pkg.desc.depends;
     ui.heading(format!("Dependencies ({})", deps.len()));
     if deps.is_empty() {
         ui.label("<none>");
     } else {
         ui.horizontal_wrapped(|ui| {
-            for dep in deps {
-                match resolve_dep(dep, dbs) {
+            for (dep, candidates) in deps.iter().zip(candidates) {
+                let chosen = candidates.iter().find(|c| c.chosen).and_then(|c| {
+                    let pkg = dbs.resolve(c.pkg_ref).1?;
+                    Some((c.pkg_ref, pkg))
+                });
+                match chosen {
                     Some((ref_, pkg)) => {
                         let label = if dep.name == pkg.desc.name {
                             dep.name.as_str()
                         } else {
                             &format!("{} ({})", dep.name, pkg.desc.name)
                         };
-                        if ui.link(label).clicked() {
+                        let mut re = ui.link(label);
+                        if candidates.len() > 1 {
+                            re = re.on_hover_ui(|ui| candidates_ui(ui, dbs, candidates));
+                        }
+                        if re.clicked() {
                             cmd.push(Cmd::OpenPkgTab(ref_));
                         }
                     }
@@ -357,23 +820,3 @@ fn deps_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, pkg: &Pkg) {
         });
     }
 }
-
-fn resolve_dep<'db>(dep: &alpacka::Depend, dbs: &'db Dbs) -> Option<(PkgRef, &'db Pkg)> {
-    for (db_i, db) in dbs.all() {
-        for (pkg_i, pkg) in db.pkgs.iter().enumerate() {
-            if pkg.desc.name == dep.name
-                || pkg.desc.provides.iter().any(|dep2| {
-                    // TODO: This might not be correct/enough
-                    dep2.name == dep.name
-                        && dep2.ver.as_ref().map(|v| &v.ver) >= dep.ver.as_ref().map(|v| &v.ver)
-                })
-            {
-                return Some((
-                    PkgRef::from_components(db_i, PkgIdx::from_usize(pkg_i)),
-                    pkg,
-                ));
-            }
-        }
-    }
-    None
-}
@@ -0,0 +1,682 @@
+//! Node-link graph of the dependency neighborhood of a package
+
+use {
+    super::calc_required_by,
+    crate::{
+        app::ui::{SharedUiState, cmd::Cmd, paint_util},
+        packages::{Dbs, PkgRef},
+        resolver,
+    },
+    alpacka::{InstallReason, Pkg},
+    eframe::egui,
+    smol_str::SmolStr,
+    std::{
+        collections::{HashMap, VecDeque},
+        fmt::Write as _,
+        path::PathBuf,
+        sync::{Arc, Weak, mpsc::Receiver},
+        thread,
+    },
+};
+
+const NODE_HEIGHT: f32 = 22.0;
+const NODE_GAP: f32 = 24.0;
+const LAYER_GAP: f32 = 80.0;
+/// Bigger graphs are cut off, they'd be unreadable anyway
+const MAX_NODES: usize = 300;
+
+/// Fill colors for repositories, in repo order
+const REPO_COLORS: [[u8; 3]; 8] = [
+    [0x8a, 0xb4, 0xf8],
+    [0x81, 0xc9, 0x95],
+    [0xfd, 0xd6, 0x63],
+    [0xf2, 0x8b, 0x82],
+    [0xc5, 0x8a, 0xf9],
+    [0x78, 0xd9, 0xec],
+    [0xfc, 0xad, 0x70],
+    [0xff, 0x8b, 0xcb],
+];
+/// Packages that aren't in any repository
+const FOREIGN_COLOR: [u8; 3] = [0xbd, 0xc1, 0xc6];
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Options {
+    /// How many levels of dependencies to follow
+    depth: usize,
+    with_opt: bool,
+    /// Also show the packages that directly require the package
+    required_by: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            depth: 2,
+            with_opt: false,
+            required_by: true,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    opts: Options,
+    graph: Option<Graph>,
+    /// The databases and options the graph is being or was built for
+    built_for: Option<(Weak<Dbs>, Options)>,
+    recv: Option<Receiver<Graph>>,
+    view: View,
+    /// Where to export, without extension
+    export_path: String,
+}
+
+impl State {
+    fn spawn_build(&mut self, dbs: &Arc<Dbs>, root: PkgRef) {
+        // The package refs of the graph are only valid for the databases it was built from
+        if !self
+            .built_for
+            .as_ref()
+            .is_some_and(|(weak, _)| Weak::ptr_eq(weak, &Arc::downgrade(dbs)))
+        {
+            self.graph = None;
+        }
+        let (send, recv) = std::sync::mpsc::channel();
+        let (dbs2, opts) = (dbs.clone(), self.opts);
+        thread::spawn(move || {
+            if let Err(e) = send.send(Graph::build(&dbs2, root, opts)) {
+                log::error!("Failed to send dependency graph: {e}");
+            }
+        });
+        self.recv = Some(recv);
+        self.built_for = Some((Arc::downgrade(dbs), self.opts));
+    }
+}
+
+struct View {
+    /// Pan, in screen space
+    offset: egui::Vec2,
+    zoom: f32,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            offset: egui::Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    /// Whether nodes were left out because of [`MAX_NODES`]
+    truncated: bool,
+}
+
+struct Node {
+    pkg_ref: PkgRef,
+    name: SmolStr,
+    /// Index in repo order and name of the repository the package comes from
+    repo: Option<(usize, SmolStr)>,
+    status: Status,
+    is_root: bool,
+    layer: usize,
+    /// Center, in graph space
+    pos: egui::Pos2,
+    width: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Explicit,
+    Dep,
+    NotInstalled,
+}
+
+struct Edge {
+    /// The dependent package
+    from: usize,
+    /// The dependency
+    to: usize,
+    optional: bool,
+}
+
+impl Graph {
+    fn build(dbs: &Dbs, root: PkgRef, opts: Options) -> Self {
+        let mut graph = Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            truncated: false,
+        };
+        let Some(root_pkg) = dbs.resolve(root).1 else {
+            return graph;
+        };
+        let mut indices = HashMap::new();
+        let root_layer = usize::from(opts.required_by);
+        let Some((root_idx, _)) = graph.add_node(dbs, &mut indices, root, root_pkg, root_layer)
+        else {
+            return graph;
+        };
+        if let Some(root_node) = graph.nodes.get_mut(root_idx) {
+            root_node.is_root = true;
+        }
+        if opts.required_by {
+            for (pkg_ref, pkg) in calc_required_by(root_pkg, dbs, true) {
+                if let Some((idx, _)) = graph.add_node(dbs, &mut indices, pkg_ref, pkg, 0) {
+                    graph.edges.push(Edge {
+                        from: idx,
+                        to: root_idx,
+                        optional: false,
+                    });
+                }
+            }
+        }
+        let mut queue = VecDeque::from([(root_idx, root_pkg, 0)]);
+        while let Some((idx, pkg, depth)) = queue.pop_front() {
+            if depth == opts.depth {
+                continue;
+            }
+            let layer = graph.nodes.get(idx).map_or(0, |node| node.layer);
+            let deps = pkg.desc.depends.iter().map(|dep| (dep, false));
+            let opt_deps = pkg
+                .desc
+                .opt_depends
+                .iter()
+                .filter(|_| opts.with_opt)
+                .map(|opt_dep| (&opt_dep.dep, true));
+            for (dep, optional) in deps.chain(opt_deps) {
+                let Some((dep_ref, dep_pkg)) = resolver::resolve(dep, dbs) else {
+                    continue;
+                };
+                let Some((dep_idx, new)) =
+                    graph.add_node(dbs, &mut indices, dep_ref, dep_pkg, layer + 1)
+                else {
+                    continue;
+                };
+                graph.edges.push(Edge {
+                    from: idx,
+                    to: dep_idx,
+                    optional,
+                });
+                if new {
+                    queue.push_back((dep_idx, dep_pkg, depth + 1));
+                }
+            }
+        }
+        graph.layout();
+        graph
+    }
+    /// Add a node unless it's already there, returning its index and whether it's new
+    fn add_node(
+        &mut self,
+        dbs: &Dbs,
+        indices: &mut HashMap<PkgRef, usize>,
+        pkg_ref: PkgRef,
+        pkg: &Pkg,
+        layer: usize,
+    ) -> Option<(usize, bool)> {
+        if let Some(&idx) = indices.get(&pkg_ref) {
+            return Some((idx, false));
+        }
+        if self.nodes.len() == MAX_NODES {
+            self.truncated = true;
+            return None;
+        }
+        let name = &pkg.desc.name;
+        let repo = if pkg_ref.is_remote() {
+            dbs.resolve(pkg_ref).0.map(|db| db.name.clone())
+        } else {
+            dbs.sync_db_of(name).map(|db| db.name.clone())
+        };
+        let repo = repo.and_then(|repo_name| {
+            let idx = dbs.remotes().position(|(_, db)| db.name == repo_name)?;
+            Some((idx, repo_name))
+        });
+        let status = match dbs.local_pkg_by_name(name) {
+            Some((_, local)) if matches!(local.desc.install_reason, InstallReason::Explicit) => {
+                Status::Explicit
+            }
+            Some(_) => Status::Dep,
+            None => Status::NotInstalled,
+        };
+        let idx = self.nodes.len();
+        self.nodes.push(Node {
+            pkg_ref,
+            name: name.clone(),
+            repo,
+            status,
+            is_root: false,
+            layer,
+            pos: egui::Pos2::ZERO,
+            width: node_width(name),
+        });
+        indices.insert(pkg_ref, idx);
+        Some((idx, true))
+    }
+    /// Layered layout: one row per layer, each node placed near the average position of
+    /// its neighbors in the rows above
+    fn layout(&mut self) {
+        let n_layers = self
+            .nodes
+            .iter()
+            .map(|node| node.layer + 1)
+            .max()
+            .unwrap_or(0);
+        let mut y = 0.0;
+        for layer in 0..n_layers {
+            let mut members: Vec<(usize, f32)> = self
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| node.layer == layer)
+                .map(|(i, _)| (i, self.barycenter(i)))
+                .collect();
+            members.sort_by(|(a_idx, a), (b_idx, b)| {
+                a.total_cmp(b).then_with(|| {
+                    let name = |i: &usize| self.nodes.get(*i).map(|node| &node.name);
+                    name(a_idx).cmp(&name(b_idx))
+                })
+            });
+            let width = |i: usize| self.nodes.get(i).map_or(0.0, |node| node.width);
+            let total: f32 = members
+                .iter()
+                .map(|&(i, _)| width(i) + NODE_GAP)
+                .sum::<f32>()
+                - NODE_GAP;
+            let mut x = -total / 2.0;
+            let positions: Vec<(usize, egui::Pos2)> = members
+                .iter()
+                .map(|&(i, _)| {
+                    let w = width(i);
+                    let pos = egui::pos2(x + w / 2.0, y);
+                    x += w + NODE_GAP;
+                    (i, pos)
+                })
+                .collect();
+            for (i, pos) in positions {
+                if let Some(node) = self.nodes.get_mut(i) {
+                    node.pos = pos;
+                }
+            }
+            y += LAYER_GAP;
+        }
+    }
+    /// Average x of the neighbors of a node in the layers above it
+    fn barycenter(&self, idx: usize) -> f32 {
+        let Some(node) = self.nodes.get(idx) else {
+            return 0.0;
+        };
+        let (sum, count) = self
+            .edges
+            .iter()
+            .filter_map(|edge| match (edge.from == idx, edge.to == idx) {
+                (true, false) => self.nodes.get(edge.to),
+                (false, true) => self.nodes.get(edge.from),
+                _ => None,
+            })
+            .filter(|other| other.layer < node.layer)
+            .fold((0.0, 0.0), |(sum, count), other| {
+                (sum + other.pos.x, count + 1.0)
+            });
+        if count == 0.0 { 0.0 } else { sum / count }
+    }
+    fn rect(node: &Node) -> egui::Rect {
+        egui::Rect::from_center_size(node.pos, egui::vec2(node.width, NODE_HEIGHT))
+    }
+    /// Start and end of the edge, on the borders of the nodes
+    fn edge_points(&self, edge: &Edge) -> Option<(egui::Pos2, egui::Pos2)> {
+        let (from, to) = (self.nodes.get(edge.from)?, self.nodes.get(edge.to)?);
+        let dir = to.pos - from.pos;
+        let clip = |node: &Node| {
+            let scale_x = (node.width / 2.0) / dir.x.abs();
+            let scale_y = (NODE_HEIGHT / 2.0) / dir.y.abs();
+            dir * scale_x.min(scale_y).min(0.5)
+        };
+        Some((from.pos + clip(from), to.pos - clip(to)))
+    }
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph deps {\n");
+        out.push_str("  node [shape=box, style=\"rounded,filled\", fontname=\"sans-serif\"];\n");
+        for node in &self.nodes {
+            let (color, width) = status_stroke(node);
+            _ = writeln!(
+                out,
+                "  \"{}\" [fillcolor=\"{}\", color=\"{}\", penwidth={width}];",
+                dot_escape(&node.name),
+                hex(fill_color(node)),
+                hex(color),
+            );
+        }
+        for edge in &self.edges {
+            let (Some(from), Some(to)) = (self.nodes.get(edge.from), self.nodes.get(edge.to))
+            else {
+                continue;
+            };
+            let style = if edge.optional { " [style=dashed]" } else { "" };
+            _ = writeln!(
+                out,
+                "  \"{}\" -> \"{}\"{style};",
+                dot_escape(&from.name),
+                dot_escape(&to.name)
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+    /// Render the graph with the same layout as the view
+    pub fn to_svg(&self) -> String {
+        let margin = 20.0;
+        let bounds = self
+            .nodes
+            .iter()
+            .map(Self::rect)
+            .reduce(egui::Rect::union)
+            .unwrap_or(egui::Rect::ZERO)
+            .expand(margin);
+        let mut out = String::new();
+        _ = writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.1} {:.1} {:.1} {:.1}\" \
+             font-family=\"sans-serif\" font-size=\"12\">",
+            bounds.min.x,
+            bounds.min.y,
+            bounds.width(),
+            bounds.height()
+        );
+        out.push_str(
+            "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+             markerWidth=\"8\" markerHeight=\"8\" orient=\"auto-start-reverse\">\
+             <path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#808080\"/></marker></defs>\n",
+        );
+        for edge in &self.edges {
+            let Some((a, b)) = self.edge_points(edge) else {
+                continue;
+            };
+            let dash = if edge.optional {
+                " stroke-dasharray=\"4 3\""
+            } else {
+                ""
+            };
+            _ = writeln!(
+                out,
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#808080\"{dash} \
+                 marker-end=\"url(#arrow)\"/>",
+                a.x, a.y, b.x, b.y
+            );
+        }
+        for node in &self.nodes {
+            let rect = Self::rect(node);
+            let (stroke, width) = status_stroke(node);
+            _ = writeln!(
+                out,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"4\" \
+                 fill=\"{}\" stroke=\"{}\" stroke-width=\"{width}\"/>",
+                rect.min.x,
+                rect.min.y,
+                rect.width(),
+                rect.height(),
+                hex(fill_color(node)),
+                hex(stroke),
+            );
+            _ = writeln!(
+                out,
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" \
+                 dominant-baseline=\"central\">{}</text>",
+                node.pos.x,
+                node.pos.y,
+                xml_escape(&node.name)
+            );
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+#[expect(clippy::cast_precision_loss)]
+fn node_width(name: &str) -> f32 {
+    // Rough estimate, so the layout doesn't depend on fonts
+    (name.chars().count() as f32).mul_add(7.0, 16.0)
+}
+
+fn fill_color(node: &Node) -> [u8; 3] {
+    node.repo.as_ref().map_or(FOREIGN_COLOR, |(idx, _)| {
+        REPO_COLORS
+            .get(idx % REPO_COLORS.len())
+            .copied()
+            .unwrap_or(FOREIGN_COLOR)
+    })
+}
+
+/// Border color and width, by install reason
+fn status_stroke(node: &Node) -> ([u8; 3], f32) {
+    if node.is_root {
+        return ([0xff, 0xd7, 0x00], 3.0);
+    }
+    match node.status {
+        Status::Explicit => ([0xff, 0xff, 0xff], 2.5),
+        Status::Dep => ([0x40, 0x40, 0x40], 1.0),
+        Status::NotInstalled => ([0xd0, 0x30, 0x30], 1.5),
+    }
+}
+
+fn color32([r, g, b]: [u8; 3]) -> egui::Color32 {
+    egui::Color32::from_rgb(r, g, b)
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn ui(
+    ui: &mut egui::Ui,
+    ui_state: &mut SharedUiState,
+    dbs: &Arc<Dbs>,
+    root: PkgRef,
+    state: &mut State,
+) {
+    if !state
+        .built_for
+        .as_ref()
+        .is_some_and(|(weak, opts)| Weak::ptr_eq(weak, &Arc::downgrade(dbs)) && *opts == state.opts)
+    {
+        state.spawn_build(dbs, root);
+    }
+    if let Some(recv) = &state.recv
+        && let Ok(graph) = recv.try_recv()
+    {
+        state.graph = Some(graph);
+        state.recv = None;
+    }
+    ui.horizontal(|ui| {
+        ui.label("Depth");
+        ui.add(egui::DragValue::new(&mut state.opts.depth).range(1..=10));
+        ui.checkbox(&mut state.opts.with_opt, "Optional dependencies");
+        ui.checkbox(&mut state.opts.required_by, "Required by");
+        if ui.button("Reset view").clicked() {
+            state.view = View::default();
+        }
+        if state.recv.is_some() {
+            ui.spinner();
+            ui.label("Building the graph...");
+        } else if let Some(graph) = &state.graph {
+            let more = if graph.truncated { " (truncated)" } else { "" };
+            ui.label(format!("{} packages{more}", graph.nodes.len()));
+        }
+    });
+    // The previous graph stays up while one with other options is built
+    let Some(graph) = &state.graph else {
+        return;
+    };
+    if state.export_path.is_empty()
+        && let Some(root) = graph.nodes.iter().find(|node| node.is_root)
+    {
+        let dir = dirs::home_dir().unwrap_or_default();
+        state.export_path = dir
+            .join(format!("{}-deps", root.name))
+            .to_string_lossy()
+            .into_owned();
+    }
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut state.export_path)
+                .hint_text("Export path, without extension"),
+        );
+        for (label, ext) in [("Export DOT", "dot"), ("Export SVG", "svg")] {
+            if ui.button(label).clicked() {
+                let path = PathBuf::from(format!("{}.{ext}", state.export_path));
+                let contents = if ext == "dot" {
+                    graph.to_dot()
+                } else {
+                    graph.to_svg()
+                };
+                if let Err(e) = std::fs::write(&path, contents) {
+                    ui_state.error_popup = Some(format!("Failed to write {}: {e}", path.display()));
+                }
+            }
+        }
+    });
+    legend_ui(ui, graph);
+    ui.separator();
+    canvas_ui(ui, ui_state, graph, &mut state.view);
+}
+
+fn legend_ui(ui: &mut egui::Ui, graph: &Graph) {
+    let mut repos: Vec<&(usize, SmolStr)> = graph
+        .nodes
+        .iter()
+        .filter_map(|node| node.repo.as_ref())
+        .collect();
+    repos.sort();
+    repos.dedup();
+    ui.horizontal_wrapped(|ui| {
+        for (idx, name) in repos {
+            let color = REPO_COLORS.get(idx % REPO_COLORS.len()).copied();
+            ui.label(egui::RichText::new("■").color(color32(color.unwrap_or(FOREIGN_COLOR))));
+            ui.label(name.as_str());
+        }
+        ui.label(egui::RichText::new("■").color(color32(FOREIGN_COLOR)));
+        ui.label("no repo");
+        ui.separator();
+        ui.label("Border: white = explicit, dark = dependency, red = not installed");
+    });
+}
+
+fn canvas_ui(ui: &mut egui::Ui, ui_state: &mut SharedUiState, graph: &Graph, view: &mut View) {
+    let (response, painter) =
+        ui.allocate_painter(ui.available_size(), egui::Sense::click_and_drag());
+    if response.dragged() {
+        view.offset += response.drag_delta();
+    }
+    let origin = response.rect.center();
+    if response.hovered() {
+        let (scroll, zoom_delta, hover) = ui.input(|inp| {
+            (
+                inp.smooth_scroll_delta.y,
+                inp.zoom_delta(),
+                inp.pointer.hover_pos(),
+            )
+        });
+        let factor = zoom_delta * (scroll * 0.002).exp();
+        if let Some(hover) = hover
+            && (factor - 1.0).abs() > f32::EPSILON
+        {
+            // Keep the point under the pointer in place
+            let graph_pos = (hover - origin - view.offset) / view.zoom;
+            view.zoom = (view.zoom * factor).clamp(0.1, 5.0);
+            view.offset = hover - origin - graph_pos * view.zoom;
+        }
+    }
+    let to_screen = |pos: egui::Pos2| origin + view.offset + pos.to_vec2() * view.zoom;
+    let screen_rect = |node: &Node| {
+        let rect = Graph::rect(node);
+        egui::Rect::from_min_max(to_screen(rect.min), to_screen(rect.max))
+    };
+    for edge in &graph.edges {
+        let Some((a, b)) = graph.edge_points(edge) else {
+            continue;
+        };
+        let color = if edge.optional {
+            ui.visuals().weak_text_color()
+        } else {
+            ui.visuals().text_color()
+        };
+        paint_util::arrow_to(
+            &painter,
+            to_screen(a),
+            to_screen(b),
+            egui::Stroke::new(1.0, color),
+        );
+    }
+    let hover_pos = response.hover_pos();
+    let mut hovered = None;
+    for node in &graph.nodes {
+        let rect = screen_rect(node);
+        let (stroke_color, mut stroke_width) = status_stroke(node);
+        if hover_pos.is_some_and(|pos| rect.contains(pos)) {
+            hovered = Some(node);
+            stroke_width += 1.5;
+        }
+        painter.rect(
+            rect,
+            4.0 * view.zoom,
+            color32(fill_color(node)),
+            egui::Stroke::new(stroke_width * view.zoom, color32(stroke_color)),
+            egui::StrokeKind::Inside,
+        );
+        // Text gets unreadable before that anyway
+        if view.zoom >= 0.3 {
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                node.name.as_str(),
+                egui::FontId::proportional(12.0 * view.zoom),
+                egui::Color32::BLACK,
+            );
+        }
+    }
+    if let Some(node) = hovered {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        if response.clicked() {
+            ui_state.cmd.push(Cmd::OpenPkgTab(node.pkg_ref));
+        }
+    }
+}
+
+#[test]
+fn test_to_dot() {
+    use crate::packages::PkgIdx;
+    let node = |name: &str, status| Node {
+        pkg_ref: PkgRef::local(PkgIdx::from_usize(0)),
+        name: name.into(),
+        repo: None,
+        status,
+        is_root: false,
+        layer: 0,
+        pos: egui::Pos2::ZERO,
+        width: node_width(name),
+    };
+    let graph = Graph {
+        nodes: vec![node("foo", Status::Explicit), node("b\"ar", Status::Dep)],
+        edges: vec![Edge {
+            from: 0,
+            to: 1,
+            optional: true,
+        }],
+        truncated: false,
+    };
+    let dot = graph.to_dot();
+    assert!(dot.contains("\"foo\" [fillcolor=\"#bdc1c6\", color=\"#ffffff\", penwidth=2.5];"));
+    assert!(dot.contains("\"foo\" -> \"b\\\"ar\" [style=dashed];"));
+}
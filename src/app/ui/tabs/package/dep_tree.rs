@@ -0,0 +1,235 @@
+//! Recursive dependency tree of a package, like `pactree`
+
+use {
+    crate::{
+        app::ui::cmd::{Cmd, CmdBuf},
+        packages::{Dbs, PkgRef},
+        resolver,
+    },
+    alpacka::Pkg,
+    eframe::egui,
+    humansize::format_size_i,
+    smol_str::SmolStr,
+    std::{
+        collections::HashSet,
+        sync::{Arc, Weak, mpsc::Receiver},
+        thread,
+    },
+};
+
+#[derive(Default)]
+pub struct State {
+    /// Include optional dependencies
+    pub with_opt: bool,
+    pub tree: Option<DepTree>,
+    /// The databases and `with_opt` the tree is being or was built for
+    built_for: Option<(Weak<Dbs>, bool)>,
+    recv: Option<Receiver<DepTree>>,
+}
+
+impl State {
+    /// Build the tree on a background thread, unless it's already built or being built.
+    ///
+    /// Returns whether it's being built.
+    pub fn update(&mut self, dbs: &Arc<Dbs>, root: PkgRef) -> bool {
+        let same_dbs = |weak: &Weak<Dbs>| Weak::ptr_eq(weak, &Arc::downgrade(dbs));
+        if !self
+            .built_for
+            .as_ref()
+            .is_some_and(|(weak, opt)| same_dbs(weak) && *opt == self.with_opt)
+        {
+            // The package refs of the tree are only valid for the databases it was built from
+            if !self
+                .built_for
+                .as_ref()
+                .is_some_and(|(weak, _)| same_dbs(weak))
+            {
+                self.tree = None;
+            }
+            let (send, recv) = std::sync::mpsc::channel();
+            let (dbs2, with_opt) = (dbs.clone(), self.with_opt);
+            thread::spawn(move || {
+                if let Err(e) = send.send(DepTree::build(&dbs2, root, with_opt)) {
+                    log::error!("Failed to send dependency tree: {e}");
+                }
+            });
+            self.recv = Some(recv);
+            self.built_for = Some((Arc::downgrade(dbs), self.with_opt));
+        }
+        if let Some(recv) = &self.recv
+            && let Ok(tree) = recv.try_recv()
+        {
+            self.tree = Some(tree);
+            self.recv = None;
+        }
+        self.recv.is_some()
+    }
+}
+
+/// The dependency tree, with every package expanded only at its first occurrence
+pub struct DepTree {
+    /// The root is the first node
+    nodes: Vec<Node>,
+    /// Number of distinct packages in the tree, including the root
+    pub n_pkgs: usize,
+    /// Installed size of all the packages in the tree, including the root
+    pub closure_size: i64,
+}
+
+struct Node {
+    /// The dependency as written, which may be provided by a package with another name
+    dep_name: SmolStr,
+    optional: bool,
+    kind: NodeKind,
+    children: Vec<usize>,
+}
+
+enum NodeKind {
+    Pkg(PkgRef),
+    /// Already expanded elsewhere in the tree
+    Duplicate(PkgRef),
+    /// Depends on one of its ancestors
+    Cycle(PkgRef),
+    Unresolved,
+}
+
+impl DepTree {
+    pub fn build(dbs: &Dbs, root: PkgRef, with_opt: bool) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            n_pkgs: 0,
+            closure_size: 0,
+        };
+        if let (_, Some(pkg)) = dbs.resolve(root) {
+            let mut builder = Builder {
+                dbs,
+                with_opt,
+                tree: &mut tree,
+                ancestors: Vec::new(),
+                seen: HashSet::new(),
+            };
+            builder.add(pkg.desc.name.clone(), false, Some((root, pkg)));
+        }
+        tree
+    }
+}
+
+struct Builder<'a> {
+    dbs: &'a Dbs,
+    with_opt: bool,
+    tree: &'a mut DepTree,
+    ancestors: Vec<PkgRef>,
+    seen: HashSet<PkgRef>,
+}
+
+impl<'a> Builder<'a> {
+    /// Add a node and its subtree, returning its index
+    fn add(
+        &mut self,
+        dep_name: SmolStr,
+        optional: bool,
+        resolved: Option<(PkgRef, &'a Pkg)>,
+    ) -> usize {
+        let idx = self.tree.nodes.len();
+        let kind = match resolved {
+            None => NodeKind::Unresolved,
+            Some((pkg_ref, _)) if self.ancestors.contains(&pkg_ref) => NodeKind::Cycle(pkg_ref),
+            Some((pkg_ref, _)) if !self.seen.insert(pkg_ref) => NodeKind::Duplicate(pkg_ref),
+            Some((pkg_ref, _)) => NodeKind::Pkg(pkg_ref),
+        };
+        let expand = matches!(kind, NodeKind::Pkg(_));
+        self.tree.nodes.push(Node {
+            dep_name,
+            optional,
+            kind,
+            children: Vec::new(),
+        });
+        let Some((pkg_ref, pkg)) = resolved.filter(|_| expand) else {
+            return idx;
+        };
+        self.tree.n_pkgs += 1;
+        self.tree.closure_size += pkg.desc.size;
+        self.ancestors.push(pkg_ref);
+        let mut children = Vec::new();
+        for dep in &pkg.desc.depends {
+            children.push(self.add(dep.name.clone(), false, resolver::resolve(dep, self.dbs)));
+        }
+        if self.with_opt {
+            for opt_dep in &pkg.desc.opt_depends {
+                let resolved = resolver::resolve(&opt_dep.dep, self.dbs);
+                children.push(self.add(opt_dep.dep.name.clone(), true, resolved));
+            }
+        }
+        self.ancestors.pop();
+        if let Some(node) = self.tree.nodes.get_mut(idx) {
+            node.children = children;
+        }
+        idx
+    }
+}
+
+pub fn ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, tree: &DepTree) {
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            node_ui(ui, cmd, dbs, tree, 0);
+        });
+}
+
+fn node_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, tree: &DepTree, idx: usize) {
+    let Some(node) = tree.nodes.get(idx) else {
+        return;
+    };
+    if node.children.is_empty() {
+        ui.horizontal(|ui| {
+            ui.add_space(ui.spacing().indent);
+            node_label_ui(ui, cmd, dbs, node);
+        });
+        return;
+    }
+    let id = ui.make_persistent_id(("dep_tree_node", idx));
+    egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, idx == 0)
+        .show_header(ui, |ui| node_label_ui(ui, cmd, dbs, node))
+        .body(|ui| {
+            for &child in &node.children {
+                node_ui(ui, cmd, dbs, tree, child);
+            }
+        });
+}
+
+fn node_label_ui(ui: &mut egui::Ui, cmd: &mut CmdBuf, dbs: &Dbs, node: &Node) {
+    let pkg_ref = match node.kind {
+        NodeKind::Pkg(pkg_ref) | NodeKind::Duplicate(pkg_ref) | NodeKind::Cycle(pkg_ref) => pkg_ref,
+        NodeKind::Unresolved => {
+            ui.label(node.dep_name.as_str());
+            ui.label(egui::RichText::new("⚠ unresolved").color(ui.visuals().error_fg_color));
+            return;
+        }
+    };
+    let (Some(db), Some(pkg)) = dbs.resolve(pkg_ref) else {
+        ui.label(format!("{} <unresolved package>", node.dep_name));
+        return;
+    };
+    let label = if node.dep_name == pkg.desc.name {
+        node.dep_name.to_string()
+    } else {
+        format!("{} ({})", node.dep_name, pkg.desc.name)
+    };
+    if ui.link(label).on_hover_text(&*db.name).clicked() {
+        cmd.push(Cmd::OpenPkgTab(pkg_ref));
+    }
+    ui.label(pkg.desc.version.as_str());
+    ui.weak(format_size_i(pkg.desc.size, humansize::BINARY));
+    if node.optional {
+        ui.weak("optional");
+    }
+    match node.kind {
+        NodeKind::Duplicate(_) => {
+            ui.weak("(already listed)");
+        }
+        NodeKind::Cycle(_) => {
+            ui.label(egui::RichText::new("⟲ cycle").color(ui.visuals().warn_fg_color));
+        }
+        NodeKind::Pkg(_) | NodeKind::Unresolved => {}
+    }
+}
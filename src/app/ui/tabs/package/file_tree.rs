@@ -0,0 +1,290 @@
+//! The files of a package as a directory tree, with their sizes on disk
+
+use {
+    super::xdg_open_link,
+    eframe::egui,
+    egui_extras::{Column, TableBuilder},
+    humansize::format_size,
+    std::{
+        cmp::Reverse,
+        collections::HashMap,
+        path::{Path, PathBuf},
+        sync::mpsc::Receiver,
+        thread,
+    },
+};
+
+pub struct FileTree {
+    /// The root (`/`) is the first node, parents come before their children
+    nodes: Vec<Node>,
+    /// Indices of the file nodes, largest first
+    by_size: Vec<usize>,
+    /// Whether the sizes are known, i.e. the files were stat-ed on disk
+    pub sized: bool,
+}
+
+struct Node {
+    name: Box<str>,
+    /// Relative to `/`
+    path: Box<str>,
+    parent: Option<usize>,
+    is_dir: bool,
+    children: Vec<usize>,
+    /// Non-directory entries in the subtree
+    n_files: usize,
+    /// Size of the subtree on disk, `None` for files that couldn't be stat-ed
+    size: Option<u64>,
+}
+
+impl FileTree {
+    /// Build the tree from the package's file list, where directories end with `/`.
+    ///
+    /// `size_of` gives the size of a file, by path relative to `/`.
+    pub fn build(files: &[Box<str>], size_of: impl Fn(&str) -> Option<u64>) -> Self {
+        let mut nodes = vec![Node {
+            name: "/".into(),
+            path: "".into(),
+            parent: None,
+            is_dir: true,
+            children: Vec::new(),
+            n_files: 0,
+            size: Some(0),
+        }];
+        let mut lookup: HashMap<(usize, &str), usize> = HashMap::new();
+        for file in files {
+            let is_dir = file.ends_with('/');
+            let mut parent = 0;
+            let mut end = 0;
+            let components: Vec<&str> = file.split('/').filter(|c| !c.is_empty()).collect();
+            for (i, component) in components.iter().enumerate() {
+                end += component.len() + 1;
+                let last = i + 1 == components.len();
+                if let Some(&idx) = lookup.get(&(parent, *component)) {
+                    parent = idx;
+                    continue;
+                }
+                let idx = nodes.len();
+                let node_is_dir = !last || is_dir;
+                let path = file.get(..end.min(file.len())).unwrap_or(file);
+                nodes.push(Node {
+                    name: (*component).into(),
+                    path: path.trim_end_matches('/').into(),
+                    parent: Some(parent),
+                    is_dir: node_is_dir,
+                    children: Vec::new(),
+                    n_files: usize::from(!node_is_dir),
+                    size: if node_is_dir { Some(0) } else { size_of(path) },
+                });
+                if let Some(parent_node) = nodes.get_mut(parent) {
+                    parent_node.children.push(idx);
+                }
+                lookup.insert((parent, *component), idx);
+                parent = idx;
+            }
+        }
+        // Children come after their parents, so this visits each subtree before its parent
+        for idx in (1..nodes.len()).rev() {
+            let Some(&Node {
+                parent: Some(parent),
+                n_files,
+                size,
+                ..
+            }) = nodes.get(idx)
+            else {
+                continue;
+            };
+            if let Some(parent) = nodes.get_mut(parent) {
+                parent.n_files += n_files;
+                parent.size = Some(parent.size.unwrap_or(0) + size.unwrap_or(0));
+            }
+        }
+        let mut by_size: Vec<usize> = (0..nodes.len())
+            .filter(|&i| nodes.get(i).is_some_and(|node| !node.is_dir))
+            .collect();
+        by_size.sort_by_key(|&i| Reverse(nodes.get(i).and_then(|node| node.size)));
+        // Directories first, then by name
+        for idx in 0..nodes.len() {
+            let mut children = nodes
+                .get_mut(idx)
+                .map(|node| std::mem::take(&mut node.children))
+                .unwrap_or_default();
+            children.sort_by_cached_key(|&i| nodes.get(i).map(|n| (!n.is_dir, n.name.clone())));
+            if let Some(node) = nodes.get_mut(idx) {
+                node.children = children;
+            }
+        }
+        Self {
+            nodes,
+            by_size,
+            sized: false,
+        }
+    }
+    /// Build from the files as installed under `root`
+    pub fn stat(root: &Path, files: &[Box<str>]) -> Self {
+        Self {
+            sized: true,
+            ..Self::build(files, |path| {
+                std::fs::symlink_metadata(root.join(path))
+                    .ok()
+                    .map(|meta| meta.len())
+            })
+        }
+    }
+    pub fn total_size(&self) -> u64 {
+        self.nodes.first().and_then(|root| root.size).unwrap_or(0)
+    }
+}
+
+/// Build the tree on a background thread.
+///
+/// The files are stat-ed under `root` if given, which only makes sense for installed packages.
+pub fn spawn_build(root: Option<PathBuf>, files: Vec<Box<str>>) -> Receiver<FileTree> {
+    let (send, recv) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let tree = match root {
+            Some(root) => FileTree::stat(&root, &files),
+            None => FileTree::build(&files, |_| None),
+        };
+        if let Err(e) = send.send(tree) {
+            log::error!("Failed to send file tree: {e}");
+        }
+    });
+    recv
+}
+
+pub fn tree_ui(ui: &mut egui::Ui, error_popup: &mut Option<String>, tree: &FileTree) {
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            let Some(root) = tree.nodes.first() else {
+                return;
+            };
+            for &child in &root.children {
+                node_ui(ui, error_popup, tree, child);
+            }
+        });
+}
+
+fn node_ui(ui: &mut egui::Ui, error_popup: &mut Option<String>, tree: &FileTree, idx: usize) {
+    let Some(node) = tree.nodes.get(idx) else {
+        return;
+    };
+    if !node.is_dir {
+        ui.horizontal(|ui| {
+            ui.add_space(ui.spacing().indent);
+            xdg_open_link(ui, error_popup, &node.name, &format!("/{}", node.path));
+            if tree.sized {
+                size_label_ui(ui, node.size);
+            }
+        });
+        return;
+    }
+    let id = ui.make_persistent_id(("file_tree_node", &node.path));
+    egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+        .show_header(ui, |ui| {
+            xdg_open_link(
+                ui,
+                error_popup,
+                &format!("📁 {}/", node.name),
+                &format!("/{}", node.path),
+            );
+            ui.weak(format!("{} files", node.n_files));
+            if tree.sized {
+                size_label_ui(ui, node.size);
+            }
+        })
+        .body(|ui| {
+            for &child in &node.children {
+                node_ui(ui, error_popup, tree, child);
+            }
+        });
+}
+
+fn size_label_ui(ui: &mut egui::Ui, size: Option<u64>) {
+    match size {
+        Some(size) => ui.weak(format_size(size, humansize::BINARY)),
+        None => ui.weak("not on disk"),
+    };
+}
+
+/// The files sorted by size, largest first, optionally filtered by `matches`
+pub fn largest_ui(
+    ui: &mut egui::Ui,
+    error_popup: &mut Option<String>,
+    tree: &FileTree,
+    matches: impl Fn(&str) -> bool,
+) {
+    let files: Vec<&Node> = tree
+        .by_size
+        .iter()
+        .filter_map(|&i| tree.nodes.get(i))
+        .filter(|node| matches(&node.path))
+        .collect();
+    TableBuilder::new(ui)
+        .column(Column::auto())
+        .column(Column::remainder())
+        .auto_shrink(false)
+        .striped(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .header(18.0, |mut row| {
+            for label in ["Size", "File"] {
+                row.col(|ui| {
+                    ui.label(label);
+                });
+            }
+        })
+        .body(|mut body| {
+            body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+            body.rows(22.0, files.len(), |mut row| {
+                let Some(node) = files.get(row.index()) else {
+                    return;
+                };
+                row.col(|ui| size_label_ui(ui, node.size));
+                row.col(|ui| {
+                    let path = format!("/{}", node.path);
+                    xdg_open_link(ui, error_popup, &path, &path);
+                });
+            });
+        });
+}
+
+#[test]
+fn test_build() {
+    let files = [
+        "usr/",
+        "usr/bin/",
+        "usr/bin/foo",
+        "usr/lib/libfoo.so",
+        "usr/share/doc/",
+    ];
+    let files = files.map(Box::from);
+    let tree = FileTree::build(&files, |path| match path {
+        "usr/bin/foo" => Some(100),
+        "usr/lib/libfoo.so" => Some(300),
+        _ => None,
+    });
+    let paths: Vec<&str> = tree.nodes.iter().map(|n| &*n.path).collect();
+    assert_eq!(
+        paths,
+        [
+            "",
+            "usr",
+            "usr/bin",
+            "usr/bin/foo",
+            "usr/lib",
+            "usr/lib/libfoo.so",
+            "usr/share",
+            "usr/share/doc"
+        ]
+    );
+    assert_eq!(tree.total_size(), 400);
+    let usr = tree.nodes.get(1).map(|n| (n.n_files, n.size));
+    assert_eq!(usr, Some((2, Some(400))));
+    let largest: Vec<&str> = tree
+        .by_size
+        .iter()
+        .filter_map(|&i| tree.nodes.get(i))
+        .map(|n| &*n.path)
+        .collect();
+    assert_eq!(largest, ["usr/lib/libfoo.so", "usr/bin/foo"]);
+}
@@ -0,0 +1,286 @@
+//! Paths from explicitly installed packages down to a package, like `pactree -r`
+
+use {
+    super::calc_required_by,
+    crate::{
+        app::ui::cmd::{Cmd, CmdBuf},
+        packages::{Dbs, PkgIdx, PkgRef},
+    },
+    alpacka::InstallReason,
+    eframe::egui,
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Weak, mpsc::Receiver},
+        thread,
+    },
+};
+
+/// Stop looking after this many paths, the number can grow exponentially
+const MAX_PATHS: usize = 1000;
+/// Stop looking after visiting this many packages along the way
+const MAX_STEPS: usize = 100_000;
+
+/// Computed on a background thread when first shown, and again after reloading
+#[derive(Default)]
+pub struct State {
+    why: Option<WhyInstalled>,
+    /// The databases `why` is being or was computed for
+    computed_for: Option<Weak<Dbs>>,
+    recv: Option<Receiver<WhyInstalled>>,
+}
+
+impl State {
+    fn spawn_compute(&mut self, dbs: &Arc<Dbs>, idx: PkgIdx) {
+        let (send, recv) = std::sync::mpsc::channel();
+        let dbs2 = dbs.clone();
+        thread::spawn(move || {
+            if let Err(e) = send.send(WhyInstalled::new(&dbs2, idx)) {
+                log::error!("Failed to send dependency paths: {e}");
+            }
+        });
+        self.recv = Some(recv);
+        self.computed_for = Some(Arc::downgrade(dbs));
+    }
+}
+
+pub struct WhyInstalled {
+    /// Each path starts at an explicitly installed package and ends at the package
+    paths: Vec<Vec<PkgIdx>>,
+    /// Whether the search stopped at [`MAX_PATHS`] paths or [`MAX_STEPS`] steps
+    truncated: bool,
+}
+
+impl WhyInstalled {
+    pub fn new(dbs: &Dbs, idx: PkgIdx) -> Self {
+        let pkgs = dbs.local_pkgs();
+        let mut required_by_cache: HashMap<usize, Vec<usize>> = HashMap::new();
+        let required_by = |i: usize| {
+            required_by_cache
+                .entry(i)
+                .or_insert_with(|| {
+                    pkgs.get(i).map_or_else(Vec::new, |pkg| {
+                        calc_required_by(pkg, dbs, true)
+                            .into_iter()
+                            .map(|(pkg_ref, _)| pkg_ref.into_components().1.to_usize())
+                            .collect()
+                    })
+                })
+                .clone()
+        };
+        let is_explicit = |i: usize| {
+            pkgs.get(i)
+                .is_some_and(|pkg| matches!(pkg.desc.install_reason, InstallReason::Explicit))
+        };
+        let (paths, truncated) = paths_to(
+            idx.to_usize(),
+            required_by,
+            is_explicit,
+            MAX_PATHS,
+            MAX_STEPS,
+        );
+        Self {
+            paths: paths
+                .into_iter()
+                .map(|path| path.into_iter().map(PkgIdx::from_usize).collect())
+                .collect(),
+            truncated,
+        }
+    }
+}
+
+/// All paths from explicit packages down to `target`, following reverse dependencies
+/// upwards and stopping at explicit packages.
+///
+/// Returns whether the search stopped at `max_paths` paths or `max_steps` visited packages.
+fn paths_to(
+    target: usize,
+    mut required_by: impl FnMut(usize) -> Vec<usize>,
+    is_explicit: impl Fn(usize) -> bool,
+    max_paths: usize,
+    max_steps: usize,
+) -> (Vec<Vec<usize>>, bool) {
+    /// A package on the current path upwards from the target
+    struct Frame {
+        idx: usize,
+        /// Requirers left to visit
+        requirers: Vec<usize>,
+        /// A path was found through this package
+        found: bool,
+        /// A requirer was skipped for being on the path, so this package may lead to
+        /// explicit packages on other paths
+        hit_cycle: bool,
+    }
+    let frame = |idx, requirers| Frame {
+        idx,
+        requirers,
+        found: false,
+        hit_cycle: false,
+    };
+    let mut paths = Vec::new();
+    // Packages known not to lead to any explicit package
+    let mut dead_ends = HashSet::new();
+    let mut steps = 0;
+    let mut truncated = false;
+    let mut stack = vec![frame(target, required_by(target))];
+    while let Some(top) = stack.last_mut() {
+        let Some(next) = top.requirers.pop() else {
+            let Some(done) = stack.pop() else {
+                break;
+            };
+            if !done.found && !done.hit_cycle {
+                dead_ends.insert(done.idx);
+            }
+            if let Some(parent) = stack.last_mut() {
+                parent.found |= done.found;
+                parent.hit_cycle |= done.hit_cycle;
+            }
+            continue;
+        };
+        if dead_ends.contains(&next) {
+            continue;
+        }
+        let on_path = stack.iter().any(|frame| frame.idx == next);
+        let Some(top) = stack.last_mut() else {
+            break;
+        };
+        if on_path {
+            top.hit_cycle = true;
+            continue;
+        }
+        steps += 1;
+        if steps > max_steps {
+            truncated = true;
+            break;
+        }
+        if is_explicit(next) {
+            if paths.len() == max_paths {
+                truncated = true;
+                break;
+            }
+            top.found = true;
+            paths.push(
+                std::iter::once(next)
+                    .chain(stack.iter().rev().map(|frame| frame.idx))
+                    .collect(),
+            );
+        } else {
+            let requirers = required_by(next);
+            stack.push(frame(next, requirers));
+        }
+    }
+    paths.sort();
+    (paths, truncated)
+}
+
+pub fn ui(
+    ui: &mut egui::Ui,
+    cmd: &mut CmdBuf,
+    dbs: &Arc<Dbs>,
+    pkg: &alpacka::Pkg,
+    idx: PkgIdx,
+    state: &mut State,
+) {
+    if !state
+        .computed_for
+        .as_ref()
+        .is_some_and(|weak| Weak::ptr_eq(weak, &Arc::downgrade(dbs)))
+    {
+        state.spawn_compute(dbs, idx);
+    }
+    if let Some(recv) = &state.recv
+        && let Ok(why) = recv.try_recv()
+    {
+        state.why = Some(why);
+        state.recv = None;
+    }
+    if matches!(pkg.desc.install_reason, InstallReason::Explicit) {
+        ui.label("Explicitly installed");
+    }
+    if state.recv.is_some() {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Looking for dependency paths...");
+        });
+        return;
+    }
+    let Some(why) = &state.why else {
+        return;
+    };
+    if why.paths.is_empty() {
+        ui.label("No explicitly installed package depends on this package");
+        return;
+    }
+    let more = if why.truncated { "more than " } else { "" };
+    ui.label(format!(
+        "Required through {more}{} paths from explicitly installed packages",
+        why.paths.len()
+    ));
+    ui.separator();
+    egui::ScrollArea::vertical().auto_shrink(false).show_rows(
+        ui,
+        18.0,
+        why.paths.len(),
+        |ui, range| {
+            for path in why.paths.get(range).unwrap_or_default() {
+                ui.horizontal(|ui| {
+                    for (i, &idx) in path.iter().enumerate() {
+                        if i != 0 {
+                            ui.label("➡");
+                        }
+                        let Some(pkg) = dbs.resolve_local(idx) else {
+                            ui.label("<unresolved>");
+                            continue;
+                        };
+                        if ui.link(pkg.desc.name.as_str()).clicked() {
+                            cmd.push(Cmd::OpenPkgTab(PkgRef::local(idx)));
+                        }
+                    }
+                });
+            }
+        },
+    );
+}
+
+#[test]
+fn test_paths_to() {
+    // 3 and 5 are explicit, 2 and 4 depend on each other
+    let required_by = |i| match i {
+        0 => vec![1, 2],
+        1 => vec![3],
+        2 => vec![3, 4],
+        4 => vec![2, 5],
+        _ => vec![],
+    };
+    let is_explicit = |i| i == 3 || i == 5;
+    let (paths, truncated) = paths_to(0, required_by, is_explicit, 10, 100);
+    assert!(!truncated);
+    assert_eq!(paths, [vec![3, 1, 0], vec![3, 2, 0], vec![5, 4, 2, 0]]);
+    let (paths, truncated) = paths_to(0, required_by, is_explicit, 2, 100);
+    assert!(truncated);
+    assert_eq!(paths.len(), 2);
+    assert!(paths.is_sorted());
+    let (_, truncated) = paths_to(0, required_by, is_explicit, 10, 3);
+    assert!(truncated);
+}
+
+#[test]
+fn test_paths_to_dead_ends() {
+    // 1 and 2 both lead to 3, which only leads to 5, and 5 to nothing explicit
+    let mut visited = Vec::new();
+    let required_by = |i| {
+        visited.push(i);
+        match i {
+            0 => vec![1, 2],
+            1 | 2 => vec![3],
+            3 => vec![5],
+            _ => vec![],
+        }
+    };
+    let is_explicit = |i| i == 4;
+    let (paths, truncated) = paths_to(0, required_by, is_explicit, 10, 100);
+    assert!(!truncated);
+    assert!(paths.is_empty());
+    // 3 is only expanded once
+    visited.sort_unstable();
+    assert_eq!(visited, [0, 1, 2, 3, 5]);
+}
@@ -0,0 +1,153 @@
+//! Package list tables with configurable, sortable columns
+
+use {
+    super::local_pkg_list::desc_label,
+    crate::{
+        packages::{Dbs, PkgRef},
+        pkg_column::{PkgColumn, SortDir, TableLayout},
+        query_syntax::PkgListQuery,
+        util::format_timestamp,
+    },
+    alpacka::Pkg,
+    eframe::egui,
+    egui_extras::{Column, TableBuilder, TableRow},
+    humansize::format_size_i,
+};
+
+pub fn pkg_list_table_builder<'a>(ui: &'a mut egui::Ui, columns: &[PkgColumn]) -> TableBuilder<'a> {
+    let mut builder = TableBuilder::new(ui);
+    for (i, col) in columns.iter().enumerate() {
+        let last = i + 1 == columns.len();
+        builder = builder.column(if *col == PkgColumn::Description || last {
+            Column::remainder()
+        } else {
+            Column::auto()
+        });
+    }
+    builder
+        .auto_shrink(false)
+        .striped(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+}
+
+/// Header with clickable column labels for sorting.
+///
+/// Returns whether the sort order changed.
+pub fn header_ui(row: &mut TableRow, layout: &mut TableLayout) -> bool {
+    let mut changed = false;
+    for col in layout.columns.clone() {
+        row.col(|ui| {
+            if !col.sortable() {
+                ui.label(col.label());
+                return;
+            }
+            let arrow = match layout.sort {
+                Some((sort_col, SortDir::Ascending)) if sort_col == col => " ⏶",
+                Some((sort_col, SortDir::Descending)) if sort_col == col => " ⏷",
+                _ => "",
+            };
+            let sorted = !arrow.is_empty();
+            if ui
+                .selectable_label(sorted, format!("{}{arrow}", col.label()))
+                .clicked()
+            {
+                layout.toggle_sort(col);
+                changed = true;
+            }
+        });
+    }
+    changed
+}
+
+/// Menu for showing and hiding columns
+pub fn column_chooser_ui(ui: &mut egui::Ui, layout: &mut TableLayout) {
+    ui.menu_button("Columns", |ui| {
+        for col in PkgColumn::ALL {
+            let mut visible = layout.is_visible(col);
+            // Name links to the package, so it's always shown
+            let enabled = col != PkgColumn::Name;
+            if ui
+                .add_enabled(enabled, egui::Checkbox::new(&mut visible, col.label()))
+                .changed()
+            {
+                layout.set_visible(col, visible);
+            }
+        }
+    });
+}
+
+/// Cell of any column other than `Name`, which is specific to each list
+pub fn cell_ui(
+    ui: &mut egui::Ui,
+    col: PkgColumn,
+    dbs: &Dbs,
+    pkg_ref: PkgRef,
+    pkg: &Pkg,
+    query: &PkgListQuery,
+) {
+    match col {
+        PkgColumn::Name => {
+            ui.label(pkg.desc.name.as_str());
+        }
+        PkgColumn::Version => {
+            ui.label(pkg.desc.version.as_str());
+        }
+        PkgColumn::Description => desc_label(ui, pkg, query),
+        PkgColumn::InstalledSize => {
+            ui.label(format_size_i(pkg.desc.size, humansize::BINARY));
+        }
+        PkgColumn::InstallDate => {
+            ui.label(
+                pkg.desc
+                    .install_date
+                    .map_or_else(|| "-".into(), format_timestamp),
+            );
+        }
+        PkgColumn::BuildDate => {
+            ui.label(format_timestamp(pkg.desc.build_date));
+        }
+        PkgColumn::Repo => {
+            ui.label(repo_of(dbs, pkg_ref, pkg));
+        }
+        PkgColumn::Packager => {
+            ui.label(pkg.desc.packager.as_deref().unwrap_or("-"));
+        }
+        PkgColumn::Arch => {
+            ui.label(pkg.desc.arch.to_string());
+        }
+    }
+}
+
+/// Name of the repository a package comes from.
+///
+/// For installed packages, that's the sync db that has it, if any.
+pub fn repo_of<'db>(dbs: &'db Dbs, pkg_ref: PkgRef, pkg: &Pkg) -> &'db str {
+    let db = if pkg_ref.is_local() {
+        dbs.sync_db_of(&pkg.desc.name)
+    } else {
+        dbs.resolve(pkg_ref).0
+    };
+    db.map_or("", |db| db.name.as_str())
+}
+
+/// Stable sort of `refs` by the sort order of `layout`
+pub fn sort_pkgs<T: Copy>(
+    refs: &mut [T],
+    dbs: &Dbs,
+    layout: &TableLayout,
+    to_ref: impl Fn(T) -> PkgRef,
+) {
+    if layout.sort.is_none() {
+        return;
+    }
+    let key = |item: T| {
+        let pkg_ref = to_ref(item);
+        dbs.resolve(pkg_ref)
+            .1
+            .map(|pkg| (pkg, repo_of(dbs, pkg_ref, pkg)))
+    };
+    refs.sort_by(|&a, &b| match (key(a), key(b)) {
+        (Some(a), Some(b)) => layout.compare(a, b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    });
+}
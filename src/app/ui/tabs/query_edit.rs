@@ -0,0 +1,330 @@
+//! The query edit box, with syntax highlighting and autocompletion
+
+use {
+    super::PkgListState,
+    crate::{
+        packages::Dbs,
+        query_syntax::{self, CompletionKind, Flag, TokenKind},
+    },
+    eframe::egui::{
+        self,
+        text::{CCursor, CCursorRange, LayoutJob},
+    },
+    std::{
+        collections::BTreeSet,
+        ops::Range,
+        sync::{Arc, Weak},
+    },
+};
+
+/// Maximum number of completions listed in the popup
+const MAX_COMPLETIONS: usize = 20;
+
+#[derive(Default)]
+pub struct CompletionState {
+    selected: usize,
+    /// Popup was closed with escape. Reset when the query is edited.
+    dismissed: bool,
+    cache: Option<CachedCompletions>,
+}
+
+/// Completions are looked up again only when the query, cursor or databases change
+struct CachedCompletions {
+    src: String,
+    cursor: usize,
+    dbs: Weak<Dbs>,
+    completions: Option<(Range<usize>, Vec<Completion>)>,
+}
+
+struct Completion {
+    /// Replaces the completion site
+    text: String,
+    detail: Option<&'static str>,
+}
+
+pub fn query_focus(ui: &egui::Ui, re: &egui::Response) {
+    let [ctrl, shift, num2, f] = ui.input(|inp| {
+        [
+            inp.modifiers.ctrl,
+            inp.modifiers.shift,
+            inp.key_pressed(egui::Key::Num2),
+            inp.key_pressed(egui::Key::F),
+        ]
+    });
+    if shift && num2 || ctrl && f {
+        re.request_focus();
+    }
+}
+
+pub fn query_edit(ui: &mut egui::Ui, state: &mut PkgListState, dbs: &Arc<Dbs>) -> egui::Response {
+    let id = ui.make_persistent_id("query_edit");
+    let mut accepted = false;
+    // Completion keys have to be consumed before the text edit sees them
+    if ui.memory(|mem| mem.has_focus(id)) && !state.completion.dismissed {
+        let cursor = egui::TextEdit::load_state(ui.ctx(), id)
+            .and_then(|te_state| te_state.cursor.char_range())
+            .map(|range| range.primary.index);
+        if let Some((span, completions)) = cursor
+            .and_then(|c| cached_completions(&mut state.completion.cache, &state.query_src, c, dbs))
+        {
+            let [up, down, accept, escape] = ui.input_mut(|inp| {
+                let mut consume = |key| inp.consume_key(egui::Modifiers::NONE, key);
+                [
+                    consume(egui::Key::ArrowUp),
+                    consume(egui::Key::ArrowDown),
+                    consume(egui::Key::Tab) || consume(egui::Key::Enter),
+                    consume(egui::Key::Escape),
+                ]
+            });
+            let len = completions.len();
+            let sel = &mut state.completion.selected;
+            *sel = (*sel).min(len - 1);
+            if up {
+                *sel = (*sel + len - 1) % len;
+            }
+            if down {
+                *sel = (*sel + 1) % len;
+            }
+            if escape {
+                state.completion.dismissed = true;
+            }
+            if accept && let Some(completion) = completions.get(*sel) {
+                apply_completion(ui.ctx(), id, &mut state.query_src, span, &completion.text);
+                accepted = true;
+            }
+        }
+    }
+    let error_span = state.query_error.as_ref().map(|err| err.span.clone());
+    let mut layouter = |ui: &egui::Ui, buf: &dyn egui::TextBuffer, wrap_width: f32| {
+        let mut job = highlight_job(ui, buf.as_str(), error_span.as_ref());
+        job.wrap.max_width = wrap_width;
+        ui.painter().layout_job(job)
+    };
+    let out = egui::TextEdit::singleline(&mut state.query_src)
+        .id(id)
+        .hint_text("🔍 Query (ctrl+f, @)")
+        .layouter(&mut layouter)
+        .show(ui);
+    let mut re = out.response;
+    query_focus(ui, &re);
+    if re.changed() {
+        state.completion = CompletionState::default();
+    }
+    if let Some(err) = &state.query_error {
+        re = re.on_hover_text(egui::RichText::new(&err.msg).color(ui.visuals().error_fg_color));
+    }
+    let cursor = out.cursor_range.map(|range| range.primary.index);
+    if re.has_focus()
+        && !state.completion.dismissed
+        && let Some((span, completions)) = cursor
+            .and_then(|c| cached_completions(&mut state.completion.cache, &state.query_src, c, dbs))
+    {
+        let mut clicked = None;
+        egui::Popup::new(id.with("completions"), ui.ctx().clone(), &re, ui.layer_id())
+            .open(true)
+            .width(re.rect.width())
+            .show(|ui| {
+                for (i, completion) in completions.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let selected = i == state.completion.selected;
+                        if ui.selectable_label(selected, &completion.text).clicked() {
+                            clicked = Some(i);
+                        }
+                        if let Some(detail) = completion.detail {
+                            ui.weak(detail);
+                        }
+                    });
+                }
+            });
+        if let Some(completion) = clicked.and_then(|i| completions.get(i)) {
+            apply_completion(ui.ctx(), id, &mut state.query_src, span, &completion.text);
+            re.request_focus();
+            accepted = true;
+        }
+    }
+    if accepted {
+        re.mark_changed();
+    }
+    re
+}
+
+/// Replace `span` of `src` with `text`, and put the cursor after it
+fn apply_completion(
+    ctx: &egui::Context,
+    id: egui::Id,
+    src: &mut String,
+    span: Range<usize>,
+    text: &str,
+) {
+    let end = span.start + text.len();
+    src.replace_range(span, text);
+    let cursor = src.get(..end).map_or(0, |s| s.chars().count());
+    let mut te_state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+    te_state
+        .cursor
+        .set_char_range(Some(CCursorRange::one(CCursor::new(cursor))));
+    te_state.store(ctx, id);
+}
+
+/// [`completions_at`], reusing the last result if nothing changed since
+fn cached_completions<'a>(
+    cache: &'a mut Option<CachedCompletions>,
+    src: &str,
+    cursor: usize,
+    dbs: &Arc<Dbs>,
+) -> Option<(Range<usize>, &'a [Completion])> {
+    let stale = cache.as_ref().is_none_or(|cached| {
+        cached.cursor != cursor
+            || cached.src != src
+            || !Weak::ptr_eq(&cached.dbs, &Arc::downgrade(dbs))
+    });
+    if stale {
+        *cache = Some(CachedCompletions {
+            src: src.to_owned(),
+            cursor,
+            dbs: Arc::downgrade(dbs),
+            completions: completions_at(src, cursor, dbs),
+        });
+    }
+    let (span, completions) = cache.as_ref()?.completions.as_ref()?;
+    Some((span.clone(), completions))
+}
+
+/// Completions at the character position `cursor`, along with the byte span they replace
+fn completions_at(src: &str, cursor: usize, dbs: &Dbs) -> Option<(Range<usize>, Vec<Completion>)> {
+    let cursor = src.char_indices().nth(cursor).map_or(src.len(), |(i, _)| i);
+    let site = query_syntax::completion_site(src, cursor)?;
+    let partial = site.partial;
+    let has_prefix = |s: &str| {
+        s.len() > partial.len()
+            && s.get(..partial.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(partial))
+    };
+    let completions: Vec<Completion> = match site.kind {
+        CompletionKind::Flag => Flag::ALL
+            .into_iter()
+            .filter(|flag| has_prefix(flag.name()))
+            .map(|flag| Completion {
+                text: format!("@{}", flag.name()),
+                detail: Some(flag.description()),
+            })
+            .collect(),
+        CompletionKind::FieldValue("repo") => dbs
+            .all()
+            .map(|(_, db)| db.name.as_str())
+            .filter(|name| has_prefix(name))
+            .map(|name| Completion {
+                text: name.to_owned(),
+                detail: None,
+            })
+            .collect(),
+        CompletionKind::FieldValue("name") => {
+            // Package names are lowercase, and the dbs are sorted by name,
+            // so the matches of each db are a contiguous range
+            let prefix = partial.to_ascii_lowercase();
+            dbs.all()
+                .flat_map(|(_, db)| {
+                    let start = db
+                        .pkgs
+                        .partition_point(|pkg| pkg.desc.name.as_str() < prefix.as_str());
+                    db.pkgs
+                        .iter()
+                        .skip(start)
+                        .map(|pkg| pkg.desc.name.as_str())
+                        .take_while(|name| name.starts_with(&prefix))
+                        .filter(|name| name.len() > prefix.len())
+                        .take(MAX_COMPLETIONS)
+                })
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .take(MAX_COMPLETIONS)
+                .map(|name| Completion {
+                    text: name.to_owned(),
+                    detail: None,
+                })
+                .collect()
+        }
+        CompletionKind::FieldValue(_) => Vec::new(),
+    };
+    (!completions.is_empty()).then_some((site.span, completions))
+}
+
+/// Syntax highlight `src`, underlining `error_span`
+fn highlight_job(ui: &egui::Ui, src: &str, error_span: Option<&Range<usize>>) -> LayoutJob {
+    let visuals = ui.visuals();
+    let text_color = visuals.text_color();
+    // Unterminated strings fail to tokenize. They are covered by the error span.
+    let tokens = query_syntax::tokenize(src).unwrap_or_default();
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    for tok in tokens {
+        segments.push((pos..tok.span.start, text_color));
+        let color = match &tok.kind {
+            TokenKind::LParen | TokenKind::RParen => visuals.weak_text_color(),
+            TokenKind::Not | TokenKind::And | TokenKind::Or => visuals.warn_fg_color,
+            TokenKind::Flag(_) => visuals.hyperlink_color,
+            TokenKind::Field { field, .. } => {
+                let field_end = tok.span.start + field.len();
+                segments.push((tok.span.start..field_end, visuals.strong_text_color()));
+                segments.push((field_end..tok.span.end, text_color));
+                pos = tok.span.end;
+                continue;
+            }
+            TokenKind::Text(_)
+                if src
+                    .get(tok.span.clone())
+                    .is_some_and(|s| s.starts_with('"')) =>
+            {
+                visuals.selection.stroke.color
+            }
+            TokenKind::Text(_) => text_color,
+        };
+        segments.push((tok.span.clone(), color));
+        pos = tok.span.end;
+    }
+    segments.push((pos..src.len(), text_color));
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let error_stroke = egui::Stroke::new(1.0, visuals.error_fg_color);
+    let error_span = error_span.map(|err| underline_span(src, err));
+    let mut job = LayoutJob::default();
+    let mut append = |range: Range<usize>, color, underline| {
+        if let Some(text) = src.get(range).filter(|text| !text.is_empty()) {
+            let format = egui::TextFormat {
+                underline: if underline {
+                    error_stroke
+                } else {
+                    egui::Stroke::NONE
+                },
+                ..egui::TextFormat::simple(font_id.clone(), color)
+            };
+            job.append(text, 0.0, format);
+        }
+    };
+    for (range, color) in segments {
+        let err = error_span.as_ref().map_or(range.start..range.start, |err| {
+            err.start.clamp(range.start, range.end)..err.end.clamp(range.start, range.end)
+        });
+        if err.is_empty() {
+            append(range, color, false);
+        } else {
+            append(range.start..err.start, color, false);
+            append(err.clone(), color, true);
+            append(err.end..range.end, color, false);
+        }
+    }
+    job
+}
+
+/// Widen an empty error span, like one at the end of input, to the character after it, or the
+/// last character, so the underline is visible
+fn underline_span(src: &str, err: &Range<usize>) -> Range<usize> {
+    if !err.is_empty() {
+        return err.clone();
+    }
+    if let Some(c) = src.get(err.start..).and_then(|rest| rest.chars().next()) {
+        err.start..err.start + c.len_utf8()
+    } else {
+        let last = src.char_indices().next_back().map_or(src.len(), |(i, _)| i);
+        last..src.len()
+    }
+}
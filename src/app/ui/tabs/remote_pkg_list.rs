@@ -1,37 +1,45 @@
 use {
-    super::{PkgListQuery, PkgListState, local_pkg_list::pkg_list_table_builder},
+    super::{
+        PkgListState,
+        pkg_table::{self, pkg_list_table_builder},
+    },
     crate::{
         app::{
             PkgCache,
-            ui::{SharedUiState, cmd::Cmd},
+            ui::{
+                SharedUiState,
+                cmd::{Cmd, CmdBuf},
+                ico,
+            },
         },
+        config::PkgListKind,
         packages::{Dbs, PkgIdx, PkgRef},
+        pkg_column::{PkgColumn, TableLayout},
+        pkg_filter::PkgFilter,
+        query_syntax::StrField,
     },
     alpacka::{Pkg, PkgDesc},
     eframe::egui,
+    smol_str::SmolStr,
+    std::sync::Arc,
 };
 
 pub fn ui(
     ui: &mut egui::Ui,
     pkgs: &mut PkgCache,
-    dbs: &Dbs,
+    dbs: &Arc<Dbs>,
     ui_state: &mut SharedUiState,
     tab_state: &mut PkgListState,
 ) {
+    let mut layout = ui_state.table_layouts.remote.clone();
+    let mut resort = false;
+    let mut sort_changed = false;
     egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
-        top_panel_ui(pkgs, dbs, tab_state, ui);
+        resort = top_panel_ui(pkgs, dbs, ui_state, tab_state, &mut layout, ui);
     });
-    pkg_list_table_builder(ui)
+    pkg_list_table_builder(ui, &layout.columns)
         .header(18.0, |mut row| {
-            row.col(|ui| {
-                ui.label("Name");
-            });
-            row.col(|ui| {
-                ui.label("Version");
-            });
-            row.col(|ui| {
-                ui.label("Description");
-            });
+            sort_changed = pkg_table::header_ui(&mut row, &mut layout);
         })
         .body(|mut body| {
             body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
@@ -56,68 +64,118 @@ pub fn ui(
                     });
                     return;
                 };
-                row.col(|ui| {
-                    ui.horizontal(|ui| {
+                for &col in &layout.columns {
+                    if col != PkgColumn::Name {
+                        row.col(|ui| {
+                            pkg_table::cell_ui(ui, col, dbs, *pkg_ref, pkg, &tab_state.query);
+                        });
+                        continue;
+                    }
+                    row.col(|ui| {
                         let db_name = &db.name;
-                        if ui.link(format!("{db_name}/{}", pkg.desc.name)).clicked() {
+                        let name = pkg.desc.name.as_str();
+                        let offset = db_name.len() + 1;
+                        let ranges = tab_state
+                            .query
+                            .highlights(StrField::Name, name)
+                            .into_iter()
+                            .map(|range| range.start + offset..range.end + offset)
+                            .collect();
+                        let label = super::highlighted(
+                            ui,
+                            &format!("{db_name}/{name}"),
+                            ranges,
+                            ui.visuals().hyperlink_color,
+                        );
+                        let re = ui.link(label);
+                        re.context_menu(|ui| {
+                            ui.label(format!("{} {db_name}/{name}", ico::PKG));
+                            ui.separator();
+                            install_buttons_ui(
+                                ui,
+                                &mut ui_state.cmd,
+                                &db.name,
+                                &pkg.desc.name,
+                                true,
+                            );
+                        });
+                        if re.clicked() {
                             ui_state.cmd.push(Cmd::OpenPkgTab(*pkg_ref));
                         }
                         installed_label_for_remote_pkg(ui, ui_state, &pkg.desc, dbs);
                     });
-                });
-                row.col(|ui| {
-                    ui.label(pkg.desc.version.as_str());
-                });
-                row.col(|ui| {
-                    ui.label(pkg.desc.desc.as_deref().unwrap_or("<missing description>"));
-                });
+                }
             });
         });
+    if sort_changed && layout.sort.is_none() {
+        // Back to relevance order
+        tab_state.refilter(ui.ctx());
+    } else if resort || sort_changed {
+        pkg_table::sort_pkgs(&mut pkgs.filt_remote_pkgs, dbs, &layout, |pkg_ref| pkg_ref);
+    }
+    ui_state.table_layouts.remote = layout;
 }
 
-fn top_panel_ui(pkgs: &mut PkgCache, dbs: &Dbs, tab_state: &mut PkgListState, ui: &mut egui::Ui) {
+fn top_panel_ui(
+    pkgs: &mut PkgCache,
+    dbs: &Arc<Dbs>,
+    ui_state: &mut SharedUiState,
+    tab_state: &mut PkgListState,
+    layout: &mut TableLayout,
+    ui: &mut egui::Ui,
+) -> bool {
+    let mut resort = false;
     ui.horizontal(|ui| {
-        if super::query_edit(ui, &mut tab_state.query_src).changed() {
-            tab_state.query = PkgListQuery::compile(&tab_state.query_src);
-            pkgs.filt_remote_pkgs = dbs
+        if super::query_ui(ui, tab_state, dbs, ui_state, PkgListKind::Remote) {
+            let filter = PkgFilter::new(dbs);
+            let scored = dbs
                 .remotes()
                 .flat_map(|(db_idx, syncdb)| {
-                    syncdb
-                        .pkgs
-                        .iter()
-                        .enumerate()
-                        .map(move |(idx, pkg)| (db_idx, idx, pkg))
+                    (0..syncdb.pkgs.len())
+                        .map(move |idx| PkgRef::from_components(db_idx, PkgIdx::from_usize(idx)))
                 })
-                .filter_map(|(db, idx, pkg)| {
-                    let filt_lo = tab_state.query.string.to_ascii_lowercase();
-                    let mut flags = tab_state.query.flags;
-                    if (flags.installed || flags.newer || flags.older)
-                        && let Some((_, cmp)) = remote_local_cmp(&pkg.desc, dbs.local_pkgs())
-                    {
-                        flags.installed = false;
-                        match cmp {
-                            RemoteLocalCmp::Newer => flags.newer = false,
-                            RemoteLocalCmp::Same => {}
-                            RemoteLocalCmp::Older => flags.older = false,
-                        }
-                    }
-                    if flags.any() {
-                        return None;
-                    }
-                    (pkg.desc.name.contains(&filt_lo)
-                        || pkg
-                            .desc
-                            .desc
-                            .as_ref()
-                            .is_some_and(|desc| desc.to_ascii_lowercase().contains(&filt_lo)))
-                    .then_some(PkgRef::from_components(db, PkgIdx::from_usize(idx)))
+                .filter_map(|pkg_ref| {
+                    let score = filter.score(&tab_state.query, pkg_ref)?;
+                    Some((pkg_ref, score))
                 })
                 .collect();
+            pkgs.filt_remote_pkgs = super::ranked(scored, tab_state.match_mode);
+            resort = true;
         }
+        pkg_table::column_chooser_ui(ui, layout);
         ui.spacing();
         ui.label(format!("{} packages listed", pkgs.filt_remote_pkgs.len()));
     });
     ui.add_space(4.0);
+    resort
+}
+
+/// Buttons for installing `db_name/pkg_name`, explicitly or as a dependency.
+///
+/// `close_menu` should be set when shown in a menu, to close it on click.
+pub fn install_buttons_ui(
+    ui: &mut egui::Ui,
+    cmd: &mut CmdBuf,
+    db_name: &str,
+    pkg_name: &str,
+    close_menu: bool,
+) {
+    let target = SmolStr::from(format!("{db_name}/{pkg_name}"));
+    let mut clicked = false;
+    if ui.button([ico::INSTALL, " Install"].concat()).clicked() {
+        cmd.push(Cmd::Install(vec![target.clone()]));
+        clicked = true;
+    }
+    if ui
+        .button([ico::INSTALL, " Install as dependency"].concat())
+        .clicked()
+    {
+        cmd.push(Cmd::InstallAsDeps(vec![target]));
+        clicked = true;
+    }
+    if clicked && close_menu {
+        ui.close();
+    }
 }
 
 pub fn remote_local_cmp(
@@ -0,0 +1,397 @@
+//! Saved queries: the dropdown of the package lists, the management tab, and pinned query tabs
+
+use {
+    super::{
+        PkgListState,
+        pkg_table::{self, pkg_list_table_builder},
+    },
+    crate::{
+        app::ui::{SharedUiState, cmd::Cmd, ico},
+        config::{PkgListKind, SavedQuery},
+        packages::{Dbs, PkgIdx, PkgRef},
+        pkg_column::{PkgColumn, SortDir},
+        pkg_filter::PkgFilter,
+        query_syntax::{ParseError, PkgListQuery, StrField},
+        text_match::MatchMode,
+    },
+    eframe::egui,
+    std::sync::{Arc, Weak},
+};
+
+/// Tab showing the packages matching a pinned saved query
+pub struct SavedQueryTab {
+    pub name: String,
+    results: Option<Results>,
+}
+
+/// Packages matching a query, for a given state of the databases
+struct Results {
+    src: String,
+    match_mode: MatchMode,
+    list: PkgListKind,
+    dbs: Weak<Dbs>,
+    query: PkgListQuery,
+    error: Option<ParseError>,
+    pkgs: Vec<PkgRef>,
+    /// The sort order `pkgs` is in. `None` is relevance order.
+    sorted_by: Option<(PkgColumn, SortDir)>,
+}
+
+impl Results {
+    fn is_current(&self, saved: &SavedQuery, dbs: &Arc<Dbs>) -> bool {
+        self.src == saved.query
+            && self.match_mode == saved.match_mode
+            && self.list == saved.list
+            && Weak::ptr_eq(&self.dbs, &Arc::downgrade(dbs))
+    }
+}
+
+impl SavedQueryTab {
+    pub const fn new(name: String) -> Self {
+        Self {
+            name,
+            results: None,
+        }
+    }
+    /// Number of matching packages, recomputed if the query or the databases changed
+    pub fn count(&mut self, saved: &[SavedQuery], dbs: &Arc<Dbs>) -> Option<usize> {
+        let saved = saved.iter().find(|q| q.name == self.name)?;
+        Some(self.refresh(saved, dbs).pkgs.len())
+    }
+    fn refresh(&mut self, saved: &SavedQuery, dbs: &Arc<Dbs>) -> &mut Results {
+        self.results
+            .take_if(|results| !results.is_current(saved, dbs));
+        self.results.get_or_insert_with(|| {
+            let (query, error) = match PkgListQuery::compile(&saved.query, saved.match_mode) {
+                Ok(query) => (query, None),
+                Err(e) => (PkgListQuery::default(), Some(e)),
+            };
+            let pkgs = if error.is_some() {
+                Vec::new()
+            } else {
+                let filter = PkgFilter::new(dbs);
+                let refs: Vec<PkgRef> = match saved.list {
+                    PkgListKind::Local => (0..dbs.local_pkgs().len())
+                        .map(|idx| PkgRef::local(PkgIdx::from_usize(idx)))
+                        .collect(),
+                    PkgListKind::Remote => dbs
+                        .remotes()
+                        .flat_map(|(db_idx, db)| {
+                            (0..db.pkgs.len()).map(move |idx| {
+                                PkgRef::from_components(db_idx, PkgIdx::from_usize(idx))
+                            })
+                        })
+                        .collect(),
+                };
+                let scored = refs
+                    .into_iter()
+                    .filter_map(|pkg_ref| Some((pkg_ref, filter.score(&query, pkg_ref)?)))
+                    .collect();
+                super::ranked(scored, saved.match_mode)
+            };
+            Results {
+                src: saved.query.clone(),
+                match_mode: saved.match_mode,
+                list: saved.list,
+                dbs: Arc::downgrade(dbs),
+                query,
+                error,
+                pkgs,
+                sorted_by: None,
+            }
+        })
+    }
+}
+
+pub fn ui(
+    ui: &mut egui::Ui,
+    dbs: &Arc<Dbs>,
+    ui_state: &mut SharedUiState,
+    tab: &mut SavedQueryTab,
+) {
+    let Some(saved) = ui_state
+        .saved_queries
+        .iter_mut()
+        .find(|q| q.name == tab.name)
+    else {
+        ui.label("This saved query no longer exists");
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new(&saved.query).monospace());
+        ui.weak(saved.match_mode.label());
+        if ui.button("Unpin").clicked() {
+            // The tab gets closed, see `TabViewer::force_close`
+            saved.pinned = false;
+        }
+    });
+    let saved = saved.clone();
+    let results = tab.refresh(&saved, dbs);
+    if let Some(err) = &results.error {
+        ui.label(egui::RichText::new(format!("⚠ {err}")).color(ui.visuals().error_fg_color));
+        return;
+    }
+    let mut layout = ui_state
+        .table_layouts
+        .saved_queries
+        .get(&saved.name)
+        .cloned()
+        .unwrap_or_default();
+    ui.horizontal(|ui| {
+        ui.label(format!("{} packages listed", results.pkgs.len()));
+        pkg_table::column_chooser_ui(ui, &mut layout);
+    });
+    ui.add_space(4.0);
+    pkg_list_table_builder(ui, &layout.columns)
+        .header(18.0, |mut row| {
+            pkg_table::header_ui(&mut row, &mut layout);
+        })
+        .body(|mut body| {
+            body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+            body.rows(22.0, results.pkgs.len(), |mut row| {
+                let Some(&pkg_ref) = results.pkgs.get(row.index()) else {
+                    return;
+                };
+                let (Some(db), Some(pkg)) = dbs.resolve(pkg_ref) else {
+                    row.col(|ui| {
+                        ui.label("<Unresolved package>");
+                    });
+                    return;
+                };
+                for &col in &layout.columns {
+                    if col != PkgColumn::Name {
+                        row.col(|ui| {
+                            pkg_table::cell_ui(ui, col, dbs, pkg_ref, pkg, &results.query);
+                        });
+                        continue;
+                    }
+                    row.col(|ui| {
+                        let name = pkg.desc.name.as_str();
+                        let (text, offset) = if pkg_ref.is_local() {
+                            (name.to_owned(), 0)
+                        } else {
+                            (format!("{}/{name}", db.name), db.name.len() + 1)
+                        };
+                        let ranges = results
+                            .query
+                            .highlights(StrField::Name, name)
+                            .into_iter()
+                            .map(|range| range.start + offset..range.end + offset)
+                            .collect();
+                        let label =
+                            super::highlighted(ui, &text, ranges, ui.visuals().hyperlink_color);
+                        if ui.link(label).clicked() {
+                            ui_state.cmd.push(Cmd::OpenPkgTab(pkg_ref));
+                        }
+                    });
+                }
+            });
+        });
+    if results.sorted_by != layout.sort {
+        if layout.sort.is_some() {
+            pkg_table::sort_pkgs(&mut results.pkgs, dbs, &layout, |pkg_ref| pkg_ref);
+            results.sorted_by = layout.sort;
+        } else {
+            // Back to relevance order
+            tab.results = None;
+        }
+    }
+    ui_state
+        .table_layouts
+        .saved_queries
+        .insert(saved.name, layout);
+}
+
+/// Dropdown next to the query box of the package lists.
+///
+/// Returns whether a saved query was loaded into `state`.
+pub fn dropdown_ui(
+    ui: &mut egui::Ui,
+    state: &mut PkgListState,
+    ui_state: &mut SharedUiState,
+    list: PkgListKind,
+) -> bool {
+    let mut loaded = false;
+    ui.menu_button("⭐", |ui| {
+        if ui_state.saved_queries.is_empty() {
+            ui.weak("No saved queries");
+        }
+        for saved in &ui_state.saved_queries {
+            if ui.button(&saved.name).on_hover_text(&saved.query).clicked() {
+                state.query_src.clone_from(&saved.query);
+                state.match_mode = saved.match_mode;
+                loaded = true;
+                ui.close();
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut state.save_name)
+                    .hint_text("Name")
+                    .desired_width(120.0),
+            );
+            let name = state.save_name.trim();
+            let taken = ui_state.saved_queries.iter().any(|q| q.name == name);
+            let re = ui.add_enabled(
+                !name.is_empty() && !taken && !state.query_src.trim().is_empty(),
+                egui::Button::new("Save current query"),
+            );
+            let re = if taken {
+                re.on_disabled_hover_text("A saved query with this name already exists")
+            } else {
+                re
+            };
+            if re.clicked() {
+                ui_state.saved_queries.push(SavedQuery {
+                    name: name.to_owned(),
+                    query: state.query_src.clone(),
+                    match_mode: state.match_mode,
+                    list,
+                    pinned: false,
+                });
+                state.save_name.clear();
+            }
+        });
+        if ui.button("Manage saved queries...").clicked() {
+            ui_state.cmd.push(Cmd::ManageSavedQueries);
+            ui.close();
+        }
+    })
+    .response
+    .on_hover_text("Saved queries");
+    loaded
+}
+
+/// State of the saved query management tab
+#[derive(Default)]
+pub struct ManageState {
+    /// Index of the query being renamed, and the new name
+    renaming: Option<(usize, String)>,
+}
+
+enum Action {
+    Rename(usize, String),
+    Swap(usize, usize),
+    Delete(usize),
+}
+
+pub fn manage_ui(ui: &mut egui::Ui, ui_state: &mut SharedUiState, state: &mut ManageState) {
+    let queries = &mut ui_state.saved_queries;
+    if queries.is_empty() {
+        ui.label("No saved queries. Save one from the ⭐ menu next to a package list query.");
+        return;
+    }
+    let mut action = None;
+    let len = queries.len();
+    egui::Grid::new("saved_queries_grid")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.strong("Name");
+            ui.strong("Query");
+            ui.strong("List");
+            ui.end_row();
+            for (i, saved) in queries.iter_mut().enumerate() {
+                match &mut state.renaming {
+                    Some((idx, new_name)) if *idx == i => {
+                        let re = ui.text_edit_singleline(new_name);
+                        if re.lost_focus() {
+                            action = Some(Action::Rename(i, new_name.trim().to_owned()));
+                        } else {
+                            re.request_focus();
+                        }
+                    }
+                    _ => {
+                        if ui
+                            .label(&saved.name)
+                            .on_hover_text("Double click to rename")
+                            .double_clicked()
+                        {
+                            state.renaming = Some((i, saved.name.clone()));
+                        }
+                    }
+                }
+                query_cells_ui(ui, i, saved);
+                ui.horizontal(|ui| {
+                    if ui.toggle_value(&mut saved.pinned, "📌").changed() && saved.pinned {
+                        ui_state.cmd.push(Cmd::OpenSavedQuery(saved.name.clone()));
+                    }
+                    if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                        action = Some(Action::Swap(i, i - 1));
+                    }
+                    if ui
+                        .add_enabled(i + 1 < len, egui::Button::new("⏷"))
+                        .clicked()
+                    {
+                        action = Some(Action::Swap(i, i + 1));
+                    }
+                    if ui.button(ico::TRASH).on_hover_text("Delete").clicked() {
+                        action = Some(Action::Delete(i));
+                    }
+                });
+                ui.end_row();
+            }
+        });
+    match action {
+        Some(Action::Rename(i, new_name)) => {
+            let taken = queries.iter().any(|q| q.name == new_name);
+            if let Some(saved) = queries.get_mut(i)
+                && !new_name.is_empty()
+                && !taken
+            {
+                if saved.pinned {
+                    // The tab of the old name closes by itself
+                    ui_state.cmd.push(Cmd::OpenSavedQuery(new_name.clone()));
+                }
+                let layouts = &mut ui_state.table_layouts.saved_queries;
+                if let Some(layout) = layouts.remove(&saved.name) {
+                    layouts.insert(new_name.clone(), layout);
+                }
+                saved.name = new_name;
+            }
+            state.renaming = None;
+        }
+        Some(Action::Swap(a, b)) => {
+            queries.swap(a, b);
+            state.renaming = None;
+        }
+        Some(Action::Delete(i)) => {
+            let saved = queries.remove(i);
+            ui_state.table_layouts.saved_queries.remove(&saved.name);
+            state.renaming = None;
+        }
+        None => {}
+    }
+}
+
+/// The query and list cells of a saved query row
+fn query_cells_ui(ui: &mut egui::Ui, i: usize, saved: &mut SavedQuery) {
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut saved.query)
+                .code_editor()
+                .desired_width(300.0),
+        );
+        egui::ComboBox::from_id_salt(("saved_query_mode", i))
+            .selected_text(saved.match_mode.label())
+            .show_ui(ui, |ui| {
+                for mode in MatchMode::ALL {
+                    ui.selectable_value(&mut saved.match_mode, mode, mode.label());
+                }
+            });
+    });
+    egui::ComboBox::from_id_salt(("saved_query_list", i))
+        .selected_text(list_label(saved.list))
+        .show_ui(ui, |ui| {
+            for list in [PkgListKind::Local, PkgListKind::Remote] {
+                ui.selectable_value(&mut saved.list, list, list_label(list));
+            }
+        });
+}
+
+const fn list_label(list: PkgListKind) -> &'static str {
+    match list {
+        PkgListKind::Local => "Local",
+        PkgListKind::Remote => "Remote",
+    }
+}
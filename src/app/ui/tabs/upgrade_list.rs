@@ -1,7 +1,7 @@
 use {
     super::remote_pkg_list::pkg_ver_cmp,
     crate::{
-        app::ui::{SharedUiState, cmd::Cmd, spawn_pacman_cmd_root_pkexec},
+        app::ui::{SharedUiState, cmd::Cmd},
         packages::{DbIdx, Dbs, PkgIdx, PkgRef},
     },
     eframe::egui,
@@ -94,15 +94,8 @@ pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, tab_s
                         .collect();
                 }
             }
-            if ui
-                .add_enabled(
-                    ui_state.pac_handler.is_none(),
-                    egui::Button::new("pacman -Su"),
-                )
-                .clicked()
-                && let Err(e) = spawn_pacman_cmd_root_pkexec(&mut ui_state.pac_handler, &["-Su"])
-            {
-                ui_state.error_popup = Some(e.to_string());
+            if ui.button("pacman -Su").clicked() {
+                ui_state.cmd.push(Cmd::SysUpgrade);
             }
         });
         ui.add_space(4.0);
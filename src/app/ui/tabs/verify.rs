@@ -0,0 +1,326 @@
+//! Checking installed files for changes, like `pacman -Qk` and `pacman -Qkk`
+
+use {
+    crate::{
+        app::ui::{
+            SharedUiState,
+            cmd::{Cmd, CmdBuf},
+        },
+        file_check::{CheckJob, FileStatus, PkgReport},
+        packages::{Dbs, PkgIdx, PkgRef},
+    },
+    eframe::egui,
+    egui_extras::{Column, TableBuilder},
+    std::sync::{Arc, Weak},
+};
+
+/// A file check, and its results so far
+struct Run {
+    job: Option<CheckJob>,
+    reports: Vec<PkgReport>,
+    deep: bool,
+    cancelled: bool,
+    checked_for: Weak<Dbs>,
+    /// Indices of the shown files, as (report, file)
+    rows: Vec<(usize, usize)>,
+    /// The number of reports and the `show_ok` setting `rows` was built for
+    rows_for: (usize, bool),
+    /// Whether the databases were reloaded since the check started.
+    ///
+    /// The results are kept, but may be out of date.
+    stale: bool,
+}
+
+impl Run {
+    fn start(dbs: &Arc<Dbs>, pkgs: Vec<PkgIdx>, deep: bool) -> Self {
+        Self {
+            job: Some(CheckJob::spawn(dbs.clone(), pkgs, deep)),
+            reports: Vec::new(),
+            deep,
+            cancelled: false,
+            checked_for: Arc::downgrade(dbs),
+            rows: Vec::new(),
+            rows_for: (0, false),
+            stale: false,
+        }
+    }
+    /// Collect the finished reports
+    fn update(&mut self, dbs: &Arc<Dbs>) {
+        self.stale = !Weak::ptr_eq(&self.checked_for, &Arc::downgrade(dbs));
+        if let Some(job) = &self.job
+            && job.poll(&mut self.reports)
+        {
+            self.job = None;
+        }
+    }
+    /// Stop the check, if it's still running
+    fn cancel(&mut self) {
+        if let Some(job) = &self.job {
+            job.cancel();
+            self.cancelled = true;
+        }
+    }
+    /// Add the rows of the new reports, or rebuild them if `show_ok` changed
+    fn update_rows(&mut self, show_ok: bool) {
+        let (n_reports, prev_show_ok) = self.rows_for;
+        let start = if prev_show_ok == show_ok {
+            n_reports
+        } else {
+            self.rows.clear();
+            0
+        };
+        let new_rows = self
+            .reports
+            .iter()
+            .enumerate()
+            .skip(start)
+            .flat_map(|(i, report)| {
+                report
+                    .files
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, file)| show_ok || file.status != FileStatus::Ok)
+                    .map(move |(j, _)| (i, j))
+            });
+        self.rows.extend(new_rows);
+        self.rows_for = (self.reports.len(), show_ok);
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    /// Compare against the mtree data, not just check existence
+    deep: bool,
+    /// Also list the files that passed
+    show_ok: bool,
+    run: Option<Run>,
+}
+
+/// Verifying the files of a single package, in its package tab
+pub struct PkgState {
+    deep: bool,
+    show_ok: bool,
+    run: Option<Box<Run>>,
+}
+
+impl Default for PkgState {
+    fn default() -> Self {
+        Self {
+            deep: false,
+            show_ok: true,
+            run: None,
+        }
+    }
+}
+
+/// Verify the files of an installed package.
+///
+/// Returns whether results are shown, which take the place of the file list.
+pub fn pkg_ui(
+    ui: &mut egui::Ui,
+    cmd: &mut CmdBuf,
+    dbs: &Arc<Dbs>,
+    state: &mut PkgState,
+    idx: PkgIdx,
+) -> bool {
+    if let Some(run) = &mut state.run {
+        run.update(dbs);
+    }
+    ui.horizontal(|ui| {
+        deep_checkbox_ui(ui, &mut state.deep);
+        if ui.button("✔ Verify files").clicked() {
+            state.run = Some(Box::new(Run::start(dbs, vec![idx], state.deep)));
+        }
+        if let Some(run) = &mut state.run {
+            ui.checkbox(&mut state.show_ok, "Show OK files");
+            if ui.button("Back to file list").clicked() {
+                run.cancel();
+                state.run = None;
+            }
+        }
+    });
+    let Some(run) = &mut state.run else {
+        return false;
+    };
+    status_ui(ui, run);
+    results_table_ui(ui, cmd, dbs, run, state.show_ok, false);
+    true
+}
+
+pub fn ui(ui: &mut egui::Ui, dbs: &Arc<Dbs>, ui_state: &mut SharedUiState, state: &mut State) {
+    if let Some(run) = &mut state.run {
+        run.update(dbs);
+    }
+    egui::Panel::top("top_panel_2").show_inside(ui, |ui| {
+        ui.horizontal(|ui| {
+            let running = state.run.as_ref().is_some_and(|run| run.job.is_some());
+            ui.add_enabled_ui(!running, |ui| {
+                deep_checkbox_ui(ui, &mut state.deep);
+                if ui.button("✔ Verify all packages").clicked() {
+                    let pkgs = (0..dbs.local_pkgs().len())
+                        .map(PkgIdx::from_usize)
+                        .collect();
+                    state.run = Some(Run::start(dbs, pkgs, state.deep));
+                }
+            });
+            ui.checkbox(&mut state.show_ok, "Show OK files");
+        });
+        if let Some(run) = &mut state.run {
+            status_ui(ui, run);
+        }
+        ui.add_space(4.0);
+    });
+    if let Some(run) = &mut state.run {
+        results_table_ui(ui, &mut ui_state.cmd, dbs, run, state.show_ok, true);
+    }
+}
+
+fn deep_checkbox_ui(ui: &mut egui::Ui, deep: &mut bool) {
+    ui.checkbox(deep, "Deep").on_hover_text(
+        "Also compare size, permissions, modification time and checksum against the package's \
+         mtree data (pacman -Qkk)",
+    );
+}
+
+/// Progress while running, a summary once done
+fn status_ui(ui: &mut egui::Ui, run: &mut Run) {
+    ui.horizontal(|ui| {
+        if let Some(job) = &run.job {
+            let (done, total) = job.progress();
+            ui.spinner();
+            #[expect(clippy::cast_precision_loss)]
+            let fraction = done as f32 / total.max(1) as f32;
+            ui.add(
+                egui::ProgressBar::new(fraction)
+                    .desired_width(200.0)
+                    .text(format!("{done}/{total} packages")),
+            );
+            if ui.button("Cancel").clicked() {
+                run.cancel();
+            }
+            return;
+        }
+        let (mut ok, mut missing, mut modified, mut unreadable) = (0, 0, 0, 0);
+        for file in run.reports.iter().flat_map(|report| &report.files) {
+            match file.status {
+                FileStatus::Ok => ok += 1,
+                FileStatus::Missing => missing += 1,
+                FileStatus::Modified(_) => modified += 1,
+                FileStatus::Unreadable(_) => unreadable += 1,
+            }
+        }
+        let cancelled = if run.cancelled { " (cancelled)" } else { "" };
+        ui.label(format!(
+            "{} packages checked{cancelled}: {ok} OK, {missing} missing, {modified} modified, \
+             {unreadable} unreadable",
+            run.reports.len()
+        ));
+    });
+    if run.stale {
+        ui.label(
+            egui::RichText::new("⚠ The databases were reloaded since, results may be out of date")
+                .color(ui.visuals().warn_fg_color),
+        );
+    }
+    let without_mtree: Vec<&str> = run
+        .reports
+        .iter()
+        .filter(|report| report.mtree_error.is_some())
+        .map(|report| report.name.as_str())
+        .collect();
+    if run.deep && !without_mtree.is_empty() {
+        ui.label(
+            egui::RichText::new(format!(
+                "⚠ {} packages have no readable mtree data, only checked for existence",
+                without_mtree.len()
+            ))
+            .color(ui.visuals().warn_fg_color),
+        )
+        .on_hover_text(without_mtree.join(", "));
+    }
+}
+
+/// Per-file results. `show_pkg` adds a column for the owning package.
+fn results_table_ui(
+    ui: &mut egui::Ui,
+    cmd: &mut CmdBuf,
+    dbs: &Dbs,
+    run: &mut Run,
+    show_ok: bool,
+    show_pkg: bool,
+) {
+    run.update_rows(show_ok);
+    let mut table = TableBuilder::new(ui);
+    if show_pkg {
+        table = table.column(Column::auto());
+    }
+    table
+        .column(Column::auto())
+        .column(Column::auto())
+        .column(Column::remainder())
+        .auto_shrink(false)
+        .striped(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .header(18.0, |mut row| {
+            let labels: &[&str] = if show_pkg {
+                &["Package", "Status", "File", "Details"]
+            } else {
+                &["Status", "File", "Details"]
+            };
+            for label in labels {
+                row.col(|ui| {
+                    ui.label(*label);
+                });
+            }
+        })
+        .body(|mut body| {
+            body.ui_mut().style_mut().wrap_mode = Some(egui::TextWrapMode::Extend);
+            body.rows(22.0, run.rows.len(), |mut row| {
+                let Some(&(i, j)) = run.rows.get(row.index()) else {
+                    return;
+                };
+                let Some(report) = run.reports.get(i) else {
+                    return;
+                };
+                let Some(file) = report.files.get(j) else {
+                    return;
+                };
+                if show_pkg {
+                    row.col(|ui| match dbs.local_pkg_by_name(&report.name) {
+                        Some((idx, _)) => {
+                            if ui.link(report.name.as_str()).clicked() {
+                                cmd.push(Cmd::OpenPkgTab(PkgRef::local(idx)));
+                            }
+                        }
+                        // Removed since the check
+                        None => {
+                            ui.label(report.name.as_str());
+                        }
+                    });
+                }
+                row.col(|ui| {
+                    let visuals = ui.visuals();
+                    let (text, color) = match &file.status {
+                        FileStatus::Ok => ("✔ OK", visuals.weak_text_color()),
+                        FileStatus::Missing => ("❌ Missing", visuals.error_fg_color),
+                        FileStatus::Modified(_) => ("⚠ Modified", visuals.warn_fg_color),
+                        FileStatus::Unreadable(_) => ("🔒 Unreadable", visuals.weak_text_color()),
+                    };
+                    ui.label(egui::RichText::new(text).color(color));
+                });
+                row.col(|ui| {
+                    ui.label(format!("/{}", file.path));
+                });
+                row.col(|ui| match &file.status {
+                    FileStatus::Modified(mismatches) => {
+                        let labels: Vec<&str> = mismatches.iter().map(|m| m.label()).collect();
+                        ui.label(labels.join(", "));
+                    }
+                    FileStatus::Unreadable(err) => {
+                        ui.label(err);
+                    }
+                    FileStatus::Ok | FileStatus::Missing => {}
+                });
+            });
+        });
+}
@@ -0,0 +1,185 @@
+//! Confirmation dialog showing what a pacman transaction would do
+
+use {
+    super::SharedUiState,
+    crate::{
+        packages::Dbs,
+        transaction::{Op, Preview},
+        vercmp::{AbCmp, vercmp},
+    },
+    eframe::egui,
+    humansize::format_size_i,
+    std::sync::{Arc, Weak},
+};
+
+pub struct PendingTransaction {
+    op: Op,
+    preview: Preview,
+    /// The databases the preview was computed from
+    dbs: Weak<Dbs>,
+}
+
+impl PendingTransaction {
+    pub fn new(op: Op, dbs: &Arc<Dbs>) -> Self {
+        Self {
+            preview: op.preview(dbs),
+            op,
+            dbs: Arc::downgrade(dbs),
+        }
+    }
+}
+
+pub fn modal(ctx: &egui::Context, ui_state: &mut SharedUiState, dbs: Option<&Arc<Dbs>>) {
+    let Some(pending) = &mut ui_state.pending_transaction else {
+        return;
+    };
+    let Some(dbs) = dbs else {
+        return;
+    };
+    // The databases got reloaded while the dialog was open
+    if !Weak::ptr_eq(&pending.dbs, &Arc::downgrade(dbs)) {
+        pending.preview = pending.op.preview(dbs);
+        pending.dbs = Arc::downgrade(dbs);
+    }
+    let mut proceed = false;
+    let mut cancel = false;
+    egui::Modal::new("transaction_modal".into()).show(ctx, |ui| {
+        ui.heading("Transaction preview");
+        ui.separator();
+        ui.label(
+            egui::RichText::new(["pacman", &pending.op.args().join(" ")].join(" ")).monospace(),
+        );
+        preview_ui(ui, &pending.preview, dbs);
+        ui.separator();
+        ui.horizontal(|ui| {
+            let preview = &pending.preview;
+            proceed = ui
+                .add_enabled(
+                    !preview.is_empty() && preview.blockers.is_empty(),
+                    egui::Button::new("Proceed"),
+                )
+                .on_disabled_hover_text("pacman would refuse this transaction")
+                .clicked();
+            cancel =
+                ui.button("Cancel").clicked() || ui.input(|inp| inp.key_pressed(egui::Key::Escape));
+        });
+    });
+    if proceed {
+        ui_state.ops.enqueue(&pending.op.args());
+        ui_state.pending_transaction = None;
+    } else if cancel {
+        ui_state.pending_transaction = None;
+    }
+}
+
+fn preview_ui(ui: &mut egui::Ui, preview: &Preview, dbs: &Dbs) {
+    if !preview.unresolved.is_empty() {
+        ui.label(
+            egui::RichText::new(format!("⚠ Not found: {}", preview.unresolved.join(", ")))
+                .color(ui.visuals().warn_fg_color),
+        );
+    }
+    if !preview.blockers.is_empty() {
+        blockers_ui(ui, preview, dbs);
+    }
+    if preview.is_empty() {
+        ui.label("Nothing to do");
+        return;
+    }
+    let avail_height = ui.ctx().content_rect().height();
+    egui::ScrollArea::vertical()
+        .max_height((avail_height * 0.5).round())
+        .show(ui, |ui| {
+            section_ui(ui, "To install", &preview.install, |ui, pkg_ref| {
+                let (Some(db), Some(pkg)) = dbs.resolve(*pkg_ref) else {
+                    ui.label("<unresolved>");
+                    return;
+                };
+                ui.label(format!(
+                    "{}/{} {}",
+                    db.name, pkg.desc.name, pkg.desc.version
+                ));
+            });
+            section_ui(ui, "To upgrade", &preview.upgrade, |ui, (local, remote)| {
+                let (Some(local), (Some(db), Some(remote))) =
+                    (dbs.resolve_local(*local), dbs.resolve(*remote))
+                else {
+                    ui.label("<unresolved>");
+                    return;
+                };
+                let (name, old, new) =
+                    (&remote.desc.name, &local.desc.version, &remote.desc.version);
+                if old == new {
+                    ui.label(format!("{}/{name} {new} (reinstall)", db.name));
+                } else {
+                    ui.label(format!("{}/{name} {old} ➡ {new}", db.name));
+                }
+            });
+            section_ui(ui, "From package files", &preview.files, |ui, file| {
+                let name = &file.name;
+                let new = &file.version;
+                match file.replaces.and_then(|idx| dbs.resolve_local(idx)) {
+                    Some(local) => {
+                        let old = &local.desc.version;
+                        let what = match vercmp(new, old) {
+                            AbCmp::ANewer => "upgrade",
+                            AbCmp::Same => "reinstall",
+                            AbCmp::BNewer => "downgrade",
+                        };
+                        ui.label(format!("{name} {old} ➡ {new} ({what})"));
+                    }
+                    None => {
+                        ui.label(format!("{name} {new}"));
+                    }
+                }
+            });
+            section_ui(ui, "To remove", &preview.remove, |ui, idx| {
+                let Some(pkg) = dbs.resolve_local(*idx) else {
+                    ui.label("<unresolved>");
+                    return;
+                };
+                ui.label(format!("{} {}", pkg.desc.name, pkg.desc.version));
+            });
+        });
+    ui.separator();
+    ui.label(format!(
+        "Download size: {}",
+        format_size_i(preview.download_size(dbs), humansize::BINARY)
+    ));
+    let delta = preview.installed_size_delta(dbs);
+    let sign = if delta > 0 { "+" } else { "" };
+    ui.label(format!(
+        "Installed size change: {sign}{}",
+        format_size_i(delta, humansize::BINARY)
+    ));
+    if !preview.files.is_empty() {
+        ui.weak("Sizes don't include package files, and their dependencies aren't checked");
+    }
+}
+
+fn blockers_ui(ui: &mut egui::Ui, preview: &Preview, dbs: &Dbs) {
+    ui.label(
+        egui::RichText::new("⚠ Packages that are kept depend on packages to be removed:")
+            .color(ui.visuals().error_fg_color),
+    );
+    for &(pkg, dep) in &preview.blockers {
+        let (Some(pkg), Some(dep)) = (dbs.resolve_local(pkg), dbs.resolve_local(dep)) else {
+            ui.label("<unresolved>");
+            continue;
+        };
+        ui.label(format!("{} requires {}", pkg.desc.name, dep.desc.name));
+    }
+}
+
+fn section_ui<T>(ui: &mut egui::Ui, title: &str, items: &[T], item_ui: impl Fn(&mut egui::Ui, &T)) {
+    if items.is_empty() {
+        return;
+    }
+    egui::CollapsingHeader::new(format!("{title} ({})", items.len()))
+        .default_open(true)
+        .show(ui, |ui| {
+            for item in items {
+                item_ui(ui, item);
+            }
+        });
+}
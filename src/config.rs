@@ -1,8 +1,9 @@
 use {
+    crate::{pkg_column::TableLayout, text_match::MatchMode},
     anyhow::Context,
     ron::ser::PrettyConfig,
     serde::{Deserialize, Serialize},
-    std::path::PathBuf,
+    std::{collections::BTreeMap, path::PathBuf},
 };
 
 pub fn cfg_dir() -> Option<PathBuf> {
@@ -12,6 +13,34 @@ pub fn cfg_dir() -> Option<PathBuf> {
 #[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     pub color_theme: Option<[Rgb; 12]>,
+    #[serde(default)]
+    pub saved_queries: Vec<SavedQuery>,
+    #[serde(default)]
+    pub table_layouts: TableLayouts,
+    #[serde(default)]
+    pub lost_files: LostFilesConfig,
+}
+
+/// A named package list query
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedQuery {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    /// The package list the query is evaluated against when pinned
+    #[serde(default)]
+    pub list: PkgListKind,
+    /// Shown as its own tab
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PkgListKind {
+    #[default]
+    Local,
+    Remote,
 }
 
 impl Config {
@@ -41,4 +70,44 @@ impl Config {
     }
 }
 
+/// Column layouts of the package list tabs
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct TableLayouts {
+    #[serde(default)]
+    pub local: TableLayout,
+    #[serde(default)]
+    pub remote: TableLayout,
+    /// Pinned saved query tabs, by query name
+    #[serde(default)]
+    pub saved_queries: BTreeMap<String, TableLayout>,
+}
+
+/// Settings of the lost files scanner
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LostFilesConfig {
+    /// Directories to scan
+    pub roots: Vec<PathBuf>,
+    /// Absolute paths to skip, along with everything under them. `*` and `?` wildcards
+    /// match within a path component.
+    pub exclusions: Vec<String>,
+}
+
+impl Default for LostFilesConfig {
+    fn default() -> Self {
+        Self {
+            roots: ["/usr", "/etc", "/opt"].map(PathBuf::from).to_vec(),
+            exclusions: [
+                "/etc/ca-certificates/extracted",
+                "/etc/pacman.d/gnupg",
+                "/etc/ssl/certs",
+                "/usr/lib/locale/locale-archive",
+                "/usr/lib/modules/*/modules.*",
+                "/usr/share/mime",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
 type Rgb = [u8; 3];
@@ -0,0 +1,516 @@
+//! Checking installed files against the package database, like `pacman -Qk` and `pacman -Qkk`
+
+use {
+    crate::packages::{Dbs, PkgIdx},
+    alpacka::Pkg,
+    sha2::{Digest as _, Sha256},
+    smol_str::SmolStr,
+    std::{
+        collections::{HashMap, HashSet},
+        io::Read as _,
+        os::unix::fs::{MetadataExt as _, PermissionsExt as _},
+        path::Path,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            mpsc::{Receiver, TryRecvError},
+        },
+        thread,
+    },
+};
+
+/// An entry of a package's mtree file, which records the metadata of the packaged files
+#[derive(Debug, PartialEq)]
+pub struct MtreeEntry {
+    /// Relative to `/`, without a trailing `/`
+    pub path: Box<str>,
+    pub kind: EntryKind,
+    pub mode: Option<u32>,
+    pub size: Option<u64>,
+    /// Seconds since the epoch, the fractional part is dropped
+    pub mtime: Option<i64>,
+    /// Lowercase hex
+    pub sha256: Option<Box<str>>,
+    /// Symlink target
+    pub link: Option<Box<str>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EntryKind {
+    #[default]
+    File,
+    Dir,
+    Link,
+}
+
+/// Keywords set by `/set`, or given on an entry line
+#[derive(Default, Clone)]
+struct Keywords {
+    kind: Option<EntryKind>,
+    mode: Option<u32>,
+    size: Option<u64>,
+    mtime: Option<i64>,
+    sha256: Option<Box<str>>,
+    link: Option<Box<str>>,
+}
+
+impl Keywords {
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "type" => {
+                self.kind = match value {
+                    "file" => Some(EntryKind::File),
+                    "dir" => Some(EntryKind::Dir),
+                    "link" => Some(EntryKind::Link),
+                    _ => None,
+                };
+            }
+            "mode" => self.mode = u32::from_str_radix(value, 8).ok(),
+            "size" => self.size = value.parse().ok(),
+            "time" => {
+                let secs = value.split_once('.').map_or(value, |(secs, _)| secs);
+                self.mtime = secs.parse().ok();
+            }
+            "sha256digest" => self.sha256 = Some(value.into()),
+            "link" => self.link = Some(unescape(value).into()),
+            _ => {}
+        }
+    }
+    fn unset(&mut self, key: &str) {
+        match key {
+            "type" => self.kind = None,
+            "mode" => self.mode = None,
+            "size" => self.size = None,
+            "time" => self.mtime = None,
+            "sha256digest" => self.sha256 = None,
+            "link" => self.link = None,
+            _ => {}
+        }
+    }
+}
+
+/// Parse the (decompressed) text of an mtree file.
+///
+/// The package metadata files at the top level, like `.PKGINFO`, are skipped.
+pub fn parse_mtree(text: &str) -> Vec<MtreeEntry> {
+    let mut defaults = Keywords::default();
+    let mut entries = Vec::new();
+    for line in text.lines() {
+        let mut words = line.split_ascii_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        let key_values = || words.clone().filter_map(|word| word.split_once('='));
+        match first {
+            "/set" => {
+                for (key, value) in key_values() {
+                    defaults.set(key, value);
+                }
+            }
+            "/unset" => {
+                for key in words.clone() {
+                    defaults.unset(key);
+                }
+            }
+            _ if first.starts_with('#') => {}
+            _ => {
+                let path = unescape(first);
+                let path = path.strip_prefix("./").unwrap_or(&path);
+                if path.is_empty() || path.starts_with('.') {
+                    continue;
+                }
+                let mut kw = defaults.clone();
+                for (key, value) in key_values() {
+                    kw.set(key, value);
+                }
+                entries.push(MtreeEntry {
+                    path: path.trim_end_matches('/').into(),
+                    kind: kw.kind.unwrap_or_default(),
+                    mode: kw.mode,
+                    size: kw.size,
+                    mtime: kw.mtime,
+                    sha256: kw.sha256,
+                    link: kw.link,
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// Decode the `\ooo` octal escapes mtree uses for special characters in paths
+fn unescape(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while let Some(&b) = bytes.get(i) {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) if b == b'\\' => {
+                out.push(byte);
+                i += 4;
+            }
+            _ => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Read the mtree file the local database keeps for an installed package
+pub fn read_mtree(dbs: &Dbs, pkg: &Pkg) -> anyhow::Result<Vec<MtreeEntry>> {
+    let path = dbs
+        .conf
+        .local_pkg_dir(&pkg.desc.name, &pkg.desc.version)
+        .join("mtree");
+    let file = std::fs::File::open(&path)?;
+    let mut text = String::new();
+    flate2::read::GzDecoder::new(std::io::BufReader::new(file)).read_to_string(&mut text)?;
+    Ok(parse_mtree(&text))
+}
+
+/// The paths of the `%BACKUP%` section of a local database `files` entry.
+///
+/// Each line is a path and the md5 it was installed with, separated by a tab.
+fn parse_backup(text: &str) -> Vec<Box<str>> {
+    text.lines()
+        .skip_while(|line| *line != "%BACKUP%")
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.split_once('\t').map_or(line, |(path, _)| path).into())
+        .collect()
+}
+
+/// Read the backup files of an installed package, like configuration files in `/etc`.
+///
+/// They are listed in the `files` entry of the local database, next to the mtree file.
+pub fn read_backup(dbs: &Dbs, pkg: &Pkg) -> anyhow::Result<HashSet<Box<str>>> {
+    let path = dbs
+        .conf
+        .local_pkg_dir(&pkg.desc.name, &pkg.desc.version)
+        .join("files");
+    Ok(parse_backup(&std::fs::read_to_string(path)?)
+        .into_iter()
+        .collect())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FileStatus {
+    Ok,
+    Missing,
+    Modified(Vec<Mismatch>),
+    /// The file couldn't be examined, e.g. for lack of permissions
+    Unreadable(String),
+}
+
+/// How a file differs from what the package recorded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    Type,
+    Mode,
+    Size,
+    Mtime,
+    Checksum,
+    LinkTarget,
+}
+
+impl Mismatch {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Type => "type",
+            Self::Mode => "permissions",
+            Self::Size => "size",
+            Self::Mtime => "modification time",
+            Self::Checksum => "checksum",
+            Self::LinkTarget => "symlink target",
+        }
+    }
+}
+
+/// Check one file, relative to `root`. Without an mtree entry, only its existence is checked.
+///
+/// Backup files are expected to be edited, so like pacman, their contents aren't checked.
+pub fn check_file(root: &Path, path: &str, entry: Option<&MtreeEntry>, backup: bool) -> FileStatus {
+    let full_path = root.join(path);
+    let meta = match std::fs::symlink_metadata(&full_path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return FileStatus::Missing,
+        Err(e) => return FileStatus::Unreadable(e.to_string()),
+    };
+    let Some(entry) = entry else {
+        return FileStatus::Ok;
+    };
+    let file_type = meta.file_type();
+    let kind_matches = match entry.kind {
+        EntryKind::File => file_type.is_file(),
+        EntryKind::Dir => file_type.is_dir(),
+        EntryKind::Link => file_type.is_symlink(),
+    };
+    if !kind_matches {
+        return FileStatus::Modified(vec![Mismatch::Type]);
+    }
+    let mut mismatches = Vec::new();
+    if entry.kind != EntryKind::Link
+        && entry
+            .mode
+            .is_some_and(|mode| mode != meta.permissions().mode() & 0o7777)
+    {
+        mismatches.push(Mismatch::Mode);
+    }
+    match entry.kind {
+        EntryKind::File if backup => {}
+        EntryKind::File => {
+            if entry.size.is_some_and(|size| size != meta.len()) {
+                mismatches.push(Mismatch::Size);
+            }
+            if entry.mtime.is_some_and(|mtime| mtime != meta.mtime()) {
+                mismatches.push(Mismatch::Mtime);
+            }
+            if let Some(sha256) = &entry.sha256 {
+                match sha256_of(&full_path) {
+                    Ok(actual) if actual != **sha256 => mismatches.push(Mismatch::Checksum),
+                    Ok(_) => {}
+                    Err(e) => return FileStatus::Unreadable(e.to_string()),
+                }
+            }
+        }
+        EntryKind::Link => {
+            let target = std::fs::read_link(&full_path);
+            if let Some(link) = &entry.link
+                && target.is_ok_and(|target| target != Path::new(&**link))
+            {
+                mismatches.push(Mismatch::LinkTarget);
+            }
+        }
+        EntryKind::Dir => {}
+    }
+    if mismatches.is_empty() {
+        FileStatus::Ok
+    } else {
+        FileStatus::Modified(mismatches)
+    }
+}
+
+fn sha256_of(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub struct FileReport {
+    /// Relative to `/`, as listed in the database
+    pub path: Box<str>,
+    pub status: FileStatus,
+}
+
+pub struct PkgReport {
+    /// The name rather than the index, which stays valid when the databases are reloaded
+    pub name: SmolStr,
+    pub files: Vec<FileReport>,
+    /// Why the deep check fell back to only checking existence
+    pub mtree_error: Option<String>,
+}
+
+/// Check the files of a package.
+///
+/// Returns `None` if cancelled midway.
+fn check_pkg(dbs: &Dbs, idx: PkgIdx, deep: bool, cancel: &AtomicBool) -> Option<PkgReport> {
+    let pkg = dbs.resolve_local(idx)?;
+    let (mtree, mtree_error) = if deep {
+        match read_mtree(dbs, pkg) {
+            Ok(entries) => (entries, None),
+            Err(e) => (Vec::new(), Some(format!("Failed to read mtree: {e}"))),
+        }
+    } else {
+        (Vec::new(), None)
+    };
+    let mtree: HashMap<&str, &MtreeEntry> = mtree.iter().map(|e| (&*e.path, e)).collect();
+    let backup = if deep {
+        read_backup(dbs, pkg).unwrap_or_else(|e| {
+            log::warn!("Failed to read backup files of {}: {e}", pkg.desc.name);
+            HashSet::new()
+        })
+    } else {
+        HashSet::new()
+    };
+    let mut files = Vec::with_capacity(pkg.files.len());
+    for path in &pkg.files {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        let entry = mtree.get(path.trim_end_matches('/')).copied();
+        files.push(FileReport {
+            path: path.clone(),
+            status: check_file(&dbs.conf.root_dir, path, entry, backup.contains(path)),
+        });
+    }
+    Some(PkgReport {
+        name: pkg.desc.name.clone(),
+        files,
+        mtree_error,
+    })
+}
+
+/// Checking the files of some packages on a background thread
+pub struct CheckJob {
+    /// Number of packages checked so far
+    done: Arc<AtomicUsize>,
+    total: usize,
+    cancel: Arc<AtomicBool>,
+    recv: Receiver<PkgReport>,
+}
+
+impl CheckJob {
+    /// In deep mode, compare against the mtree data (`-Qkk`), otherwise only check
+    /// that the files exist (`-Qk`).
+    pub fn spawn(dbs: Arc<Dbs>, pkgs: Vec<PkgIdx>, deep: bool) -> Self {
+        let (send, recv) = std::sync::mpsc::channel();
+        let done = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let total = pkgs.len();
+        let (done2, cancel2) = (done.clone(), cancel.clone());
+        thread::spawn(move || {
+            for idx in pkgs {
+                let Some(report) = check_pkg(&dbs, idx, deep, &cancel2) else {
+                    return;
+                };
+                if send.send(report).is_err() {
+                    return;
+                }
+                done2.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        Self {
+            done,
+            total,
+            cancel,
+            recv,
+        }
+    }
+    /// Packages checked, out of the total
+    pub fn progress(&self) -> (usize, usize) {
+        (self.done.load(Ordering::Relaxed), self.total)
+    }
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+    /// Move the reports that are ready into `out`. Returns whether the job is over.
+    pub fn poll(&self, out: &mut Vec<PkgReport>) -> bool {
+        loop {
+            match self.recv.try_recv() {
+                Ok(report) => out.push(report),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_mtree() {
+    let text = "#mtree
+/set type=file uid=0 gid=0 mode=644
+./.PKGINFO time=1700000000.0 size=500 sha256digest=aa
+./usr time=1700000000.0 mode=755 type=dir
+./usr/bin/foo\\040bar time=1700000001.5 mode=755 size=12 sha256digest=bb
+/unset mode
+./usr/lib/libfoo.so time=1700000000.0 type=link link=libfoo.so.1
+";
+    let entries = parse_mtree(text);
+    assert_eq!(
+        entries.iter().map(|e| &*e.path).collect::<Vec<_>>(),
+        ["usr", "usr/bin/foo bar", "usr/lib/libfoo.so"]
+    );
+    let [dir, file, link] = entries.as_slice() else {
+        panic!("expected 3 entries");
+    };
+    assert_eq!((dir.kind, dir.mode), (EntryKind::Dir, Some(0o755)));
+    assert_eq!(file.kind, EntryKind::File);
+    assert_eq!((file.size, file.mtime), (Some(12), Some(1_700_000_001)));
+    assert_eq!(file.sha256.as_deref(), Some("bb"));
+    assert_eq!((link.kind, link.mode), (EntryKind::Link, None));
+    assert_eq!(link.link.as_deref(), Some("libfoo.so.1"));
+}
+
+#[test]
+fn test_parse_backup() {
+    let text = "%FILES%\netc/\netc/foo.conf\n\n%BACKUP%\netc/foo.conf\t0123abcd\netc/bar\n";
+    assert_eq!(
+        parse_backup(text),
+        [Box::from("etc/foo.conf"), Box::from("etc/bar")]
+    );
+    assert!(parse_backup("%FILES%\netc/\n").is_empty());
+}
+
+#[test]
+fn test_check_file() {
+    let root = std::env::temp_dir().join(format!("alpacka-app-check-file-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("etc")).expect("Failed to create test dir");
+    std::fs::write(root.join("etc/foo"), "hello").expect("Failed to write test file");
+    std::os::unix::fs::symlink("foo", root.join("etc/link")).expect("Failed to create symlink");
+    let mode = std::fs::metadata(root.join("etc/foo"))
+        .expect("Failed to stat test file")
+        .permissions()
+        .mode()
+        & 0o7777;
+    let file = MtreeEntry {
+        path: "etc/foo".into(),
+        kind: EntryKind::File,
+        mode: Some(mode),
+        size: Some(5),
+        mtime: None,
+        sha256: Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".into()),
+        link: None,
+    };
+    assert_eq!(
+        check_file(&root, "etc/foo", Some(&file), false),
+        FileStatus::Ok
+    );
+    assert_eq!(check_file(&root, "etc/foo", None, false), FileStatus::Ok);
+    assert_eq!(
+        check_file(&root, "etc/gone", None, false),
+        FileStatus::Missing
+    );
+    let dir = MtreeEntry {
+        kind: EntryKind::Dir,
+        ..file
+    };
+    assert_eq!(
+        check_file(&root, "etc/foo", Some(&dir), false),
+        FileStatus::Modified(vec![Mismatch::Type])
+    );
+    let link = MtreeEntry {
+        path: "etc/link".into(),
+        kind: EntryKind::Link,
+        mode: None,
+        size: None,
+        mtime: None,
+        sha256: None,
+        link: Some("bar".into()),
+    };
+    assert_eq!(
+        check_file(&root, "etc/link", Some(&link), false),
+        FileStatus::Modified(vec![Mismatch::LinkTarget])
+    );
+    std::fs::write(root.join("etc/foo"), "hello, world").expect("Failed to write test file");
+    let file = MtreeEntry {
+        kind: EntryKind::File,
+        ..dir
+    };
+    assert_eq!(
+        check_file(&root, "etc/foo", Some(&file), false),
+        FileStatus::Modified(vec![Mismatch::Size, Mismatch::Checksum])
+    );
+    // Edited backup files are fine
+    assert_eq!(
+        check_file(&root, "etc/foo", Some(&file), true),
+        FileStatus::Ok
+    );
+    std::fs::remove_dir_all(&root).expect("Failed to remove test dir");
+}
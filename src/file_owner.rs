@@ -0,0 +1,254 @@
+//! Finding the packages that own a file, like `pacman -Qo` and `pacman -F`
+
+use {
+    crate::{
+        packages::{Dbs, PkgIdx},
+        pacman_conf::PacmanConf,
+    },
+    anyhow::Context as _,
+    regex::Regex,
+    smol_str::SmolStr,
+    std::{
+        io::{BufReader, Read, Seek},
+        path::Path,
+    },
+};
+
+/// Stop collecting results after this many hits
+const MAX_HITS: usize = 10_000;
+
+/// What to look for in the file lists
+#[derive(Debug)]
+pub enum FileQuery {
+    /// Absolute path, stored without the leading `/`
+    Path(String),
+    /// Last component of the path
+    Name(String),
+    /// `*` and `?` wildcards, matched against the whole path if the pattern has a `/`,
+    /// otherwise against the file name
+    Glob { regex: Regex, whole_path: bool },
+}
+
+impl FileQuery {
+    /// Returns `None` for an empty query
+    pub fn parse(input: &str) -> anyhow::Result<Option<Self>> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        if input.contains(['*', '?']) {
+            let whole_path = input.contains('/');
+            let pattern = if whole_path {
+                input.trim_start_matches('/')
+            } else {
+                input
+            };
+            let regex = Regex::new(&glob_to_regex(pattern.trim_end_matches('/')))?;
+            return Ok(Some(Self::Glob { regex, whole_path }));
+        }
+        let query = match input.strip_prefix('/') {
+            Some(path) => Self::Path(path.trim_end_matches('/').to_owned()),
+            None => Self::Name(input.trim_end_matches('/').to_owned()),
+        };
+        Ok(Some(query))
+    }
+    /// Whether `file`, as listed in a package database, matches.
+    ///
+    /// Paths in the databases are relative to `/`, and directories end with `/`.
+    pub fn matches(&self, file: &str) -> bool {
+        let file = file.trim_end_matches('/');
+        let name = file.rsplit_once('/').map_or(file, |(_, name)| name);
+        match self {
+            Self::Path(path) => file == path,
+            Self::Name(wanted) => name == wanted,
+            Self::Glob { regex, whole_path } => {
+                regex.is_match(if *whole_path { file } else { name })
+            }
+        }
+    }
+}
+
+/// An anchored regex for a glob where `*` and `?` don't match `/`
+pub fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut buf = [0; 4];
+    for ch in glob.chars() {
+        match ch {
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            _ => regex.push_str(&regex::escape(ch.encode_utf8(&mut buf))),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// The file lists of a sync repository, read from its `.files` database
+pub struct FilesDb {
+    pub repo: SmolStr,
+    pub pkgs: Vec<FilesDbPkg>,
+}
+
+pub struct FilesDbPkg {
+    pub name: SmolStr,
+    pub files: Vec<Box<str>>,
+}
+
+/// Read the `.files` databases of the configured repositories.
+///
+/// Repositories without one (`pacman -Fy` was never run) are skipped.
+pub fn read_files_dbs(conf: &PacmanConf) -> Vec<FilesDb> {
+    let mut dbs = Vec::new();
+    for repo in &conf.repos {
+        let path = conf.sync_db_path(&repo.name).with_extension("files");
+        if !path.exists() {
+            continue;
+        }
+        match read_files_db(&path) {
+            Ok(pkgs) => dbs.push(FilesDb {
+                repo: repo.name.clone(),
+                pkgs,
+            }),
+            Err(e) => log::error!("Failed to read {}: {e:#}", path.display()),
+        }
+    }
+    dbs
+}
+
+fn read_files_db(path: &Path) -> anyhow::Result<Vec<FilesDbPkg>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut magic = [0; 2];
+    file.read_exact(&mut magic)?;
+    anyhow::ensure!(
+        magic == [0x1f, 0x8b],
+        "Only gzip compressed databases are supported"
+    );
+    file.rewind()?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(BufReader::new(file)));
+    let mut pkgs = Vec::new();
+    let mut text = String::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        // Entries are `name-pkgver-pkgrel/{desc,files}`
+        if entry_path.file_name().is_none_or(|name| name != "files") {
+            continue;
+        }
+        let Some(name) = entry_path
+            .parent()
+            .and_then(Path::to_str)
+            .and_then(pkg_name_of_dir)
+        else {
+            log::warn!(
+                "Unexpected entry in files database: {}",
+                entry_path.display()
+            );
+            continue;
+        };
+        text.clear();
+        entry
+            .read_to_string(&mut text)
+            .with_context(|| format!("Reading {}", entry_path.display()))?;
+        pkgs.push(FilesDbPkg {
+            name: name.into(),
+            files: parse_files_entry(&text),
+        });
+    }
+    pkgs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(pkgs)
+}
+
+/// `name-pkgver-pkgrel` -> `name`
+fn pkg_name_of_dir(dir: &str) -> Option<&str> {
+    let mut parts = dir.rsplitn(3, '-');
+    let (_rel, _ver) = (parts.next()?, parts.next()?);
+    parts.next()
+}
+
+/// The paths of the `%FILES%` section
+fn parse_files_entry(text: &str) -> Vec<Box<str>> {
+    text.lines()
+        .skip_while(|line| *line != "%FILES%")
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .map(Box::from)
+        .collect()
+}
+
+/// The package owning a file
+pub enum Owner {
+    Local(PkgIdx),
+    Sync { repo: SmolStr, name: SmolStr },
+}
+
+pub struct Hit {
+    pub owner: Owner,
+    /// Path relative to `/`, as listed in the database
+    pub path: Box<str>,
+}
+
+/// Every owned file matching `query`, in the local database and in `files_dbs`.
+///
+/// Returns whether the search stopped at [`MAX_HITS`].
+pub fn search(query: &FileQuery, dbs: &Dbs, files_dbs: &[FilesDb]) -> (Vec<Hit>, bool) {
+    let mut hits = Vec::new();
+    for (i, pkg) in dbs.local_pkgs().iter().enumerate() {
+        for file in pkg.files.iter().filter(|file| query.matches(file)) {
+            if hits.len() == MAX_HITS {
+                return (hits, true);
+            }
+            hits.push(Hit {
+                owner: Owner::Local(PkgIdx::from_usize(i)),
+                path: file.clone(),
+            });
+        }
+    }
+    for db in files_dbs {
+        for pkg in &db.pkgs {
+            for file in pkg.files.iter().filter(|file| query.matches(file)) {
+                if hits.len() == MAX_HITS {
+                    return (hits, true);
+                }
+                hits.push(Hit {
+                    owner: Owner::Sync {
+                        repo: db.repo.clone(),
+                        name: pkg.name.clone(),
+                    },
+                    path: file.clone(),
+                });
+            }
+        }
+    }
+    (hits, false)
+}
+
+#[test]
+fn test_file_query() {
+    let matches = |input, file| {
+        FileQuery::parse(input)
+            .ok()
+            .flatten()
+            .is_some_and(|query| query.matches(file))
+    };
+    assert!(matches("/usr/bin/ls", "usr/bin/ls"));
+    assert!(!matches("/usr/bin/ls", "usr/bin/lsblk"));
+    assert!(matches("/usr/share/licenses/", "usr/share/licenses/"));
+    assert!(matches("ls", "usr/bin/ls"));
+    assert!(!matches("ls", "usr/bin/lsblk"));
+    assert!(matches("libz.so*", "usr/lib/libz.so.1"));
+    assert!(!matches("libz.so*", "usr/lib/libzstd.so"));
+    assert!(matches("/usr/lib/*.so", "usr/lib/libz.so"));
+    assert!(!matches("/usr/lib/*.so", "usr/lib/foo/libz.so"));
+    assert!(matches!(FileQuery::parse("  "), Ok(None)));
+}
+
+#[test]
+fn test_parse_files_entry() {
+    let text = "%FILES%\nusr/\nusr/bin/\nusr/bin/ls\n\n%BACKUP%\netc/foo\n";
+    assert_eq!(
+        parse_files_entry(text),
+        ["usr/", "usr/bin/", "usr/bin/ls"].map(Box::from)
+    );
+    assert_eq!(pkg_name_of_dir("lib32-glibc-2.41-1"), Some("lib32-glibc"));
+    assert_eq!(pkg_name_of_dir("glibc"), None);
+}
@@ -0,0 +1,195 @@
+//! Files that no installed package owns, like the `lostfiles` script
+
+use {
+    crate::{file_owner::glob_to_regex, packages::Dbs},
+    regex::bytes::{RegexSet, RegexSetBuilder},
+    std::{
+        collections::{BTreeMap, HashSet},
+        ffi::OsStr,
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            mpsc::Receiver,
+        },
+        thread,
+    },
+};
+
+/// Lost files sharing a parent directory
+pub struct LostDir {
+    pub path: PathBuf,
+    /// File names and sizes, by name
+    pub files: Vec<(Box<str>, u64)>,
+    pub size: u64,
+}
+
+/// Paths owned by installed packages, relative to `/` and without a trailing `/`
+fn ownership_index(dbs: &Dbs) -> HashSet<&OsStr> {
+    dbs.local_pkgs()
+        .iter()
+        .flat_map(|pkg| &pkg.files)
+        .map(|file| OsStr::new(file.trim_end_matches('/')))
+        .collect()
+}
+
+/// Compile the exclusions, which are absolute paths with optional wildcards.
+///
+/// They match the raw bytes of paths, so paths that aren't valid UTF-8 are checked too.
+pub fn compile_exclusions(exclusions: &[String]) -> Result<RegexSet, regex::Error> {
+    RegexSetBuilder::new(
+        exclusions
+            .iter()
+            .map(|excl| excl.trim())
+            .filter(|excl| !excl.is_empty())
+            .map(|excl| glob_to_regex(excl.trim_matches('/'))),
+    )
+    // Let wildcards match any byte
+    .unicode(false)
+    .build()
+}
+
+struct Walker<'a> {
+    root_dir: &'a Path,
+    owned: HashSet<&'a OsStr>,
+    exclusions: &'a RegexSet,
+    scanned: &'a AtomicUsize,
+    found: &'a AtomicUsize,
+    cancel: &'a AtomicBool,
+    lost: BTreeMap<PathBuf, Vec<(Box<str>, u64)>>,
+}
+
+impl Walker<'_> {
+    /// Walk `start` without following symlinks. Unreadable directories are skipped.
+    fn walk(&mut self, start: &Path) {
+        let mut stack = vec![start.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            if self.cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let read_dir = match std::fs::read_dir(&dir) {
+                Ok(read_dir) => read_dir,
+                Err(e) => {
+                    log::warn!("Failed to read {}: {e}", dir.display());
+                    continue;
+                }
+            };
+            for entry in read_dir.flatten() {
+                self.scanned.fetch_add(1, Ordering::Relaxed);
+                let path = entry.path();
+                let Ok(rel) = path.strip_prefix(self.root_dir) else {
+                    continue;
+                };
+                let rel = rel.as_os_str();
+                if self.exclusions.is_match(rel.as_encoded_bytes()) {
+                    continue;
+                }
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    stack.push(path);
+                } else if !self.owned.contains(rel) {
+                    self.found.fetch_add(1, Ordering::Relaxed);
+                    let name = entry.file_name().to_string_lossy().into();
+                    self.lost
+                        .entry(dir.clone())
+                        .or_default()
+                        .push((name, meta.len()));
+                }
+            }
+        }
+    }
+}
+
+/// Group the lost files of each directory, sorted by name
+fn into_dirs(lost: BTreeMap<PathBuf, Vec<(Box<str>, u64)>>) -> Vec<LostDir> {
+    lost.into_iter()
+        .map(|(path, mut files)| {
+            files.sort_unstable();
+            LostDir {
+                path,
+                size: files.iter().map(|(_, size)| size).sum(),
+                files,
+            }
+        })
+        .collect()
+}
+
+/// Scanning for lost files on a background thread
+pub struct ScanJob {
+    /// Filesystem entries looked at so far
+    scanned: Arc<AtomicUsize>,
+    /// Lost files found so far
+    found: Arc<AtomicUsize>,
+    cancel: Arc<AtomicBool>,
+    recv: Receiver<Vec<LostDir>>,
+}
+
+impl ScanJob {
+    /// Scan `roots`, which are relative to the configured root directory.
+    ///
+    /// When cancelled, the files found until then are still reported.
+    pub fn spawn(dbs: Arc<Dbs>, roots: Vec<PathBuf>, exclusions: RegexSet) -> Self {
+        let (send, recv) = std::sync::mpsc::channel();
+        let scanned = Arc::new(AtomicUsize::new(0));
+        let found = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (scanned2, found2, cancel2) = (scanned.clone(), found.clone(), cancel.clone());
+        thread::spawn(move || {
+            let root_dir = &dbs.conf.root_dir;
+            let mut walker = Walker {
+                root_dir,
+                owned: ownership_index(&dbs),
+                exclusions: &exclusions,
+                scanned: &scanned2,
+                found: &found2,
+                cancel: &cancel2,
+                lost: BTreeMap::new(),
+            };
+            for root in &roots {
+                let rel = root.strip_prefix("/").unwrap_or(root);
+                walker.walk(&root_dir.join(rel));
+            }
+            if let Err(e) = send.send(into_dirs(walker.lost)) {
+                log::error!("Failed to send lost files: {e}");
+            }
+        });
+        Self {
+            scanned,
+            found,
+            cancel,
+            recv,
+        }
+    }
+    /// Entries scanned and lost files found so far
+    pub fn progress(&self) -> (usize, usize) {
+        (
+            self.scanned.load(Ordering::Relaxed),
+            self.found.load(Ordering::Relaxed),
+        )
+    }
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+    /// The results, once the scan is over
+    pub fn poll(&self) -> Option<Vec<LostDir>> {
+        self.recv.try_recv().ok()
+    }
+}
+
+#[test]
+fn test_compile_exclusions() {
+    let exclusions = ["/etc/ssl/certs", "/usr/lib/modules/*/modules.*", " "].map(String::from);
+    let set = compile_exclusions(&exclusions).expect("Invalid exclusions");
+    assert!(set.is_match(b"etc/ssl/certs"));
+    assert!(!set.is_match(b"etc/ssl/certs.bak"));
+    assert!(set.is_match(b"usr/lib/modules/6.12.1-arch1-1/modules.dep"));
+    assert!(!set.is_match(b"usr/lib/modules/6.12.1-arch1-1/kernel"));
+    assert!(set.is_match(b"usr/lib/modules/caf\xe9/modules.dep"));
+    let set = compile_exclusions(&["/srv/café".to_owned()]).expect("Invalid exclusions");
+    assert!(set.is_match("srv/café".as_bytes()));
+}
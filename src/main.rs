@@ -17,8 +17,19 @@ use {
 
 mod app;
 mod config;
+mod file_check;
+mod file_owner;
+mod lost_files;
 mod packages;
+mod pacman_conf;
+mod pacman_log;
+mod pkg_cache;
+mod pkg_column;
+mod pkg_filter;
 mod query_syntax;
+mod resolver;
+mod text_match;
+mod transaction;
 mod util;
 mod vercmp;
 
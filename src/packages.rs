@@ -1,4 +1,7 @@
-use {alpacka::Pkg, smol_str::SmolStr};
+use {crate::pacman_conf::PacmanConf, alpacka::Pkg, smol_str::SmolStr, std::path::Path};
+
+/// The database location libalpacka reads from
+const ALPACKA_DB_PATH: &str = "/var/lib/pacman/";
 
 /// Used to index into a package list in order to refer to a package efficiently
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -39,7 +42,7 @@ impl DbIdx {
 /// Refers to a package that's either in a local or a remote database
 ///
 /// Internally, it uses 8 bits for the db index, and 24 bits for the package index
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct PkgRef(u32);
 
 impl PkgRef {
@@ -107,6 +110,8 @@ pub struct PkgCache {
 pub struct Dbs {
     /// Invariant: dbs[0] is present, and it's the local db
     inner: Vec<Db>,
+    /// The pacman configuration the databases were loaded according to
+    pub conf: PacmanConf,
 }
 
 impl Dbs {
@@ -119,6 +124,34 @@ impl Dbs {
     pub fn resolve_local(&self, idx: PkgIdx) -> Option<&Pkg> {
         self.local_pkgs().get(idx.to_usize())
     }
+    /// Look up an installed package by name
+    pub fn local_pkg_by_name(&self, name: &str) -> Option<(PkgIdx, &Pkg)> {
+        let pkgs = self.local_pkgs();
+        // The local db is sorted by name on load
+        let idx = pkgs
+            .binary_search_by(|pkg| pkg.desc.name.as_str().cmp(name))
+            .ok()?;
+        pkgs.get(idx).map(|pkg| (PkgIdx::from_usize(idx), pkg))
+    }
+    /// The first sync db, in repo order, that has a package with this name
+    pub fn sync_db_of(&self, name: &str) -> Option<&Db> {
+        // Sync dbs are sorted by name on load
+        self.remotes().map(|(_, db)| db).find(|db| {
+            db.pkgs
+                .binary_search_by(|pkg| pkg.desc.name.as_str().cmp(name))
+                .is_ok()
+        })
+    }
+    /// Look up a sync package by name, in the first repo that has it
+    pub fn sync_pkg_by_name(&self, name: &str) -> Option<PkgRef> {
+        self.remotes()
+            .find_map(|(db_idx, db)| find_in_db(db_idx, db, name))
+    }
+    /// Look up a package of the sync db called `db_name`
+    pub fn sync_pkg_in(&self, db_name: &str, name: &str) -> Option<PkgRef> {
+        let (db_idx, db) = self.remotes().find(|(_, db)| db.name == db_name)?;
+        find_in_db(db_idx, db, name)
+    }
     pub fn local_pkgs(&self) -> &[Pkg] {
         // Invariant: self.dbs[0] is the local db
         #[expect(clippy::indexing_slicing)]
@@ -152,16 +185,52 @@ impl Dbs {
     }
 }
 
-pub type LoadResult = anyhow::Result<(PkgCache, Dbs)>;
+fn find_in_db(db_idx: DbIdx, db: &Db, name: &str) -> Option<PkgRef> {
+    // Databases are sorted by name on load
+    let idx = db
+        .pkgs
+        .binary_search_by(|pkg| pkg.desc.name.as_str().cmp(name))
+        .ok()?;
+    Some(PkgRef::from_components(db_idx, PkgIdx::from_usize(idx)))
+}
+
+/// Problems that prevented some, but not all databases from loading
+#[derive(Default)]
+pub struct LoadReport {
+    /// Repositories that couldn't be loaded, along with the reason
+    pub failed_repos: Vec<(SmolStr, String)>,
+    /// Why `pacman.conf` couldn't be read, in which case the default repositories are used
+    pub conf_error: Option<String>,
+}
+
+pub type LoadResult = anyhow::Result<(PkgCache, Dbs, LoadReport)>;
 pub type LoadRecv = std::sync::mpsc::Receiver<LoadResult>;
 
 pub fn spawn_load_thread() -> LoadRecv {
     let (send, recv) = std::sync::mpsc::channel();
-    std::thread::spawn(move || send.send(load()));
+    std::thread::spawn(move || {
+        if let Err(e) = send.send(load()) {
+            log::error!("Failed to send load result: {e}");
+        }
+    });
     recv
 }
 
 fn load() -> LoadResult {
+    let mut report = LoadReport::default();
+    let conf = PacmanConf::read_system().unwrap_or_else(|e| {
+        log::error!("Failed to read pacman.conf: {e:#}");
+        report.conf_error = Some(format!("{e:#}"));
+        PacmanConf::fallback()
+    });
+    // alpacka always reads the databases from the default location, so the files, log and
+    // cache of another root would be mixed with the host's databases
+    anyhow::ensure!(
+        conf.root_dir == Path::new("/") && conf.db_path == Path::new(ALPACKA_DB_PATH),
+        "Custom RootDir ({}) and DBPath ({}) are not supported, only / and {ALPACKA_DB_PATH}",
+        conf.root_dir.display(),
+        conf.db_path.display()
+    );
     let mut local_db = alpacka::read_local_db()?;
     local_db.sort_by(|a, b| a.desc.name.cmp(&b.desc.name));
     let mut syncdbs = Vec::new();
@@ -170,18 +239,25 @@ fn load() -> LoadResult {
         name: "local".into(),
         pkgs: local_db,
     });
-    for db_name in [
-        "core-testing",
-        "core",
-        "extra-testing",
-        "extra",
-        "multilib-testing",
-        "multilib",
-    ] {
-        let mut pkgs = alpacka::read_syncdb(db_name)?;
+    for repo in &conf.repos {
+        let path = conf.sync_db_path(&repo.name);
+        if !path.exists() {
+            report.failed_repos.push((
+                repo.name.clone(),
+                format!("Database doesn't exist at {}. Try syncing.", path.display()),
+            ));
+            continue;
+        }
+        let mut pkgs = match alpacka::read_syncdb(repo.name.as_str()) {
+            Ok(pkgs) => pkgs,
+            Err(e) => {
+                report.failed_repos.push((repo.name.clone(), e.to_string()));
+                continue;
+            }
+        };
         pkgs.sort_by(|a, b| a.desc.name.cmp(&b.desc.name));
         syncdbs.push(Db {
-            name: db_name.into(),
+            name: repo.name.clone(),
             pkgs,
         });
     }
@@ -201,6 +277,94 @@ fn load() -> LoadResult {
                 vec
             },
         },
-        Dbs { inner: syncdbs },
+        Dbs {
+            inner: syncdbs,
+            conf,
+        },
+        report,
     ))
 }
+
+/// Packages and databases for tests
+#[cfg(test)]
+pub mod fixtures {
+    use {
+        super::{Db, Dbs},
+        crate::pacman_conf::PacmanConf,
+        alpacka::Pkg,
+    };
+
+    /// Databases from (name, packages) pairs, the first being the local db
+    pub fn dbs(pkgs: Vec<(&str, Vec<Pkg>)>) -> Dbs {
+        let conf = PacmanConf::parse("", &|_| Err(std::io::ErrorKind::NotFound.into()))
+            .expect("Empty config is valid");
+        Dbs {
+            inner: pkgs
+                .into_iter()
+                .map(|(name, mut pkgs)| {
+                    pkgs.sort_by(|a, b| a.desc.name.cmp(&b.desc.name));
+                    Db {
+                        name: name.into(),
+                        pkgs,
+                    }
+                })
+                .collect(),
+            conf,
+        }
+    }
+
+    /// A package. Dependencies are written like `foo>=1.2`.
+    pub fn pkg(name: &str, version: &str, depends: &[&str]) -> Pkg {
+        Pkg {
+            desc: alpacka::PkgDesc {
+                name: name.into(),
+                version: version.into(),
+                depends: depends.iter().map(|src| dep(src)).collect(),
+                ..Default::default()
+            },
+            files: Vec::new(),
+        }
+    }
+
+    /// A dependency, written like `foo>=1.2`
+    pub fn dep(src: &str) -> alpacka::Depend {
+        use alpacka::VerCmp;
+        let Some(pos) = src.find(['<', '>', '=']) else {
+            return alpacka::Depend {
+                name: src.into(),
+                ver: None,
+            };
+        };
+        let (name, rest) = src.split_at(pos);
+        let (cmp, ver) = [
+            (">=", VerCmp::Ge),
+            ("<=", VerCmp::Le),
+            ("=", VerCmp::Eq),
+            ("<", VerCmp::Lt),
+            (">", VerCmp::Gt),
+        ]
+        .into_iter()
+        .find_map(|(op, cmp)| rest.strip_prefix(op).map(|ver| (cmp, ver)))
+        .expect("Valid comparison operator");
+        alpacka::Depend {
+            name: name.into(),
+            ver: Some(alpacka::DepVer {
+                ver: ver.into(),
+                cmp,
+            }),
+        }
+    }
+
+    /// Installed as a dependency
+    pub fn as_dep(mut pkg: Pkg) -> Pkg {
+        pkg.desc.install_reason = alpacka::InstallReason::Dep;
+        pkg
+    }
+
+    /// With an installed size of `size` and a download size of `csize`
+    pub fn sized(mut pkg: Pkg, size: i64, csize: i64) -> Pkg {
+        pkg.desc.size = size;
+        pkg.desc.csize = csize;
+        pkg
+    }
+}
@@ -0,0 +1,282 @@
+//! Parsing of `pacman.conf`, see `pacman.conf(5)`
+
+use {
+    crate::util::wildcard_match,
+    smol_str::SmolStr,
+    std::path::{Path, PathBuf},
+};
+
+pub const DEFAULT_PATH: &str = "/etc/pacman.conf";
+
+/// Pacman refuses to follow includes deeper than this, so do we
+const MAX_INCLUDE_DEPTH: u8 = 10;
+
+/// Repositories maintained by Arch Linux
+const ARCH_REPOS: [&str; 6] = [
+    "core",
+    "extra",
+    "core-testing",
+    "extra-testing",
+    "multilib",
+    "multilib-testing",
+];
+
+/// Repositories used when `pacman.conf` can't be read, in pacman's order
+const FALLBACK_REPOS: [&str; 6] = [
+    "core-testing",
+    "core",
+    "extra-testing",
+    "extra",
+    "multilib-testing",
+    "multilib",
+];
+
+#[derive(Debug, PartialEq)]
+pub struct PacmanConf {
+    pub root_dir: PathBuf,
+    pub db_path: PathBuf,
+    pub cache_dirs: Vec<PathBuf>,
+    pub log_file: PathBuf,
+    /// Sync repositories, in the order they are declared
+    pub repos: Vec<Repo>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Repo {
+    pub name: SmolStr,
+    pub servers: Vec<String>,
+}
+
+impl Repo {
+    pub fn is_arch(&self) -> bool {
+        ARCH_REPOS.contains(&self.name.as_str())
+    }
+}
+
+/// Options that pacman derives from other options if they are not set explicitly
+#[derive(Default)]
+struct Raw {
+    root_dir: Option<PathBuf>,
+    db_path: Option<PathBuf>,
+    cache_dirs: Vec<PathBuf>,
+    log_file: Option<PathBuf>,
+    repos: Vec<Repo>,
+}
+
+enum Section {
+    None,
+    Options,
+    Repo,
+}
+
+impl PacmanConf {
+    /// Read the system pacman config (`/etc/pacman.conf`)
+    pub fn read_system() -> anyhow::Result<Self> {
+        Self::read(Path::new(DEFAULT_PATH))
+    }
+    /// Default options with the Arch Linux repositories, for when the config can't be read
+    pub fn fallback() -> Self {
+        Self {
+            repos: FALLBACK_REPOS
+                .into_iter()
+                .map(|name| Repo {
+                    name: name.into(),
+                    servers: Vec::new(),
+                })
+                .collect(),
+            ..Self::parse("", &|_| Err(std::io::ErrorKind::NotFound.into()))
+                .expect("Empty config is valid")
+        }
+    }
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let src = std::fs::read_to_string(path)?;
+        Self::parse(&src, &|path| std::fs::read_to_string(path))
+    }
+    /// Parse from a string. `read` is used to read the files pulled in by `Include`.
+    pub fn parse(
+        src: &str,
+        read: &dyn Fn(&Path) -> std::io::Result<String>,
+    ) -> anyhow::Result<Self> {
+        let mut raw = Raw::default();
+        let mut section = Section::None;
+        parse_into(&mut raw, &mut section, src, read, 0)?;
+        let root_dir = raw.root_dir.unwrap_or_else(|| PathBuf::from("/"));
+        let db_path = raw
+            .db_path
+            .unwrap_or_else(|| root_dir.join("var/lib/pacman/"));
+        let log_file = raw
+            .log_file
+            .unwrap_or_else(|| root_dir.join("var/log/pacman.log"));
+        let cache_dirs = if raw.cache_dirs.is_empty() {
+            vec![PathBuf::from("/var/cache/pacman/pkg/")]
+        } else {
+            raw.cache_dirs
+        };
+        Ok(Self {
+            root_dir,
+            db_path,
+            cache_dirs,
+            log_file,
+            repos: raw.repos,
+        })
+    }
+    pub fn repo(&self, name: &str) -> Option<&Repo> {
+        self.repos.iter().find(|repo| repo.name == name)
+    }
+    /// Path of the sync database file for a repo
+    pub fn sync_db_path(&self, repo_name: &str) -> PathBuf {
+        self.db_path.join("sync").join(format!("{repo_name}.db"))
+    }
+    /// Directory of an installed package in the local database
+    pub fn local_pkg_dir(&self, name: &str, version: &str) -> PathBuf {
+        self.db_path.join("local").join(format!("{name}-{version}"))
+    }
+}
+
+fn parse_into(
+    raw: &mut Raw,
+    section: &mut Section,
+    src: &str,
+    read: &dyn Fn(&Path) -> std::io::Result<String>,
+    depth: u8,
+) -> anyhow::Result<()> {
+    for (line_no, line) in src.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if name == "options" {
+                *section = Section::Options;
+            } else {
+                *section = Section::Repo;
+                raw.repos.push(Repo {
+                    name: name.into(),
+                    servers: Vec::new(),
+                });
+            }
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => (line, ""),
+        };
+        match (&section, key) {
+            (_, "Include") => {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    anyhow::bail!("Include depth limit exceeded at {value}");
+                }
+                for path in expand_include(value) {
+                    match read(&path) {
+                        Ok(src) => parse_into(raw, section, &src, read, depth + 1)?,
+                        Err(e) => log::warn!("Failed to include {}: {e}", path.display()),
+                    }
+                }
+            }
+            (Section::Options, "RootDir") => raw.root_dir = Some(value.into()),
+            (Section::Options, "DBPath") => raw.db_path = Some(value.into()),
+            (Section::Options, "CacheDir") => {
+                raw.cache_dirs
+                    .extend(value.split_ascii_whitespace().map(PathBuf::from));
+            }
+            (Section::Options, "LogFile") => raw.log_file = Some(value.into()),
+            (Section::Repo, "Server") => {
+                if let Some(repo) = raw.repos.last_mut() {
+                    repo.servers.push(value.to_owned());
+                }
+            }
+            (Section::None, _) => {
+                anyhow::bail!("Line {}: `{key}` outside of any section", line_no + 1);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Expand the (possibly globbed) file name of an `Include` value
+fn expand_include(value: &str) -> Vec<PathBuf> {
+    let path = Path::new(value);
+    let (Some(dir), Some(file_pat)) = (path.parent(), path.file_name().and_then(|f| f.to_str()))
+    else {
+        return vec![path.to_owned()];
+    };
+    if !file_pat.contains(['*', '?']) {
+        return vec![path.to_owned()];
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|en| {
+            let en = en.ok()?;
+            wildcard_match(file_pat, en.file_name().to_str()?).then(|| en.path())
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_parse() {
+    let conf = "
+# Comment
+[options]
+RootDir = /mnt
+CacheDir = /a /b
+HoldPkg = pacman glibc
+
+[core]
+Include = /etc/pacman.d/mirrorlist
+
+[chaotic-aur]
+Server = https://example.com/$repo/$arch # trailing comment
+
+[extra]
+Include = /etc/pacman.d/mirrorlist
+";
+    let read = |path: &Path| -> std::io::Result<String> {
+        assert_eq!(path, Path::new("/etc/pacman.d/mirrorlist"));
+        Ok("Server = https://mirror/$repo/os/$arch\n".to_owned())
+    };
+    let conf = PacmanConf::parse(conf, &read).expect("Failed to parse");
+    assert_eq!(conf.root_dir, Path::new("/mnt"));
+    assert_eq!(conf.db_path, Path::new("/mnt/var/lib/pacman/"));
+    assert_eq!(conf.log_file, Path::new("/mnt/var/log/pacman.log"));
+    assert_eq!(conf.cache_dirs, [Path::new("/a"), Path::new("/b")]);
+    let names: Vec<&str> = conf.repos.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["core", "chaotic-aur", "extra"]);
+    let servers: Vec<&[String]> = conf.repos.iter().map(|r| r.servers.as_slice()).collect();
+    assert_eq!(
+        servers,
+        [
+            ["https://mirror/$repo/os/$arch".to_owned()].as_slice(),
+            &["https://example.com/$repo/$arch".to_owned()],
+            &["https://mirror/$repo/os/$arch".to_owned()],
+        ]
+    );
+    assert!(conf.repo("extra").is_some_and(Repo::is_arch));
+    assert!(conf.repo("chaotic-aur").is_some_and(|repo| !repo.is_arch()));
+}
+
+#[test]
+fn test_parse_errors() {
+    let no_read =
+        |_: &Path| -> std::io::Result<String> { Err(std::io::ErrorKind::NotFound.into()) };
+    assert!(PacmanConf::parse("Server = foo", &no_read).is_err());
+    let recursive = |_: &Path| -> std::io::Result<String> { Ok("Include = /self\n".to_owned()) };
+    assert!(PacmanConf::parse("[options]\nInclude = /self", &recursive).is_err());
+}
+
+#[test]
+fn test_fallback() {
+    let conf = PacmanConf::fallback();
+    assert_eq!(conf.db_path, Path::new("/var/lib/pacman/"));
+    assert_eq!(
+        conf.repos
+            .iter()
+            .map(|repo| repo.name.as_str())
+            .collect::<Vec<_>>(),
+        FALLBACK_REPOS
+    );
+}
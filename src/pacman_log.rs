@@ -0,0 +1,214 @@
+//! Parsing of `pacman.log` into transactions
+
+use {smol_str::SmolStr, std::path::Path};
+
+#[derive(Debug, PartialEq)]
+pub struct Transaction {
+    /// Timestamp of the `transaction started` line, as written in the log
+    pub started: SmolStr,
+    /// The pacman command line that ran the transaction, if logged
+    pub command: Option<String>,
+    pub outcome: Outcome,
+    pub changes: Vec<Change>,
+}
+
+impl Transaction {
+    /// The `YYYY-MM-DD` part of the timestamp
+    pub fn date(&self) -> &str {
+        self.started.get(..10).unwrap_or(&self.started)
+    }
+    /// Date and time, without seconds and time zone
+    pub fn date_time(&self) -> String {
+        let time = self.started.get(11..16).unwrap_or_default();
+        format!("{} {time}", self.date())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    Completed,
+    Failed,
+    Interrupted,
+    /// The log ends before the transaction does
+    Unfinished,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub pkg: SmolStr,
+    /// Version before the change, if it was installed
+    pub old_ver: Option<SmolStr>,
+    /// Version after the change, if it's still installed
+    pub new_ver: Option<SmolStr>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChangeKind {
+    Installed,
+    Upgraded,
+    Downgraded,
+    Reinstalled,
+    Removed,
+}
+
+impl ChangeKind {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Installed => "installed",
+            Self::Upgraded => "upgraded",
+            Self::Downgraded => "downgraded",
+            Self::Reinstalled => "reinstalled",
+            Self::Removed => "removed",
+        }
+    }
+}
+
+pub fn read(path: &Path) -> anyhow::Result<Vec<Transaction>> {
+    let bytes = std::fs::read(path)?;
+    Ok(parse(&String::from_utf8_lossy(&bytes)))
+}
+
+/// Parse the transactions of a log, oldest first.
+///
+/// Changes logged outside of `transaction started`/`completed` blocks are ignored.
+pub fn parse(src: &str) -> Vec<Transaction> {
+    let mut transactions = Vec::new();
+    let mut current: Option<Transaction> = None;
+    let mut last_command = None;
+    for line in src.lines() {
+        let Some((timestamp, source, msg)) = split_line(line) else {
+            continue;
+        };
+        match (source, msg) {
+            ("PACMAN", msg) => {
+                if let Some(cmd) = msg
+                    .strip_prefix("Running '")
+                    .and_then(|rest| rest.strip_suffix('\''))
+                {
+                    last_command = Some(cmd.to_owned());
+                }
+            }
+            ("ALPM", "transaction started") => {
+                transactions.extend(current.take());
+                current = Some(Transaction {
+                    started: timestamp.into(),
+                    command: last_command.take(),
+                    outcome: Outcome::Unfinished,
+                    changes: Vec::new(),
+                });
+            }
+            (
+                "ALPM",
+                "transaction completed" | "transaction failed" | "transaction interrupted",
+            ) => {
+                if let Some(mut transaction) = current.take() {
+                    transaction.outcome = match msg {
+                        "transaction completed" => Outcome::Completed,
+                        "transaction failed" => Outcome::Failed,
+                        _ => Outcome::Interrupted,
+                    };
+                    transactions.push(transaction);
+                }
+            }
+            ("ALPM", msg) => {
+                if let Some(transaction) = &mut current
+                    && let Some(change) = parse_change(msg)
+                {
+                    transaction.changes.push(change);
+                }
+            }
+            _ => {}
+        }
+    }
+    transactions.extend(current);
+    transactions
+}
+
+/// `[timestamp] [source] message`
+fn split_line(line: &str) -> Option<(&str, &str, &str)> {
+    let (timestamp, rest) = line.strip_prefix('[')?.split_once("] ")?;
+    let (source, msg) = rest.strip_prefix('[')?.split_once("] ")?;
+    Some((timestamp, source, msg))
+}
+
+/// `upgraded foo (1.0-1 -> 1.1-1)`, `installed foo (1.0-1)`, etc.
+fn parse_change(msg: &str) -> Option<Change> {
+    let (verb, rest) = msg.split_once(' ')?;
+    let (pkg, versions) = rest.split_once(" (")?;
+    let versions = versions.strip_suffix(')')?;
+    let kind = match verb {
+        "installed" => ChangeKind::Installed,
+        "upgraded" => ChangeKind::Upgraded,
+        "downgraded" => ChangeKind::Downgraded,
+        "reinstalled" => ChangeKind::Reinstalled,
+        "removed" => ChangeKind::Removed,
+        _ => return None,
+    };
+    let (old_ver, new_ver) = match kind {
+        ChangeKind::Installed => (None, Some(versions.into())),
+        ChangeKind::Removed => (Some(versions.into()), None),
+        ChangeKind::Reinstalled => (Some(versions.into()), Some(versions.into())),
+        ChangeKind::Upgraded | ChangeKind::Downgraded => {
+            let (old, new) = versions.split_once(" -> ")?;
+            (Some(old.into()), Some(new.into()))
+        }
+    };
+    Some(Change {
+        kind,
+        pkg: pkg.into(),
+        old_ver,
+        new_ver,
+    })
+}
+
+#[test]
+fn test_parse() {
+    let log = "\
+[2024-01-15T10:23:45+0100] [PACMAN] Running 'pacman -Syu'
+[2024-01-15T10:23:45+0100] [PACMAN] synchronizing package lists
+[2024-01-15T10:24:01+0100] [ALPM] transaction started
+[2024-01-15T10:24:02+0100] [ALPM] upgraded linux (6.7.0.arch3-1 -> 6.7.1.arch1-1)
+[2024-01-15T10:24:02+0100] [ALPM] installed foo (1.0-1)
+[2024-01-15T10:24:02+0100] [ALPM-SCRIPTLET] some output (not a change)
+[2024-01-15T10:24:02+0100] [ALPM] removed bar (2.0-1)
+[2024-01-15T10:24:05+0100] [ALPM] transaction completed
+[2024-01-16T08:00:00+0100] [ALPM] installed stray (1-1)
+[2024-01-16T09:00:00+0100] [ALPM] transaction started
+[2024-01-16T09:00:01+0100] [ALPM] downgraded baz (2.0-1 -> 1.0-1)
+";
+    let transactions = parse(log);
+    assert_eq!(transactions.len(), 2);
+    let [first, second] = transactions.as_slice() else {
+        panic!("Expected 2 transactions");
+    };
+    assert_eq!(first.command.as_deref(), Some("pacman -Syu"));
+    assert_eq!(first.outcome, Outcome::Completed);
+    assert_eq!(first.date_time(), "2024-01-15 10:24");
+    assert_eq!(
+        first.changes,
+        [
+            Change {
+                kind: ChangeKind::Upgraded,
+                pkg: "linux".into(),
+                old_ver: Some("6.7.0.arch3-1".into()),
+                new_ver: Some("6.7.1.arch1-1".into()),
+            },
+            Change {
+                kind: ChangeKind::Installed,
+                pkg: "foo".into(),
+                old_ver: None,
+                new_ver: Some("1.0-1".into()),
+            },
+            Change {
+                kind: ChangeKind::Removed,
+                pkg: "bar".into(),
+                old_ver: Some("2.0-1".into()),
+                new_ver: None,
+            },
+        ]
+    );
+    assert_eq!(second.command, None);
+    assert_eq!(second.outcome, Outcome::Unfinished);
+    assert_eq!(second.changes.len(), 1);
+}
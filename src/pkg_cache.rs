@@ -0,0 +1,187 @@
+//! Package files in pacman's cache directories
+
+use {
+    crate::vercmp::{AbCmp, vercmp},
+    smol_str::SmolStr,
+    std::{cmp::Ordering, collections::HashSet, path::PathBuf, sync::mpsc::Receiver, thread},
+};
+
+pub struct CachedPkg {
+    pub path: PathBuf,
+    pub name: SmolStr,
+    /// `[epoch:]pkgver-pkgrel`
+    pub version: SmolStr,
+    pub arch: SmolStr,
+    /// File size in bytes
+    pub size: u64,
+    /// Whether a detached `.sig` file exists next to it
+    pub has_sig: bool,
+}
+
+impl CachedPkg {
+    pub fn sig_path(&self) -> Option<PathBuf> {
+        self.has_sig.then(|| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(".sig");
+            path.into()
+        })
+    }
+}
+
+/// List the package files in the cache directories.
+///
+/// Directories that can't be read are skipped.
+pub fn scan(dirs: &[PathBuf]) -> Vec<CachedPkg> {
+    let mut pkgs = Vec::new();
+    for dir in dirs {
+        let read_dir = match std::fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                log::warn!("Failed to read cache dir {}: {e}", dir.display());
+                continue;
+            }
+        };
+        let entries: Vec<_> = read_dir.filter_map(Result::ok).collect();
+        let file_names: HashSet<String> = entries
+            .iter()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        for entry in entries {
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let Some((name, version, arch)) = parse_file_name(&file_name) else {
+                continue;
+            };
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if !meta.is_file() {
+                continue;
+            }
+            pkgs.push(CachedPkg {
+                has_sig: file_names.contains(&format!("{file_name}.sig")),
+                path: entry.path(),
+                name: name.into(),
+                version: version.into(),
+                arch: arch.into(),
+                size: meta.len(),
+            });
+        }
+    }
+    pkgs
+}
+
+/// [`scan`] and [`sort`] on a background thread
+pub fn spawn_scan(dirs: Vec<PathBuf>) -> Receiver<Vec<CachedPkg>> {
+    let (send, recv) = std::sync::mpsc::channel();
+    thread::spawn(move || {
+        let mut pkgs = scan(&dirs);
+        sort(&mut pkgs);
+        if let Err(e) = send.send(pkgs) {
+            log::error!("Failed to send cached packages: {e}");
+        }
+    });
+    recv
+}
+
+/// Sort by name and architecture, then newest version first
+pub fn sort(pkgs: &mut [CachedPkg]) {
+    pkgs.sort_by(|a, b| {
+        (&a.name, &a.arch).cmp(&(&b.name, &b.arch)).then_with(|| {
+            match vercmp(&a.version, &b.version) {
+                AbCmp::ANewer => Ordering::Less,
+                AbCmp::Same => Ordering::Equal,
+                AbCmp::BNewer => Ordering::Greater,
+            }
+        })
+    });
+}
+
+/// Indices of the files beyond the newest `keep` versions of each package and architecture,
+/// like `paccache`.
+///
+/// `pkgs` must be sorted with [`sort`].
+pub fn old_versions(pkgs: &[CachedPkg], keep: usize) -> Vec<usize> {
+    let mut old = Vec::new();
+    let mut n_newer = 0;
+    let mut prev = None;
+    for (i, pkg) in pkgs.iter().enumerate() {
+        if prev != Some((&pkg.name, &pkg.arch)) {
+            n_newer = 0;
+            prev = Some((&pkg.name, &pkg.arch));
+        }
+        if n_newer >= keep {
+            old.push(i);
+        }
+        n_newer += 1;
+    }
+    old
+}
+
+/// `name-pkgver-pkgrel-arch.pkg.tar[.ext]` into name, version and arch
+fn parse_file_name(file_name: &str) -> Option<(&str, String, &str)> {
+    let (stem, ext) = file_name.rsplit_once(".pkg.tar")?;
+    if !matches!(
+        ext,
+        "" | ".zst" | ".xz" | ".gz" | ".bz2" | ".lz4" | ".lzo" | ".lrz" | ".lz" | ".Z"
+    ) {
+        return None;
+    }
+    let (rest, arch) = stem.rsplit_once('-')?;
+    let (rest, rel) = rest.rsplit_once('-')?;
+    let (name, ver) = rest.rsplit_once('-')?;
+    Some((name, format!("{ver}-{rel}"), arch))
+}
+
+#[test]
+fn test_old_versions() {
+    let pkg = |name: &str, version: &str, arch: &str| CachedPkg {
+        path: PathBuf::new(),
+        name: name.into(),
+        version: version.into(),
+        arch: arch.into(),
+        size: 0,
+        has_sig: false,
+    };
+    let mut pkgs = vec![
+        pkg("foo", "1.0-1", "x86_64"),
+        pkg("bar", "1-1", "any"),
+        pkg("foo", "1.10-1", "x86_64"),
+        pkg("foo", "1.2-1", "x86_64"),
+        pkg("foo", "1.0-1", "i686"),
+    ];
+    sort(&mut pkgs);
+    let versions: Vec<(&str, &str)> = pkgs
+        .iter()
+        .map(|pkg| (pkg.version.as_str(), pkg.arch.as_str()))
+        .collect();
+    assert_eq!(
+        versions,
+        [
+            ("1-1", "any"),
+            ("1.0-1", "i686"),
+            ("1.10-1", "x86_64"),
+            ("1.2-1", "x86_64"),
+            ("1.0-1", "x86_64")
+        ]
+    );
+    assert_eq!(old_versions(&pkgs, 2), [4]);
+    assert_eq!(old_versions(&pkgs, 1), [3, 4]);
+    assert_eq!(old_versions(&pkgs, 0), [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn test_parse_file_name() {
+    assert_eq!(
+        parse_file_name("linux-firmware-20240115.9b6d0b08-1-any.pkg.tar.zst"),
+        Some(("linux-firmware", "20240115.9b6d0b08-1".into(), "any"))
+    );
+    assert_eq!(
+        parse_file_name("python-3:1.0-2-x86_64.pkg.tar.xz"),
+        Some(("python", "3:1.0-2".into(), "x86_64"))
+    );
+    assert_eq!(parse_file_name("foo-1.0-1-x86_64.pkg.tar.zst.sig"), None);
+    assert_eq!(parse_file_name("foo-1.0-1-x86_64.pkg.tar.zst.part"), None);
+    assert_eq!(parse_file_name("foo.pkg.tar.zst"), None);
+}
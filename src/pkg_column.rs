@@ -0,0 +1,167 @@
+//! Columns of the package list tables, and sorting by them
+
+use {
+    crate::vercmp::{AbCmp, vercmp},
+    alpacka::Pkg,
+    serde::{Deserialize, Serialize},
+    std::cmp::Ordering,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PkgColumn {
+    Name,
+    Version,
+    Description,
+    InstalledSize,
+    InstallDate,
+    BuildDate,
+    Repo,
+    Packager,
+    Arch,
+}
+
+impl PkgColumn {
+    /// In display order
+    pub const ALL: [Self; 9] = [
+        Self::Name,
+        Self::Version,
+        Self::InstalledSize,
+        Self::InstallDate,
+        Self::BuildDate,
+        Self::Repo,
+        Self::Packager,
+        Self::Arch,
+        Self::Description,
+    ];
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Version => "Version",
+            Self::Description => "Description",
+            Self::InstalledSize => "Installed size",
+            Self::InstallDate => "Install date",
+            Self::BuildDate => "Build date",
+            Self::Repo => "Repo",
+            Self::Packager => "Packager",
+            Self::Arch => "Arch",
+        }
+    }
+    /// Whether the table can be sorted by this column
+    pub const fn sortable(self) -> bool {
+        !matches!(self, Self::Description | Self::Arch)
+    }
+    /// Compare two packages by this column, in ascending order.
+    ///
+    /// `repo` is the name of the repository the package comes from.
+    pub fn compare(self, (a, a_repo): (&Pkg, &str), (b, b_repo): (&Pkg, &str)) -> Ordering {
+        let (a, b) = (&a.desc, &b.desc);
+        match self {
+            Self::Name => a.name.cmp(&b.name),
+            Self::Version => match vercmp(&a.version, &b.version) {
+                AbCmp::ANewer => Ordering::Greater,
+                AbCmp::Same => Ordering::Equal,
+                AbCmp::BNewer => Ordering::Less,
+            },
+            Self::Description => a.desc.cmp(&b.desc),
+            Self::InstalledSize => a.size.cmp(&b.size),
+            Self::InstallDate => a.install_date.cmp(&b.install_date),
+            Self::BuildDate => a.build_date.cmp(&b.build_date),
+            Self::Repo => a_repo.cmp(b_repo),
+            Self::Packager => a.packager.cmp(&b.packager),
+            Self::Arch => a.arch.to_string().cmp(&b.arch.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SortDir {
+    Ascending,
+    Descending,
+}
+
+/// Visible columns and sort order of a package list table
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct TableLayout {
+    /// In the order of [`PkgColumn::ALL`]
+    pub columns: Vec<PkgColumn>,
+    /// If `None`, packages are listed in database (or relevance) order
+    pub sort: Option<(PkgColumn, SortDir)>,
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        Self {
+            columns: vec![PkgColumn::Name, PkgColumn::Version, PkgColumn::Description],
+            sort: None,
+        }
+    }
+}
+
+impl TableLayout {
+    pub fn is_visible(&self, column: PkgColumn) -> bool {
+        self.columns.contains(&column)
+    }
+    pub fn set_visible(&mut self, column: PkgColumn, visible: bool) {
+        let columns = PkgColumn::ALL.into_iter().filter(|&col| {
+            if col == column {
+                visible
+            } else {
+                self.columns.contains(&col)
+            }
+        });
+        self.columns = columns.collect();
+        if !visible && self.sort.is_some_and(|(col, _)| col == column) {
+            self.sort = None;
+        }
+    }
+    /// Sort by `column` ascending, then descending, then not at all
+    pub fn toggle_sort(&mut self, column: PkgColumn) {
+        self.sort = match self.sort {
+            Some((col, SortDir::Ascending)) if col == column => Some((column, SortDir::Descending)),
+            Some((col, SortDir::Descending)) if col == column => None,
+            _ => Some((column, SortDir::Ascending)),
+        };
+    }
+    /// Compare two packages according to the sort order
+    pub fn compare(&self, a: (&Pkg, &str), b: (&Pkg, &str)) -> Ordering {
+        match self.sort {
+            Some((col, SortDir::Ascending)) => col.compare(a, b),
+            Some((col, SortDir::Descending)) => col.compare(b, a),
+            None => Ordering::Equal,
+        }
+    }
+}
+
+#[test]
+fn test_table_layout() {
+    let mut layout = TableLayout::default();
+    layout.set_visible(PkgColumn::BuildDate, true);
+    assert_eq!(
+        layout.columns,
+        [
+            PkgColumn::Name,
+            PkgColumn::Version,
+            PkgColumn::BuildDate,
+            PkgColumn::Description
+        ]
+    );
+    layout.toggle_sort(PkgColumn::BuildDate);
+    assert_eq!(
+        layout.sort,
+        Some((PkgColumn::BuildDate, SortDir::Ascending))
+    );
+    layout.toggle_sort(PkgColumn::BuildDate);
+    assert_eq!(
+        layout.sort,
+        Some((PkgColumn::BuildDate, SortDir::Descending))
+    );
+    layout.toggle_sort(PkgColumn::BuildDate);
+    assert_eq!(layout.sort, None);
+    layout.toggle_sort(PkgColumn::Name);
+    assert_eq!(layout.sort, Some((PkgColumn::Name, SortDir::Ascending)));
+    layout.set_visible(PkgColumn::BuildDate, false);
+    assert_eq!(layout.sort, Some((PkgColumn::Name, SortDir::Ascending)));
+    layout.toggle_sort(PkgColumn::Version);
+    layout.set_visible(PkgColumn::Version, false);
+    assert_eq!(layout.sort, None);
+}
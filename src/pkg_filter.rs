@@ -0,0 +1,268 @@
+//! Evaluation of package list queries against the loaded databases
+
+use {
+    crate::{
+        packages::{Dbs, PkgIdx, PkgRef},
+        query_syntax::{Flag, PkgListQuery, Subject},
+        vercmp::{AbCmp, vercmp},
+    },
+    alpacka::{InstallReason, Pkg, PkgDesc},
+    std::{cell::OnceCell, collections::HashMap},
+};
+
+/// Evaluates queries for packages of both local and remote databases.
+///
+/// Data that's expensive to compute for every package is computed once on demand,
+/// so the same `PkgFilter` should be used for filtering a whole list.
+pub struct PkgFilter<'db> {
+    dbs: &'db Dbs,
+    /// First package with a given name in the sync dbs, in repo order
+    sync_by_name: OnceCell<HashMap<&'db str, &'db Pkg>>,
+    /// Indexed by local package index
+    requirements: OnceCell<Vec<Requirement>>,
+}
+
+/// How much a local package is needed by other local packages
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Requirement {
+    None,
+    Optional,
+    Required,
+}
+
+impl<'db> PkgFilter<'db> {
+    pub const fn new(dbs: &'db Dbs) -> Self {
+        Self {
+            dbs,
+            sync_by_name: OnceCell::new(),
+            requirements: OnceCell::new(),
+        }
+    }
+    /// Returns `None` if the package doesn't match the query, otherwise its score
+    pub fn score(&self, query: &PkgListQuery, pkg_ref: PkgRef) -> Option<u32> {
+        let (Some(db), Some(pkg)) = self.dbs.resolve(pkg_ref) else {
+            return None;
+        };
+        query.score(&PkgSubject {
+            filter: self,
+            pkg_ref,
+            pkg,
+            db_name: &db.name,
+        })
+    }
+    /// Whether `pkg` has `flag`.
+    ///
+    /// Flags describing an installed package (like `Explicit`) refer to the installed
+    /// counterpart of remote packages.
+    /// Version flags (`Newer`, `Older`) compare local packages to their sync db counterparts,
+    /// and remote packages to their installed counterparts.
+    fn has_flag(&self, pkg_ref: PkgRef, pkg: &Pkg, flag: Flag) -> bool {
+        let local = if pkg_ref.is_local() {
+            let (_, idx) = pkg_ref.into_components();
+            Some((idx, pkg))
+        } else {
+            self.dbs.local_pkg_by_name(&pkg.desc.name)
+        };
+        match flag {
+            Flag::Installed => local.is_some(),
+            Flag::Explicit => local.is_some_and(|(_, local)| is_explicit(&local.desc)),
+            Flag::Foreign => {
+                pkg_ref.is_local() && !self.sync_by_name().contains_key(pkg.desc.name.as_str())
+            }
+            Flag::Orphan => self.local_requirement(local) == Some(Requirement::None),
+            Flag::OptionalOnly => self.local_requirement(local) == Some(Requirement::Optional),
+            Flag::Newer | Flag::Older | Flag::Upgradable => {
+                let counterpart = if pkg_ref.is_local() {
+                    self.sync_by_name().get(pkg.desc.name.as_str()).copied()
+                } else {
+                    local.map(|(_, local)| local)
+                };
+                let Some(counterpart) = counterpart else {
+                    return false;
+                };
+                let cmp = vercmp(&pkg.desc.version, &counterpart.desc.version);
+                match flag {
+                    Flag::Newer => cmp == AbCmp::ANewer,
+                    Flag::Older => cmp == AbCmp::BNewer,
+                    // The remote package is the upgrade, or the local one has an upgrade
+                    _ if pkg_ref.is_local() => cmp == AbCmp::BNewer,
+                    _ => cmp == AbCmp::ANewer,
+                }
+            }
+        }
+    }
+    /// Requirement of a local package installed as a dependency.
+    ///
+    /// `None` for packages that aren't installed, or are explicitly installed.
+    fn local_requirement(&self, local: Option<(PkgIdx, &Pkg)>) -> Option<Requirement> {
+        let (idx, pkg) = local?;
+        if is_explicit(&pkg.desc) {
+            return None;
+        }
+        self.requirements().get(idx.to_usize()).copied()
+    }
+    fn sync_by_name(&self) -> &HashMap<&'db str, &'db Pkg> {
+        self.sync_by_name.get_or_init(|| {
+            let mut map = HashMap::new();
+            for (_, db) in self.dbs.remotes() {
+                for pkg in &db.pkgs {
+                    map.entry(pkg.desc.name.as_str()).or_insert(pkg);
+                }
+            }
+            map
+        })
+    }
+    fn requirements(&self) -> &[Requirement] {
+        self.requirements
+            .get_or_init(|| local_requirements(self.dbs.local_pkgs()))
+    }
+}
+
+/// How much each local package is needed by the others
+pub fn local_requirements(local_pkgs: &[Pkg]) -> Vec<Requirement> {
+    let mut providers: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, pkg) in local_pkgs.iter().enumerate() {
+        providers.entry(pkg.desc.name.as_str()).or_default().push(i);
+        for provided in &pkg.desc.provides {
+            providers.entry(provided.name.as_str()).or_default().push(i);
+        }
+    }
+    let mut reqs = vec![Requirement::None; local_pkgs.len()];
+    let mut mark = |name: &str, req: Requirement| {
+        for &i in providers.get(name).into_iter().flatten() {
+            if let Some(r) = reqs.get_mut(i) {
+                *r = (*r).max(req);
+            }
+        }
+    };
+    for pkg in local_pkgs {
+        for dep in &pkg.desc.depends {
+            mark(&dep.name, Requirement::Required);
+        }
+        for opt_dep in &pkg.desc.opt_depends {
+            mark(&opt_dep.dep.name, Requirement::Optional);
+        }
+    }
+    reqs
+}
+
+fn is_explicit(desc: &PkgDesc) -> bool {
+    matches!(desc.install_reason, InstallReason::Explicit)
+}
+
+struct PkgSubject<'a, 'db> {
+    filter: &'a PkgFilter<'db>,
+    pkg_ref: PkgRef,
+    pkg: &'db Pkg,
+    db_name: &'db str,
+}
+
+impl Subject for PkgSubject<'_, '_> {
+    fn desc(&self) -> &PkgDesc {
+        &self.pkg.desc
+    }
+    fn repo(&self) -> &str {
+        self.db_name
+    }
+    fn has_flag(&self, flag: Flag) -> bool {
+        self.filter.has_flag(self.pkg_ref, self.pkg, flag)
+    }
+}
+
+#[cfg(test)]
+fn filter_dbs() -> Dbs {
+    use crate::packages::fixtures;
+    let mut app = fixtures::pkg("app", "1.0-1", &["lib"]);
+    app.desc.opt_depends.push(alpacka::OptDepend {
+        dep: fixtures::dep("optdep"),
+        reason: None,
+    });
+    fixtures::dbs(vec![
+        (
+            "local",
+            vec![
+                app,
+                fixtures::as_dep(fixtures::pkg("lib", "1.0-1", &[])),
+                fixtures::as_dep(fixtures::pkg("optdep", "1.0-1", &[])),
+                fixtures::as_dep(fixtures::pkg("orphan", "1.0-1", &[])),
+                fixtures::pkg("aur", "1.0-1", &[]),
+            ],
+        ),
+        (
+            "core",
+            vec![
+                fixtures::pkg("app", "1.0-1", &["lib"]),
+                fixtures::pkg("lib", "1.1-1", &[]),
+                fixtures::pkg("optdep", "1.0-1", &[]),
+                fixtures::pkg("orphan", "1.0-1", &[]),
+                fixtures::pkg("new", "1.0-1", &[]),
+            ],
+        ),
+    ])
+}
+
+/// The flags of the local package `name`, and of its sync counterpart
+#[cfg(test)]
+fn flags_of(dbs: &Dbs, name: &str) -> (Vec<Flag>, Vec<Flag>) {
+    const ALL: [Flag; 8] = [
+        Flag::Installed,
+        Flag::Newer,
+        Flag::Older,
+        Flag::Explicit,
+        Flag::Foreign,
+        Flag::Orphan,
+        Flag::Upgradable,
+        Flag::OptionalOnly,
+    ];
+    let filter = PkgFilter::new(dbs);
+    let flags = |pkg_ref: Option<PkgRef>| {
+        let Some((pkg_ref, Some(pkg))) = pkg_ref.map(|r| (r, dbs.resolve(r).1)) else {
+            return Vec::new();
+        };
+        ALL.into_iter()
+            .filter(|&flag| filter.has_flag(pkg_ref, pkg, flag))
+            .collect()
+    };
+    let local = dbs
+        .local_pkg_by_name(name)
+        .map(|(idx, _)| PkgRef::local(idx));
+    (flags(local), flags(dbs.remote_pkgs_for_name(name).next()))
+}
+
+#[test]
+fn test_flags_explicit_and_up_to_date() {
+    let dbs = filter_dbs();
+    let (local, remote) = flags_of(&dbs, "app");
+    assert_eq!(local, [Flag::Installed, Flag::Explicit]);
+    assert_eq!(remote, [Flag::Installed, Flag::Explicit]);
+}
+
+#[test]
+fn test_flags_upgradable() {
+    let dbs = filter_dbs();
+    let (local, remote) = flags_of(&dbs, "lib");
+    assert_eq!(local, [Flag::Installed, Flag::Older, Flag::Upgradable]);
+    assert_eq!(remote, [Flag::Installed, Flag::Newer, Flag::Upgradable]);
+}
+
+#[test]
+fn test_flags_orphan_and_optional_only() {
+    let dbs = filter_dbs();
+    let (local, remote) = flags_of(&dbs, "orphan");
+    assert_eq!(local, [Flag::Installed, Flag::Orphan]);
+    assert_eq!(remote, [Flag::Installed, Flag::Orphan]);
+    let (local, remote) = flags_of(&dbs, "optdep");
+    assert_eq!(local, [Flag::Installed, Flag::OptionalOnly]);
+    assert_eq!(remote, [Flag::Installed, Flag::OptionalOnly]);
+}
+
+#[test]
+fn test_flags_foreign_and_not_installed() {
+    let dbs = filter_dbs();
+    let (local, remote) = flags_of(&dbs, "aur");
+    assert_eq!(local, [Flag::Installed, Flag::Explicit, Flag::Foreign]);
+    assert_eq!(remote, []);
+    let (local, remote) = flags_of(&dbs, "new");
+    assert_eq!(local, []);
+    assert_eq!(remote, []);
+}
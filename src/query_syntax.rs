@@ -1,91 +1,796 @@
+//! The package list query language
+//!
+//! A query is a boolean expression of terms:
+//!
+//! - `foo`, `"foo bar"`: name, description or provides contains the string
+//! - `@installed`, `@explicit`, ...: package has the flag
+//! - `name:foo`, `name=foo`: field contains/equals the string.
+//!   Fields: `name`, `desc`, `provides`, `depends`, `repo`, `arch`
+//! - `size>50M`, `version<2.0`: comparison with `=`, `<`, `<=`, `>`, `>=`
+//!
+//! Terms can be combined with `AND` (or `&`, or just juxtaposition), `OR` (or `|`),
+//! `NOT` (or `!`), and grouped with parentheses.
+//!
+//! How strings are matched is determined by the [`MatchMode`] the query is compiled with.
+
+use {
+    crate::{
+        text_match::{MatchMode, Matcher},
+        vercmp::{AbCmp, vercmp},
+    },
+    alpacka::PkgDesc,
+    std::{cmp::Ordering, ops::Range},
+};
+
 #[derive(Default, PartialEq, Debug)]
 pub struct PkgListQuery {
-    /// Filter out packages that don't satisfy these properties
-    pub flags: QueryFlags,
-    /// If non-empty, filter out packages that don't contain this string
-    pub string: String,
+    /// If `None`, the query matches everything
+    pub expr: Option<Expr>,
 }
 
 impl PkgListQuery {
-    pub fn compile(src: &str) -> Self {
-        let mut head = src;
-        let mut flags = QueryFlags::default();
-        while let Some(at) = head.find('@') {
-            let end = head.find(' ').unwrap_or(head.len());
-            let Some(token) = head.get(at..end) else {
-                break;
-            };
-            match token {
-                "@installed" => flags.installed = true,
-                "@older" => flags.older = true,
-                "@newer" => flags.newer = true,
-                "@asexplicit" | "@explicit" => flags.explicitly_installed = true,
-                _ => break,
+    pub fn compile(src: &str, mode: MatchMode) -> Result<Self, ParseError> {
+        let tokens = tokenize(src)?;
+        if tokens.is_empty() {
+            return Ok(Self::default());
+        }
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            src_len: src.len(),
+            mode,
+        };
+        let expr = parser.parse_or()?;
+        if let Some(tok) = parser.tokens.get(parser.pos) {
+            return Err(ParseError::new(tok.span.clone(), "Unexpected token"));
+        }
+        Ok(Self { expr: Some(expr) })
+    }
+    /// Returns `None` if the subject doesn't match, otherwise its score (see [`Matcher::score`])
+    pub fn score(&self, subject: &dyn Subject) -> Option<u32> {
+        match &self.expr {
+            Some(expr) => expr.score(subject),
+            None => Some(0),
+        }
+    }
+    /// Byte ranges of `text` (the value of `field`) that the query matches, for highlighting
+    pub fn highlights(&self, field: StrField, text: &str) -> Vec<Range<usize>> {
+        let mut out = Vec::new();
+        if let Some(expr) = &self.expr {
+            expr.collect_highlights(field, text, &mut out);
+        }
+        out
+    }
+}
+
+/// A package that's being matched against a query
+pub trait Subject {
+    fn desc(&self) -> &PkgDesc;
+    /// Name of the database the package is in
+    fn repo(&self) -> &str;
+    fn has_flag(&self, flag: Flag) -> bool;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+    Not(Box<Self>),
+    Flag(Flag),
+    /// Name, description or provides matches
+    Text(Matcher),
+    /// String field matches
+    Str {
+        field: StrField,
+        matcher: Matcher,
+    },
+    /// Installed size compared to this amount of bytes
+    Size(Cmp, i64),
+    Version(Cmp, String),
+}
+
+impl Expr {
+    pub fn score(&self, subject: &dyn Subject) -> Option<u32> {
+        let desc = subject.desc();
+        match self {
+            Self::And(lhs, rhs) => Some(lhs.score(subject)? + rhs.score(subject)?),
+            Self::Or(lhs, rhs) => lhs.score(subject).max(rhs.score(subject)),
+            Self::Not(expr) => expr.score(subject).is_none().then_some(0),
+            Self::Flag(flag) => subject.has_flag(*flag).then_some(0),
+            Self::Text(matcher) => {
+                // Name matches are the most relevant
+                let name = matcher.score(&desc.name).map(|score| score * 2);
+                let rest = best_score(
+                    matcher,
+                    desc.desc
+                        .as_deref()
+                        .into_iter()
+                        .chain(desc.provides.iter().map(|dep| dep.name.as_str())),
+                );
+                name.max(rest)
+            }
+            Self::Str { field, matcher } => match field {
+                StrField::Name => matcher.score(&desc.name),
+                StrField::Desc => desc.desc.as_deref().and_then(|d| matcher.score(d)),
+                StrField::Provides => {
+                    best_score(matcher, desc.provides.iter().map(|dep| dep.name.as_str()))
+                }
+                StrField::Depends => {
+                    best_score(matcher, desc.depends.iter().map(|dep| dep.name.as_str()))
+                }
+                StrField::Repo => matcher.score(subject.repo()),
+                StrField::Arch => matcher.score(&desc.arch.to_string()),
+            },
+            Self::Size(cmp, bytes) => cmp.test(desc.size.cmp(bytes)).then_some(0),
+            Self::Version(cmp, ver) => cmp
+                .test(match vercmp(&desc.version, ver) {
+                    AbCmp::ANewer => Ordering::Greater,
+                    AbCmp::Same => Ordering::Equal,
+                    AbCmp::BNewer => Ordering::Less,
+                })
+                .then_some(0),
+        }
+    }
+    fn collect_highlights(&self, field: StrField, text: &str, out: &mut Vec<Range<usize>>) {
+        match self {
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) => {
+                lhs.collect_highlights(field, text, out);
+                rhs.collect_highlights(field, text, out);
+            }
+            Self::Text(matcher) => {
+                if matches!(field, StrField::Name | StrField::Desc | StrField::Provides) {
+                    out.extend(matcher.ranges(text));
+                }
             }
-            let next = std::cmp::min(end + 1, head.len());
-            match head.get(next..) {
-                Some(next) => head = next,
-                None => break,
+            Self::Str {
+                field: expr_field,
+                matcher,
+            } => {
+                if *expr_field == field {
+                    out.extend(matcher.ranges(text));
+                }
             }
+            // Negated terms don't match anything that's shown
+            Self::Not(_) | Self::Flag(_) | Self::Size(..) | Self::Version(..) => {}
+        }
+    }
+}
+
+/// The best score of any of the haystacks
+fn best_score<'a>(matcher: &Matcher, haystacks: impl Iterator<Item = &'a str>) -> Option<u32> {
+    haystacks.filter_map(|h| matcher.score(h)).max()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Flag {
+    Installed,
+    Newer,
+    Older,
+    Explicit,
+    /// Installed, but not in any sync db
+    Foreign,
+    /// Installed as a dependency, but nothing requires it
+    Orphan,
+    Upgradable,
+    /// Installed as a dependency, but only optionally required
+    OptionalOnly,
+}
+
+impl Flag {
+    pub const ALL: [Self; 8] = [
+        Self::Installed,
+        Self::Newer,
+        Self::Older,
+        Self::Explicit,
+        Self::Foreign,
+        Self::Orphan,
+        Self::Upgradable,
+        Self::OptionalOnly,
+    ];
+    /// Name as written in queries, without the `@`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Installed => "installed",
+            Self::Newer => "newer",
+            Self::Older => "older",
+            Self::Explicit => "explicit",
+            Self::Foreign => "foreign",
+            Self::Orphan => "orphan",
+            Self::Upgradable => "upgradable",
+            Self::OptionalOnly => "optional-only",
         }
+    }
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Installed => "Package is installed",
+            Self::Newer => "Version is newer than its local/remote counterpart",
+            Self::Older => "Version is older than its local/remote counterpart",
+            Self::Explicit => "Installed explicitly",
+            Self::Foreign => "Installed, but not in any sync db",
+            Self::Orphan => "Installed as a dependency, but nothing requires it",
+            Self::Upgradable => "An upgrade is available",
+            Self::OptionalOnly => "Installed as a dependency, but only optionally required",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        // `asexplicit` mirrors pacman's `--asexplicit`
+        if name == "asexplicit" {
+            return Some(Self::Explicit);
+        }
+        Self::ALL.into_iter().find(|flag| flag.name() == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StrField {
+    Name,
+    Desc,
+    Provides,
+    Depends,
+    Repo,
+    Arch,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    /// Whether `ordering` (of the package's value relative to the query's) satisfies this
+    const fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    /// Byte range of the offending part of the source
+    pub span: Range<usize>,
+    pub msg: String,
+}
+
+impl ParseError {
+    fn new(span: Range<usize>, msg: impl Into<String>) -> Self {
         Self {
-            flags,
-            string: head.to_owned(),
+            span,
+            msg: msg.into(),
         }
     }
 }
 
-#[expect(clippy::struct_excessive_bools)]
-#[derive(Default, Debug, PartialEq, Clone, Copy)]
-pub struct QueryFlags {
-    pub installed: bool,
-    pub newer: bool,
-    pub older: bool,
-    pub explicitly_installed: bool,
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at {})", self.msg, self.span.start)
+    }
 }
 
-impl QueryFlags {
-    pub const fn any(self) -> bool {
-        self.installed || self.newer || self.older
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte range in the source
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TokenKind {
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    /// `@name`
+    Flag(String),
+    /// `field<op>value`
+    Field {
+        field: String,
+        op: FieldOp,
+        value: String,
+    },
+    /// Bare word or quoted string
+    Text(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FieldOp {
+    /// `:`
+    Contains,
+    Cmp(Cmp),
+}
+
+const FIELDS: [&str; 8] = [
+    "name", "desc", "provides", "depends", "repo", "arch", "size", "version",
+];
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let single = match c {
+            '(' => Some(TokenKind::LParen),
+            ')' => Some(TokenKind::RParen),
+            '!' => Some(TokenKind::Not),
+            '&' => Some(TokenKind::And),
+            '|' => Some(TokenKind::Or),
+            _ => None,
+        };
+        if let Some(kind) = single {
+            chars.next();
+            tokens.push(Token {
+                kind,
+                span: start..start + 1,
+            });
+            continue;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '"' {
+            let (text, end) = lex_quoted(src, start)?;
+            tokens.push(Token {
+                kind: TokenKind::Text(text),
+                span: start..end,
+            });
+            while chars.peek().is_some_and(|&(i, _)| i < end) {
+                chars.next();
+            }
+            continue;
+        }
+        let word_end = src
+            .get(start..)
+            .and_then(|rest| rest.find(|c: char| c.is_whitespace() || "()\"".contains(c)))
+            .map_or(src.len(), |off| start + off);
+        let word = src.get(start..word_end).unwrap_or_default();
+        let mut end = word_end;
+        let kind = match word {
+            "AND" => TokenKind::And,
+            "OR" => TokenKind::Or,
+            "NOT" => TokenKind::Not,
+            _ => {
+                if let Some(flag) = word.strip_prefix('@') {
+                    TokenKind::Flag(flag.to_owned())
+                } else if let Some((field, op, op_len)) = split_field(word) {
+                    let value_start = start + field.len() + op_len;
+                    let value = if src.get(value_start..).is_some_and(|s| s.starts_with('"')) {
+                        let (text, quote_end) = lex_quoted(src, value_start)?;
+                        end = quote_end;
+                        text
+                    } else {
+                        src.get(value_start..word_end)
+                            .unwrap_or_default()
+                            .to_owned()
+                    };
+                    TokenKind::Field {
+                        field: field.to_owned(),
+                        op,
+                        value,
+                    }
+                } else {
+                    TokenKind::Text(word.to_owned())
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: start..end,
+        });
+        while chars.peek().is_some_and(|&(i, _)| i < end) {
+            chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+/// What's being typed at the cursor, for autocompletion
+#[derive(Debug, PartialEq)]
+pub struct CompletionSite<'src> {
+    pub kind: CompletionKind<'src>,
+    /// Already typed part of the completed item
+    pub partial: &'src str,
+    /// Byte range of the source that a completion replaces
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompletionKind<'src> {
+    /// `@flag`. The replaced span includes the `@`.
+    Flag,
+    /// Value of `field:` or `field=`
+    FieldValue(&'src str),
+}
+
+/// Find what can be completed at the byte position `cursor` of `src`
+pub fn completion_site(src: &str, cursor: usize) -> Option<CompletionSite<'_>> {
+    let is_sep = |c: char| c.is_whitespace() || "()!&|".contains(c);
+    let before = src.get(..cursor)?;
+    let start = before.rfind(is_sep).map_or(0, |i| i + 1);
+    let end = src
+        .get(cursor..)?
+        .find(is_sep)
+        .map_or(src.len(), |off| cursor + off);
+    let word = src.get(start..end)?;
+    if let Some(partial) = word.strip_prefix('@') {
+        return Some(CompletionSite {
+            kind: CompletionKind::Flag,
+            partial,
+            span: start..end,
+        });
+    }
+    let (field, _, op_len) = split_field(word)?;
+    let value_start = start + field.len() + op_len;
+    if cursor < value_start {
+        return None;
+    }
+    let partial = src.get(value_start..end)?;
+    // Quoted values can contain separators, so we can't tell where they end
+    if partial.starts_with('"') {
+        return None;
     }
+    Some(CompletionSite {
+        kind: CompletionKind::FieldValue(field),
+        partial,
+        span: value_start..end,
+    })
+}
+
+/// If `word` starts with a known field followed by an operator, return the field,
+/// the operator, and the length of the operator
+fn split_field(word: &str) -> Option<(&str, FieldOp, usize)> {
+    let field_end = word.find(|c: char| !c.is_ascii_alphabetic())?;
+    let (field, rest) = word.split_at_checked(field_end)?;
+    if !FIELDS.contains(&field) {
+        return None;
+    }
+    let (op, len) = if rest.starts_with("<=") {
+        (FieldOp::Cmp(Cmp::Le), 2)
+    } else if rest.starts_with(">=") {
+        (FieldOp::Cmp(Cmp::Ge), 2)
+    } else if rest.starts_with('<') {
+        (FieldOp::Cmp(Cmp::Lt), 1)
+    } else if rest.starts_with('>') {
+        (FieldOp::Cmp(Cmp::Gt), 1)
+    } else if rest.starts_with('=') {
+        (FieldOp::Cmp(Cmp::Eq), 1)
+    } else if rest.starts_with(':') {
+        (FieldOp::Contains, 1)
+    } else {
+        return None;
+    };
+    Some((field, op, len))
+}
+
+/// Lex a quoted string starting at `start`. Returns the unescaped string, and the end position.
+fn lex_quoted(src: &str, start: usize) -> Result<(String, usize), ParseError> {
+    let mut out = String::new();
+    let mut escaped = false;
+    for (i, c) in src.get(start + 1..).unwrap_or_default().char_indices() {
+        match c {
+            _ if escaped => {
+                out.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            '"' => return Ok((out, start + 1 + i + 1)),
+            _ => out.push(c),
+        }
+    }
+    Err(ParseError::new(start..src.len(), "Unterminated string"))
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    src_len: usize,
+    mode: MatchMode,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|tok| &tok.kind)
+    }
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_and()?;
+        while self.peek() == Some(&TokenKind::Or) {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                None | Some(TokenKind::Or | TokenKind::RParen) => break,
+                Some(TokenKind::And) => self.pos += 1,
+                // Juxtaposition is an implicit AND
+                Some(_) => {}
+            }
+            let rhs = self.parse_unary()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        if self.peek() == Some(&TokenKind::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let Some(tok) = self.tokens.get(self.pos) else {
+            return Err(ParseError::new(
+                self.src_len..self.src_len,
+                "Unexpected end of query",
+            ));
+        };
+        self.pos += 1;
+        let span = tok.span.clone();
+        match &tok.kind {
+            TokenKind::LParen => {
+                let expr = self.parse_or()?;
+                match self.tokens.get(self.pos) {
+                    Some(Token {
+                        kind: TokenKind::RParen,
+                        ..
+                    }) => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err(ParseError::new(span, "Unclosed `(`")),
+                }
+            }
+            TokenKind::RParen => Err(ParseError::new(span, "Unexpected `)`")),
+            TokenKind::Not | TokenKind::And | TokenKind::Or => {
+                Err(ParseError::new(span, "Expected a term"))
+            }
+            TokenKind::Flag(name) => match Flag::from_name(name) {
+                Some(flag) => Ok(Expr::Flag(flag)),
+                None => Err(ParseError::new(span, format!("Unknown flag `@{name}`"))),
+            },
+            TokenKind::Field { field, op, value } => field_expr(field, *op, value, span, self.mode),
+            TokenKind::Text(text) => Ok(Expr::Text(matcher(self.mode, text, false, span)?)),
+        }
+    }
+}
+
+fn matcher(
+    mode: MatchMode,
+    pattern: &str,
+    exact: bool,
+    span: Range<usize>,
+) -> Result<Matcher, ParseError> {
+    Matcher::new(mode, pattern, exact).map_err(|e| ParseError::new(span, e.to_string()))
+}
+
+fn field_expr(
+    field: &str,
+    op: FieldOp,
+    value: &str,
+    span: Range<usize>,
+    mode: MatchMode,
+) -> Result<Expr, ParseError> {
+    if value.is_empty() {
+        return Err(ParseError::new(
+            span,
+            format!("Missing value for `{field}`"),
+        ));
+    }
+    let str_field = match field {
+        "name" => StrField::Name,
+        "desc" => StrField::Desc,
+        "provides" => StrField::Provides,
+        "depends" => StrField::Depends,
+        "repo" => StrField::Repo,
+        "arch" => StrField::Arch,
+        "size" | "version" => {
+            let FieldOp::Cmp(cmp) = op else {
+                return Err(ParseError::new(
+                    span,
+                    format!("`{field}` needs one of `=`, `<`, `<=`, `>`, `>=`"),
+                ));
+            };
+            if field == "size" {
+                return match parse_size(value) {
+                    Some(bytes) => Ok(Expr::Size(cmp, bytes)),
+                    None => Err(ParseError::new(span, format!("Invalid size `{value}`"))),
+                };
+            }
+            return Ok(Expr::Version(cmp, value.to_owned()));
+        }
+        _ => return Err(ParseError::new(span, format!("Unknown field `{field}`"))),
+    };
+    let exact = match op {
+        FieldOp::Contains => false,
+        FieldOp::Cmp(Cmp::Eq) => true,
+        FieldOp::Cmp(_) => {
+            return Err(ParseError::new(span, format!("`{field}` needs `:` or `=`")));
+        }
+    };
+    Ok(Expr::Str {
+        field: str_field,
+        matcher: matcher(mode, value, exact, span)?,
+    })
+}
+
+/// Parse a size like `512`, `50M`, `1.5GiB` into bytes. Units are binary.
+fn parse_size(src: &str) -> Option<i64> {
+    let num_end = src
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(src.len());
+    let (num, unit) = src.split_at_checked(num_end)?;
+    let num: f64 = num.parse().ok()?;
+    let unit = unit.to_ascii_uppercase();
+    let shift = match unit.as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return None,
+    };
+    #[expect(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    Some((num * (1_i64 << shift) as f64) as i64)
+}
+
+#[cfg(test)]
+fn text(s: &str) -> Expr {
+    Expr::Text(Matcher::new(MatchMode::Substring, s, false).expect("Invalid pattern"))
 }
 
 #[test]
 fn test_compile() {
+    let compile = |src| PkgListQuery::compile(src, MatchMode::Substring).map(|q| q.expr);
+    assert_eq!(compile(""), Ok(None));
+    assert_eq!(compile("  "), Ok(None));
     assert_eq!(
-        PkgListQuery::compile("@installed cool"),
-        PkgListQuery {
-            flags: QueryFlags {
-                installed: true,
-                newer: false,
-                older: false,
-                explicitly_installed: false,
-            },
-            string: "cool".into(),
-        }
+        compile("@installed cool"),
+        Ok(Some(Expr::And(
+            Box::new(Expr::Flag(Flag::Installed)),
+            Box::new(text("cool"))
+        )))
     );
     assert_eq!(
-        PkgListQuery::compile("hello world"),
-        PkgListQuery {
-            flags: QueryFlags {
-                installed: false,
-                newer: false,
-                older: false,
-                explicitly_installed: false,
-            },
-            string: "hello world".into(),
-        }
+        compile("Hello \"big world\""),
+        Ok(Some(Expr::And(
+            Box::new(text("hello")),
+            Box::new(text("big world"))
+        )))
+    );
+    assert_eq!(compile(r#""say \"hi\"""#), Ok(Some(text("say \"hi\""))));
+}
+
+#[test]
+fn test_precedence() {
+    let compile = |src| PkgListQuery::compile(src, MatchMode::Substring).map(|q| q.expr);
+    // AND binds tighter than OR
+    assert_eq!(
+        compile("a b OR c"),
+        Ok(Some(Expr::Or(
+            Box::new(Expr::And(Box::new(text("a")), Box::new(text("b")))),
+            Box::new(text("c"))
+        )))
     );
     assert_eq!(
-        PkgListQuery::compile("@"),
-        PkgListQuery {
-            flags: QueryFlags {
-                installed: false,
-                newer: false,
-                older: false,
-                explicitly_installed: false,
-            },
-            string: "@".into(),
-        }
+        compile("a & (b | c)"),
+        Ok(Some(Expr::And(
+            Box::new(text("a")),
+            Box::new(Expr::Or(Box::new(text("b")), Box::new(text("c"))))
+        )))
+    );
+    assert_eq!(
+        compile("NOT a !b"),
+        Ok(Some(Expr::And(
+            Box::new(Expr::Not(Box::new(text("a")))),
+            Box::new(Expr::Not(Box::new(text("b"))))
+        )))
+    );
+    // Lowercase keywords are just text
+    assert_eq!(
+        compile("a or b"),
+        Ok(Some(Expr::And(
+            Box::new(Expr::And(Box::new(text("a")), Box::new(text("or")))),
+            Box::new(text("b"))
+        )))
+    );
+}
+
+#[test]
+fn test_fields() {
+    let compile = |src| PkgListQuery::compile(src, MatchMode::Substring).map(|q| q.expr);
+    assert_eq!(
+        compile("name:Foo"),
+        Ok(Some(Expr::Str {
+            field: StrField::Name,
+            matcher: Matcher::Substring("foo".into())
+        }))
+    );
+    assert_eq!(
+        compile("desc=\"a b\""),
+        Ok(Some(Expr::Str {
+            field: StrField::Desc,
+            matcher: Matcher::Exact {
+                needle: "a b".into(),
+                case_sensitive: false
+            }
+        }))
+    );
+    assert_eq!(
+        compile("size>50M"),
+        Ok(Some(Expr::Size(Cmp::Gt, 50 * 1024 * 1024)))
+    );
+    assert_eq!(
+        compile("version<=2.0"),
+        Ok(Some(Expr::Version(Cmp::Le, "2.0".into())))
+    );
+    // Unknown fields are plain text
+    assert_eq!(compile("http://foo"), Ok(Some(text("http://foo"))));
+}
+
+#[test]
+fn test_errors() {
+    let err_span = |src| PkgListQuery::compile(src, MatchMode::Substring).map_err(|e| e.span);
+    assert_eq!(err_span("@"), Err(0..1));
+    assert_eq!(err_span("a @bogus"), Err(2..8));
+    assert_eq!(err_span("(a b"), Err(0..1));
+    assert_eq!(err_span("a)"), Err(1..2));
+    assert_eq!(err_span("a OR"), Err(4..4));
+    assert_eq!(err_span("a \"b"), Err(2..4));
+    assert_eq!(err_span("size:5"), Err(0..6));
+    assert_eq!(err_span("name<a"), Err(0..6));
+    assert_eq!(err_span("size>5X"), Err(0..7));
+    assert_eq!(err_span("repo:"), Err(0..5));
+    let regex_err_span = |src| PkgListQuery::compile(src, MatchMode::Regex).map_err(|e| e.span);
+    assert_eq!(regex_err_span("ok name:a["), Err(3..10));
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("512"), Some(512));
+    assert_eq!(parse_size("2k"), Some(2048));
+    assert_eq!(parse_size("1.5GiB"), Some(3 * 512 * 1024 * 1024));
+    assert_eq!(parse_size("10MB"), Some(10 * 1024 * 1024));
+    assert_eq!(parse_size("M"), None);
+    assert_eq!(parse_size("5X"), None);
+    assert_eq!(parse_size("5B"), Some(5));
+    assert_eq!(parse_size("5ib"), None);
+    assert_eq!(parse_size("5IBB"), None);
+    assert_eq!(parse_size("5kbib"), None);
+}
+
+#[test]
+fn test_completion_site() {
+    let site = |src: &'static str, cursor| {
+        completion_site(src, cursor).map(|s| (s.kind, s.partial, s.span))
+    };
+    assert_eq!(site("a @ins", 6), Some((CompletionKind::Flag, "ins", 2..6)));
+    assert_eq!(site("!@", 2), Some((CompletionKind::Flag, "", 1..2)));
+    // The whole word is replaced, even past the cursor
+    assert_eq!(
+        site("@instx b", 3),
+        Some((CompletionKind::Flag, "instx", 0..6))
+    );
+    assert_eq!(
+        site("(repo:ex)", 8),
+        Some((CompletionKind::FieldValue("repo"), "ex", 6..8))
+    );
+    assert_eq!(
+        site("name=", 5),
+        Some((CompletionKind::FieldValue("name"), "", 5..5))
     );
+    assert_eq!(site("name:foo", 2), None);
+    assert_eq!(site("foo bar", 2), None);
+    assert_eq!(site("name:\"a b", 9), None);
 }
@@ -0,0 +1,238 @@
+//! Finding the packages that satisfy a dependency, taking version constraints into account
+
+use {
+    crate::{
+        packages::{Db, DbIdx, Dbs, PkgIdx, PkgRef},
+        vercmp::{AbCmp, vercmp},
+    },
+    alpacka::{Depend, Pkg, VerCmp},
+};
+
+/// A package that satisfies a dependency
+pub struct Candidate {
+    pub pkg_ref: PkgRef,
+    /// Satisfies it through `provides`, rather than by name
+    pub via_provides: bool,
+    /// The one pacman would pick
+    pub chosen: bool,
+}
+
+/// The package that satisfies `dep`.
+///
+/// Installed packages are preferred, then packages named like the dependency in repo
+/// order, then providers in repo order.
+pub fn resolve<'db>(dep: &Depend, dbs: &'db Dbs) -> Option<(PkgRef, &'db Pkg)> {
+    let candidate = candidates(dep, dbs).into_iter().find(|c| c.chosen)?;
+    let pkg = dbs.resolve(candidate.pkg_ref).1?;
+    Some((candidate.pkg_ref, pkg))
+}
+
+/// Every package that satisfies `dep`, installed ones first, then in repo order.
+///
+/// At most one of them is flagged as chosen, see [`resolve`].
+pub fn candidates(dep: &Depend, dbs: &Dbs) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for (db_idx, db) in dbs.all() {
+        db_candidates(dep, db_idx, db, &mut candidates);
+    }
+    let chosen = candidates
+        .iter()
+        .position(|c| c.pkg_ref.is_local())
+        .or_else(|| candidates.iter().position(|c| !c.via_provides))
+        .or_else(|| (!candidates.is_empty()).then_some(0));
+    if let Some(c) = chosen.and_then(|i| candidates.get_mut(i)) {
+        c.chosen = true;
+    }
+    candidates
+}
+
+fn db_candidates(dep: &Depend, db_idx: DbIdx, db: &Db, out: &mut Vec<Candidate>) {
+    // Dbs are sorted by name on load
+    let by_name = db
+        .pkgs
+        .binary_search_by(|pkg| pkg.desc.name.as_str().cmp(&dep.name))
+        .ok();
+    let mut push = |i: usize, via_provides| {
+        out.push(Candidate {
+            pkg_ref: PkgRef::from_components(db_idx, PkgIdx::from_usize(i)),
+            via_provides,
+            chosen: false,
+        });
+    };
+    if let Some(i) = by_name
+        && db
+            .pkgs
+            .get(i)
+            .is_some_and(|pkg| satisfies_by_name(pkg, dep))
+    {
+        push(i, false);
+    }
+    for (i, pkg) in db.pkgs.iter().enumerate() {
+        if Some(i) != by_name && satisfies_by_provides(pkg, dep) {
+            push(i, true);
+        }
+    }
+}
+
+/// Whether `pkg` satisfies `dep`, by name or through `provides`
+pub fn pkg_satisfies(pkg: &Pkg, dep: &Depend) -> bool {
+    satisfies_by_name(pkg, dep) || satisfies_by_provides(pkg, dep)
+}
+
+fn satisfies_by_name(pkg: &Pkg, dep: &Depend) -> bool {
+    pkg.desc.name == dep.name
+        && dep
+            .ver
+            .as_ref()
+            .is_none_or(|ver| satisfies(&pkg.desc.version, ver.cmp, &ver.ver))
+}
+
+fn satisfies_by_provides(pkg: &Pkg, dep: &Depend) -> bool {
+    pkg.desc.provides.iter().any(|provided| {
+        provided.name == dep.name
+            && match (&dep.ver, &provided.ver) {
+                (None, _) => true,
+                (Some(ver), Some(provided)) => satisfies(&provided.ver, ver.cmp, &ver.ver),
+                // An unversioned provision can't satisfy a versioned dependency
+                (Some(_), None) => false,
+            }
+    })
+}
+
+/// The dependency as pacman writes it, e.g. `foo>=1.2`
+pub fn dep_string(dep: &Depend) -> String {
+    match &dep.ver {
+        None => dep.name.to_string(),
+        Some(ver) => {
+            let cmp = match ver.cmp {
+                VerCmp::Eq => "=",
+                VerCmp::Lt => "<",
+                VerCmp::Le => "<=",
+                VerCmp::Gt => ">",
+                VerCmp::Ge => ">=",
+            };
+            format!("{}{cmp}{}", dep.name, ver.ver)
+        }
+    }
+}
+
+/// Whether `version` satisfies `cmp wanted`, e.g. `>= 1.2`.
+///
+/// Like pacman, the pkgrel is only compared if `wanted` has one.
+fn satisfies(version: &str, cmp: VerCmp, wanted: &str) -> bool {
+    let version = if wanted.contains('-') {
+        version
+    } else {
+        version.rsplit_once('-').map_or(version, |(ver, _)| ver)
+    };
+    let ord = vercmp(version, wanted);
+    match cmp {
+        VerCmp::Eq => ord == AbCmp::Same,
+        VerCmp::Lt => ord == AbCmp::BNewer,
+        VerCmp::Le => ord != AbCmp::ANewer,
+        VerCmp::Gt => ord == AbCmp::ANewer,
+        VerCmp::Ge => ord != AbCmp::BNewer,
+    }
+}
+
+#[test]
+fn test_satisfies() {
+    assert!(satisfies("1.2-3", VerCmp::Eq, "1.2"));
+    assert!(!satisfies("1.2-3", VerCmp::Eq, "1.2-1"));
+    assert!(satisfies("1.10-1", VerCmp::Gt, "1.9"));
+    assert!(satisfies("1.10-1", VerCmp::Ge, "1.10"));
+    assert!(!satisfies("1.10-1", VerCmp::Lt, "1.10"));
+    assert!(satisfies("1.9-1", VerCmp::Le, "1.10"));
+    assert!(satisfies("1:0.5-1", VerCmp::Gt, "2.0"));
+}
+
+#[test]
+fn test_pkg_satisfies() {
+    use crate::packages::fixtures;
+    let mut pkg = fixtures::pkg("foo", "1.2-1", &[]);
+    pkg.desc.provides.push(fixtures::dep("libfoo=3.0"));
+    pkg.desc.provides.push(fixtures::dep("foo-any"));
+    let satisfied = |dep| pkg_satisfies(&pkg, &fixtures::dep(dep));
+    assert!(satisfied("foo"));
+    assert!(satisfied("foo>=1.2"));
+    assert!(!satisfied("foo>1.2"));
+    assert!(satisfied("libfoo"));
+    assert!(satisfied("libfoo>=2"));
+    assert!(!satisfied("libfoo<3"));
+    assert!(satisfied("foo-any"));
+    assert!(!satisfied("foo-any>=1"));
+    assert!(!satisfied("bar"));
+}
+
+#[test]
+fn test_dep_string() {
+    use crate::packages::fixtures;
+    for src in [
+        "foo",
+        "foo>=1.2",
+        "foo<=1.2",
+        "foo<2",
+        "foo>2",
+        "foo=1:1.0-1",
+    ] {
+        assert_eq!(dep_string(&fixtures::dep(src)), src);
+    }
+}
+
+/// The candidates of `dep` as `db/name`, with `*` marking the chosen one and `?` the
+/// ones satisfying it through `provides`
+#[cfg(test)]
+fn candidate_names(dbs: &Dbs, dep: &str) -> Vec<String> {
+    candidates(&crate::packages::fixtures::dep(dep), dbs)
+        .into_iter()
+        .map(|c| {
+            let chosen = if c.chosen { "*" } else { "" };
+            let via_provides = if c.via_provides { "?" } else { "" };
+            format!("{chosen}{}{via_provides}", c.pkg_ref.display(dbs))
+        })
+        .collect()
+}
+
+#[test]
+fn test_candidates_order_and_choice() {
+    use crate::packages::fixtures;
+    let provider = |name, provides| {
+        let mut pkg = fixtures::pkg(name, "1.0-1", &[]);
+        pkg.desc.provides.push(fixtures::dep(provides));
+        pkg
+    };
+    let dbs = fixtures::dbs(vec![
+        ("local", vec![fixtures::pkg("bar", "1.0-1", &[])]),
+        (
+            "core",
+            vec![
+                provider("foo-git", "foo"),
+                fixtures::pkg("foo", "1.0-1", &[]),
+                fixtures::pkg("bar", "2.0-1", &[]),
+            ],
+        ),
+        (
+            "extra",
+            vec![
+                provider("alt", "foo=3.0"),
+                fixtures::pkg("foo", "2.0-1", &[]),
+            ],
+        ),
+    ]);
+    // By name before providers within a repo, repos in order
+    assert_eq!(
+        candidate_names(&dbs, "foo"),
+        ["*core/foo", "core/foo-git?", "extra/foo", "extra/alt?"]
+    );
+    // Unversioned provisions don't satisfy versioned dependencies
+    assert_eq!(
+        candidate_names(&dbs, "foo>=2"),
+        ["*extra/foo", "extra/alt?"]
+    );
+    // Providers are chosen when nothing has the name
+    assert_eq!(candidate_names(&dbs, "foo>2.0"), ["*extra/alt?"]);
+    // Installed packages are preferred
+    assert_eq!(candidate_names(&dbs, "bar"), ["*local/bar", "core/bar"]);
+    assert_eq!(candidate_names(&dbs, "bar>=2"), ["*core/bar"]);
+    assert!(candidate_names(&dbs, "baz").is_empty());
+}
@@ -0,0 +1,225 @@
+//! Text matching strategies used by the package list queries
+
+use {
+    regex::Regex,
+    serde::{Deserialize, Serialize},
+    std::ops::Range,
+};
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum MatchMode {
+    /// Case-insensitive substring
+    #[default]
+    Substring,
+    CaseSensitive,
+    Regex,
+    /// Pattern characters appear in order, ranked by how well they match
+    Fuzzy,
+}
+
+impl MatchMode {
+    pub const ALL: [Self; 4] = [
+        Self::Substring,
+        Self::CaseSensitive,
+        Self::Regex,
+        Self::Fuzzy,
+    ];
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Substring => "Substring",
+            Self::CaseSensitive => "Case-sensitive",
+            Self::Regex => "Regex",
+            Self::Fuzzy => "Fuzzy",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Matcher {
+    /// Lowercase needle
+    Substring(String),
+    CaseSensitive(String),
+    Exact {
+        needle: String,
+        case_sensitive: bool,
+    },
+    Regex(Regex),
+    /// Lowercase pattern
+    Fuzzy(String),
+}
+
+impl PartialEq for Matcher {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Substring(a), Self::Substring(b))
+            | (Self::CaseSensitive(a), Self::CaseSensitive(b))
+            | (Self::Fuzzy(a), Self::Fuzzy(b)) => a == b,
+            (
+                Self::Exact {
+                    needle: a,
+                    case_sensitive: a_cs,
+                },
+                Self::Exact {
+                    needle: b,
+                    case_sensitive: b_cs,
+                },
+            ) => a == b && a_cs == b_cs,
+            (Self::Regex(a), Self::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+impl Matcher {
+    /// Create a matcher for `pattern`. If `exact`, the whole text has to match.
+    pub fn new(mode: MatchMode, pattern: &str, exact: bool) -> Result<Self, regex::Error> {
+        Ok(match (mode, exact) {
+            (MatchMode::Substring, false) => Self::Substring(pattern.to_ascii_lowercase()),
+            (MatchMode::CaseSensitive, false) => Self::CaseSensitive(pattern.to_owned()),
+            (MatchMode::Fuzzy, false) => Self::Fuzzy(pattern.to_ascii_lowercase()),
+            (MatchMode::Regex, false) => Self::Regex(Regex::new(pattern)?),
+            (MatchMode::Regex, true) => Self::Regex(Regex::new(&format!("^(?:{pattern})$"))?),
+            (MatchMode::Substring | MatchMode::Fuzzy, true) => Self::Exact {
+                needle: pattern.to_owned(),
+                case_sensitive: false,
+            },
+            (MatchMode::CaseSensitive, true) => Self::Exact {
+                needle: pattern.to_owned(),
+                case_sensitive: true,
+            },
+        })
+    }
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.score(haystack).is_some()
+    }
+    /// Returns `None` if `haystack` doesn't match, otherwise how well it matches.
+    ///
+    /// Only fuzzy matching produces meaningful scores, the rest always score 0.
+    pub fn score(&self, haystack: &str) -> Option<u32> {
+        let matched = match self {
+            Self::Substring(needle) => haystack.to_ascii_lowercase().contains(needle.as_str()),
+            Self::CaseSensitive(needle) => haystack.contains(needle.as_str()),
+            Self::Exact {
+                needle,
+                case_sensitive,
+            } => {
+                if *case_sensitive {
+                    haystack == needle
+                } else {
+                    haystack.eq_ignore_ascii_case(needle)
+                }
+            }
+            Self::Regex(re) => re.is_match(haystack),
+            Self::Fuzzy(pattern) => return fuzzy_match(pattern, haystack).map(|(score, _)| score),
+        };
+        matched.then_some(0)
+    }
+    /// Byte ranges of `haystack` that match, in ascending order
+    pub fn ranges(&self, haystack: &str) -> Vec<Range<usize>> {
+        let indices_of = |haystack: &str, needle: &str| {
+            if needle.is_empty() {
+                return Vec::new();
+            }
+            haystack
+                .match_indices(needle)
+                .map(|(pos, s)| pos..pos + s.len())
+                .collect()
+        };
+        match self {
+            Self::Substring(needle) => indices_of(&haystack.to_ascii_lowercase(), needle),
+            Self::CaseSensitive(needle) => indices_of(haystack, needle),
+            Self::Exact { .. } => std::iter::once(0..haystack.len())
+                .filter(|_| self.is_match(haystack))
+                .collect(),
+            Self::Regex(re) => re
+                .find_iter(haystack)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect(),
+            Self::Fuzzy(pattern) => fuzzy_match(pattern, haystack)
+                .map(|(_, ranges)| ranges)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Match the characters of `pattern_lo` in order, case-insensitively.
+///
+/// Returns the score and the matched byte ranges.
+/// Matches at the start, at word starts and consecutive matches score higher,
+/// and so do shorter haystacks.
+fn fuzzy_match(pattern_lo: &str, haystack: &str) -> Option<(u32, Vec<Range<usize>>)> {
+    let mut pattern = pattern_lo.chars().peekable();
+    let mut score: u32 = 0;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut prev: Option<char> = None;
+    for (i, c) in haystack.char_indices() {
+        let Some(&p) = pattern.peek() else {
+            break;
+        };
+        if c.to_ascii_lowercase() == p {
+            pattern.next();
+            score += 1;
+            match prev {
+                None => score += 8,
+                Some(prev) if !prev.is_alphanumeric() => score += 4,
+                Some(_) => {}
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == i => {
+                    range.end = i + c.len_utf8();
+                    score += 5;
+                }
+                _ => ranges.push(i..i + c.len_utf8()),
+            }
+        }
+        prev = Some(c);
+    }
+    if pattern.peek().is_some() {
+        return None;
+    }
+    let len_penalty = u32::try_from(haystack.len()).unwrap_or(u32::MAX).min(15);
+    Some(((score * 16).saturating_sub(len_penalty), ranges))
+}
+
+#[test]
+fn test_fuzzy() {
+    assert_eq!(
+        fuzzy_match("abc", "xaybzc").map(|(_, r)| r),
+        Some(vec![1..2, 3..4, 5..6])
+    );
+    assert_eq!(
+        fuzzy_match("ff", "firefox").map(|(_, r)| r),
+        Some(vec![0..1, 4..5])
+    );
+    assert_eq!(
+        fuzzy_match("fox", "firefox").map(|(_, r)| r),
+        Some(vec![0..1, 5..7])
+    );
+    assert!(fuzzy_match("abc", "acb").is_none());
+    let score = |pat, hay| fuzzy_match(pat, hay).map_or(0, |(score, _)| score);
+    // Consecutive beats scattered
+    assert!(score("fire", "firefox") > score("fire", "f-i-r-e"));
+    // Word start beats mid-word
+    assert!(score("fox", "fire-fox") > score("fox", "xfoxx"));
+    // Shorter beats longer
+    assert!(score("vim", "vim") > score("vim", "vim-plugins"));
+}
+
+#[test]
+fn test_ranges() {
+    let m = |mode, pat| Matcher::new(mode, pat, false).expect("Invalid pattern");
+    assert_eq!(
+        m(MatchMode::Substring, "O").ranges("foo bOo"),
+        [1..2, 2..3, 5..6, 6..7]
+    );
+    assert_eq!(
+        m(MatchMode::CaseSensitive, "O").ranges("foo bOo O"),
+        [5..6, 8..9]
+    );
+    assert_eq!(m(MatchMode::Regex, "o+").ranges("foo bOo"), [1..3, 6..7]);
+    assert!(!m(MatchMode::CaseSensitive, "Foo").is_match("foo"));
+    let exact = Matcher::new(MatchMode::Regex, "fo|bar", true).expect("Invalid pattern");
+    assert!(exact.is_match("bar"));
+    assert!(!exact.is_match("foo"));
+}
@@ -0,0 +1,552 @@
+//! Dry-run previews of pacman transactions, computed from the loaded databases
+
+use {
+    crate::{
+        packages::{Dbs, PkgIdx, PkgRef},
+        resolver::pkg_satisfies,
+        vercmp::{AbCmp, vercmp},
+    },
+    alpacka::{InstallReason, Pkg},
+    smol_str::SmolStr,
+    std::collections::{HashMap, HashSet},
+};
+
+/// A pacman operation that changes the set of installed packages
+pub enum Op {
+    /// `-S`, or `-S --asdeps`. Targets are either `repo/pkg` or plain names.
+    Install {
+        targets: Vec<SmolStr>,
+        as_deps: bool,
+    },
+    /// `-Su`
+    SysUpgrade,
+    /// `-Rns`, or `-Rscn` if `cascade` is set
+    Remove { names: Vec<SmolStr>, cascade: bool },
+    /// `-U` on a package file of package `name`
+    InstallFile {
+        path: String,
+        name: SmolStr,
+        version: SmolStr,
+    },
+}
+
+impl Op {
+    /// Arguments to pass to pacman
+    pub fn args(&self) -> Vec<&str> {
+        let (flags, names): (&[&str], &[SmolStr]) = match self {
+            Self::Install {
+                targets,
+                as_deps: false,
+            } => (&["-S"], targets),
+            Self::Install {
+                targets,
+                as_deps: true,
+            } => (&["-S", "--asdeps"], targets),
+            Self::SysUpgrade => (&["-Su"], &[]),
+            Self::Remove {
+                names,
+                cascade: false,
+            } => (&["-Rns"], names),
+            Self::Remove {
+                names,
+                cascade: true,
+            } => (&["-Rscn"], names),
+            Self::InstallFile { path, .. } => return vec!["-U", path],
+        };
+        flags
+            .iter()
+            .copied()
+            .chain(names.iter().map(SmolStr::as_str))
+            .collect()
+    }
+    pub fn preview(&self, dbs: &Dbs) -> Preview {
+        match self {
+            Self::Install { targets, .. } => {
+                let mut plan = InstallPlan::new(dbs);
+                for target in targets {
+                    match resolve_target(dbs, target) {
+                        Some(pkg_ref) => plan.queue.push(pkg_ref),
+                        None => plan.preview.unresolved.push(target.clone()),
+                    }
+                }
+                plan.run()
+            }
+            Self::SysUpgrade => {
+                let mut plan = InstallPlan::new(dbs);
+                for local in dbs.local_pkgs() {
+                    if let Some(pkg_ref) = dbs.sync_pkg_by_name(&local.desc.name)
+                        && let (_, Some(remote)) = dbs.resolve(pkg_ref)
+                        && vercmp(&remote.desc.version, &local.desc.version) == AbCmp::ANewer
+                    {
+                        plan.queue.push(pkg_ref);
+                    }
+                }
+                plan.run()
+            }
+            Self::Remove { names, cascade } => plan_removal(dbs, names, *cascade),
+            // The dependencies of the file aren't known without reading it
+            Self::InstallFile { name, version, .. } => Preview {
+                files: vec![FileTarget {
+                    name: name.clone(),
+                    version: version.clone(),
+                    replaces: dbs.local_pkg_by_name(name).map(|(idx, _)| idx),
+                }],
+                ..Preview::default()
+            },
+        }
+    }
+}
+
+/// What a transaction would do
+#[derive(Default)]
+pub struct Preview {
+    /// Packages that aren't installed yet, including new dependencies
+    pub install: Vec<PkgRef>,
+    /// Installed packages, and the sync packages replacing them
+    pub upgrade: Vec<(PkgIdx, PkgRef)>,
+    pub remove: Vec<PkgIdx>,
+    /// Package files to install. Their sizes aren't known.
+    pub files: Vec<FileTarget>,
+    /// Targets and dependencies that couldn't be found
+    pub unresolved: Vec<SmolStr>,
+    /// Installed packages that are kept, but depend on a removed package, along with it.
+    ///
+    /// pacman refuses removals that leave such packages behind.
+    pub blockers: Vec<(PkgIdx, PkgIdx)>,
+}
+
+/// A package file to install
+pub struct FileTarget {
+    pub name: SmolStr,
+    pub version: SmolStr,
+    /// The installed package it replaces
+    pub replaces: Option<PkgIdx>,
+}
+
+impl Preview {
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty()
+            && self.upgrade.is_empty()
+            && self.remove.is_empty()
+            && self.files.is_empty()
+    }
+    /// Size of the packages that need to be downloaded
+    pub fn download_size(&self, dbs: &Dbs) -> i64 {
+        self.install
+            .iter()
+            .chain(self.upgrade.iter().map(|(_, remote)| remote))
+            .filter_map(|pkg_ref| dbs.resolve(*pkg_ref).1)
+            .map(|pkg| pkg.desc.csize)
+            .sum()
+    }
+    /// How much the installed size changes by
+    pub fn installed_size_delta(&self, dbs: &Dbs) -> i64 {
+        let size = |pkg: Option<&Pkg>| pkg.map_or(0, |pkg| pkg.desc.size);
+        let installed: i64 = self
+            .install
+            .iter()
+            .map(|pkg_ref| size(dbs.resolve(*pkg_ref).1))
+            .sum();
+        let upgraded: i64 = self
+            .upgrade
+            .iter()
+            .map(|(local, remote)| size(dbs.resolve(*remote).1) - size(dbs.resolve_local(*local)))
+            .sum();
+        let removed: i64 = self
+            .remove
+            .iter()
+            .map(|idx| size(dbs.resolve_local(*idx)))
+            .sum();
+        installed + upgraded - removed
+    }
+}
+
+/// `repo/pkg` or a plain name, which may also be provided by another package
+fn resolve_target(dbs: &Dbs, target: &str) -> Option<PkgRef> {
+    match target.split_once('/') {
+        Some((db_name, name)) => dbs.sync_pkg_in(db_name, name),
+        None => resolve_name(dbs, target),
+    }
+}
+
+/// The sync package with this name, or else the first one providing it, in repo order
+fn resolve_name(dbs: &Dbs, name: &str) -> Option<PkgRef> {
+    dbs.sync_pkg_by_name(name).or_else(|| {
+        dbs.remotes().find_map(|(db_idx, db)| {
+            let idx = db
+                .pkgs
+                .iter()
+                .position(|pkg| pkg.desc.provides.iter().any(|dep| dep.name == name))?;
+            Some(PkgRef::from_components(db_idx, PkgIdx::from_usize(idx)))
+        })
+    })
+}
+
+/// Sync packages to install or upgrade, along with the missing dependencies they pull in
+struct InstallPlan<'db> {
+    dbs: &'db Dbs,
+    preview: Preview,
+    /// Sync packages to process, in order
+    queue: Vec<PkgRef>,
+    /// Names installed or provided by installed and planned packages
+    satisfied: HashSet<&'db str>,
+}
+
+impl<'db> InstallPlan<'db> {
+    fn new(dbs: &'db Dbs) -> Self {
+        let satisfied = dbs
+            .local_pkgs()
+            .iter()
+            .flat_map(|pkg| {
+                std::iter::once(pkg.desc.name.as_str())
+                    .chain(pkg.desc.provides.iter().map(|dep| dep.name.as_str()))
+            })
+            .collect();
+        Self {
+            dbs,
+            preview: Preview::default(),
+            queue: Vec::new(),
+            satisfied,
+        }
+    }
+    fn run(mut self) -> Preview {
+        let mut planned = HashSet::new();
+        let mut i = 0;
+        while let Some(&pkg_ref) = self.queue.get(i) {
+            i += 1;
+            let Some(pkg) = self.dbs.resolve(pkg_ref).1 else {
+                continue;
+            };
+            if !planned.insert(pkg.desc.name.as_str()) {
+                continue;
+            }
+            match self.dbs.local_pkg_by_name(&pkg.desc.name) {
+                Some((local_idx, _)) => self.preview.upgrade.push((local_idx, pkg_ref)),
+                None => self.preview.install.push(pkg_ref),
+            }
+            self.satisfied.insert(&pkg.desc.name);
+            self.satisfied
+                .extend(pkg.desc.provides.iter().map(|dep| dep.name.as_str()));
+            for dep in &pkg.desc.depends {
+                if !self.satisfied.insert(&dep.name) {
+                    continue;
+                }
+                match resolve_name(self.dbs, &dep.name) {
+                    Some(dep_ref) => self.queue.push(dep_ref),
+                    None => self.preview.unresolved.push(dep.name.clone()),
+                }
+            }
+        }
+        self.preview
+    }
+}
+
+/// Dependency edges between installed packages
+struct DepIndex {
+    /// The installed packages satisfying the dependencies of each package
+    deps: Vec<Vec<usize>>,
+    /// The installed packages depending on each package
+    required_by: Vec<Vec<usize>>,
+}
+
+impl DepIndex {
+    fn new(pkgs: &[Pkg]) -> Self {
+        let mut providers: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, pkg) in pkgs.iter().enumerate() {
+            providers.entry(pkg.desc.name.as_str()).or_default().push(i);
+            for provided in &pkg.desc.provides {
+                providers.entry(provided.name.as_str()).or_default().push(i);
+            }
+        }
+        let mut deps = vec![Vec::new(); pkgs.len()];
+        let mut required_by = vec![Vec::new(); pkgs.len()];
+        for (i, (pkg, pkg_deps)) in pkgs.iter().zip(&mut deps).enumerate() {
+            for dep in &pkg.desc.depends {
+                for &j in providers.get(dep.name.as_str()).into_iter().flatten() {
+                    if pkgs
+                        .get(j)
+                        .is_some_and(|provider| pkg_satisfies(provider, dep))
+                        && let Some(dependents) = required_by.get_mut(j)
+                    {
+                        pkg_deps.push(j);
+                        dependents.push(i);
+                    }
+                }
+            }
+        }
+        for edges in deps.iter_mut().chain(&mut required_by) {
+            edges.sort_unstable();
+            edges.dedup();
+        }
+        Self { deps, required_by }
+    }
+}
+
+/// The targets, the packages depending on them if `cascade` is set, and the dependencies
+/// that are no longer needed afterwards
+fn plan_removal(dbs: &Dbs, names: &[SmolStr], cascade: bool) -> Preview {
+    let pkgs = dbs.local_pkgs();
+    let index = DepIndex::new(pkgs);
+    let mut preview = Preview::default();
+    let mut removed = vec![false; pkgs.len()];
+    let mut stack = Vec::new();
+    for name in names {
+        match dbs.local_pkg_by_name(name) {
+            Some((idx, _)) => stack.push(idx.to_usize()),
+            None => preview.unresolved.push(name.clone()),
+        }
+    }
+    // Targets, and with `cascade`, everything depending on them
+    while let Some(i) = stack.pop() {
+        let Some(removed) = removed.get_mut(i).filter(|removed| !**removed) else {
+            continue;
+        };
+        *removed = true;
+        if cascade {
+            stack.extend(index.required_by.get(i).into_iter().flatten());
+        }
+    }
+    // Dependencies that nothing left depends on
+    let mut stack: Vec<usize> = (0..pkgs.len())
+        .filter(|&i| removed.get(i) == Some(&true))
+        .collect();
+    while let Some(i) = stack.pop() {
+        for &dep in index.deps.get(i).into_iter().flatten() {
+            let unneeded = removed.get(dep) == Some(&false)
+                && pkgs
+                    .get(dep)
+                    .is_some_and(|pkg| matches!(pkg.desc.install_reason, InstallReason::Dep))
+                && index
+                    .required_by
+                    .get(dep)
+                    .into_iter()
+                    .flatten()
+                    .all(|&dependent| removed.get(dependent) == Some(&true));
+            if unneeded && let Some(removed) = removed.get_mut(dep) {
+                *removed = true;
+                stack.push(dep);
+            }
+        }
+    }
+    preview.remove = removed
+        .iter()
+        .enumerate()
+        .filter_map(|(i, removed)| removed.then_some(PkgIdx::from_usize(i)))
+        .collect();
+    preview.blockers = blockers(pkgs, &index, &removed);
+    preview
+}
+
+/// Kept packages with a dependency that only removed packages satisfy, along with the
+/// first of those
+fn blockers(pkgs: &[Pkg], index: &DepIndex, removed: &[bool]) -> Vec<(PkgIdx, PkgIdx)> {
+    let is_removed = |i: usize| removed.get(i) == Some(&true);
+    let mut blockers = Vec::new();
+    for (i, (pkg, deps)) in pkgs.iter().zip(&index.deps).enumerate() {
+        if is_removed(i) || !deps.iter().any(|&dep| is_removed(dep)) {
+            continue;
+        }
+        for dep in &pkg.desc.depends {
+            let satisfying = || {
+                deps.iter()
+                    .copied()
+                    .filter(|&j| pkgs.get(j).is_some_and(|p| pkg_satisfies(p, dep)))
+            };
+            if satisfying().all(is_removed)
+                && let Some(j) = satisfying().next()
+            {
+                blockers.push((PkgIdx::from_usize(i), PkgIdx::from_usize(j)));
+            }
+        }
+    }
+    blockers
+}
+
+#[test]
+fn test_args() {
+    let op = Op::Install {
+        targets: vec!["extra/foo".into(), "bar".into()],
+        as_deps: true,
+    };
+    assert_eq!(op.args(), ["-S", "--asdeps", "extra/foo", "bar"]);
+    let op = Op::Remove {
+        names: vec!["foo".into()],
+        cascade: true,
+    };
+    assert_eq!(op.args(), ["-Rscn", "foo"]);
+    assert_eq!(Op::SysUpgrade.args(), ["-Su"]);
+    let op = Op::InstallFile {
+        path: "/var/cache/pacman/pkg/foo-1.0-1-x86_64.pkg.tar.zst".into(),
+        name: "foo".into(),
+        version: "1.0-1".into(),
+    };
+    assert_eq!(
+        op.args(),
+        ["-U", "/var/cache/pacman/pkg/foo-1.0-1-x86_64.pkg.tar.zst"]
+    );
+}
+
+#[cfg(test)]
+fn removal_dbs() -> Dbs {
+    use crate::packages::fixtures;
+    fixtures::dbs(vec![(
+        "local",
+        vec![
+            fixtures::sized(fixtures::pkg("app", "1.0-1", &["lib", "shared"]), 100, 0),
+            fixtures::sized(
+                fixtures::as_dep(fixtures::pkg("lib", "1.0-1", &["libdep"])),
+                20,
+                0,
+            ),
+            fixtures::sized(
+                fixtures::as_dep(fixtures::pkg("libdep", "1.0-1", &[])),
+                5,
+                0,
+            ),
+            fixtures::as_dep(fixtures::pkg("shared", "1.0-1", &[])),
+            fixtures::pkg("tool", "1.0-1", &["shared"]),
+            fixtures::pkg("plugin", "1.0-1", &["app>=1.0"]),
+            fixtures::pkg("addon", "1.0-1", &["app>=2.0"]),
+            fixtures::as_dep(fixtures::pkg("orphan", "1.0-1", &[])),
+        ],
+    )])
+}
+
+#[cfg(test)]
+fn removed_names(dbs: &Dbs, names: &[&str], cascade: bool) -> Vec<String> {
+    let names = names.iter().map(|&name| SmolStr::from(name)).collect();
+    let preview = Op::Remove { names, cascade }.preview(dbs);
+    let mut removed: Vec<String> = preview
+        .remove
+        .iter()
+        .filter_map(|idx| dbs.resolve_local(*idx))
+        .map(|pkg| pkg.desc.name.to_string())
+        .collect();
+    removed.sort_unstable();
+    removed
+}
+
+#[test]
+fn test_plan_removal_recursive() {
+    let dbs = removal_dbs();
+    // `shared` is still needed by `tool`
+    assert_eq!(
+        removed_names(&dbs, &["app"], false),
+        ["app", "lib", "libdep"]
+    );
+    assert_eq!(
+        removed_names(&dbs, &["app", "tool"], false),
+        ["app", "lib", "libdep", "shared", "tool"]
+    );
+    // Explicitly installed packages are kept
+    assert_eq!(removed_names(&dbs, &["plugin"], false), ["plugin"]);
+    let names = vec![SmolStr::from("app"), SmolStr::from("missing")];
+    let preview = Op::Remove {
+        names,
+        cascade: false,
+    }
+    .preview(&dbs);
+    assert_eq!(preview.unresolved, ["missing"]);
+    assert_eq!(preview.installed_size_delta(&dbs), -125);
+    assert_eq!(preview.download_size(&dbs), 0);
+}
+
+#[test]
+fn test_plan_removal_blockers() {
+    let dbs = removal_dbs();
+    let blockers = |names: &[&str], cascade| {
+        let names = names.iter().map(|&name| SmolStr::from(name)).collect();
+        let preview = Op::Remove { names, cascade }.preview(&dbs);
+        preview
+            .blockers
+            .iter()
+            .filter_map(|&(pkg, dep)| Some((dbs.resolve_local(pkg)?, dbs.resolve_local(dep)?)))
+            .map(|(pkg, dep)| format!("{} -> {}", pkg.desc.name, dep.desc.name))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(blockers(&["app"], false), ["plugin -> app"]);
+    assert_eq!(
+        blockers(&["shared"], false),
+        ["app -> shared", "tool -> shared"]
+    );
+    // `addon` wants a version of `app` that isn't installed
+    assert!(blockers(&["app", "plugin"], false).is_empty());
+    assert!(blockers(&["shared"], true).is_empty());
+}
+
+#[test]
+fn test_plan_removal_cascade() {
+    let dbs = removal_dbs();
+    // `addon` wants a version of `app` that isn't installed, so it doesn't depend on it
+    assert_eq!(
+        removed_names(&dbs, &["app"], true),
+        ["app", "lib", "libdep", "plugin"]
+    );
+    assert_eq!(
+        removed_names(&dbs, &["shared"], true),
+        ["app", "lib", "libdep", "plugin", "shared", "tool"]
+    );
+}
+
+#[cfg(test)]
+fn install_dbs() -> Dbs {
+    use crate::packages::fixtures;
+    let mut provider = fixtures::sized(fixtures::pkg("provider", "1.0-1", &[]), 1, 1);
+    provider.desc.provides.push(fixtures::dep("virt"));
+    fixtures::dbs(vec![
+        (
+            "local",
+            vec![fixtures::sized(fixtures::pkg("lib", "1.0-1", &[]), 20, 0)],
+        ),
+        (
+            "core",
+            vec![
+                fixtures::sized(fixtures::pkg("app", "1.0-1", &["lib", "newdep"]), 100, 50),
+                fixtures::sized(fixtures::pkg("lib", "1.1-1", &[]), 30, 15),
+                fixtures::sized(fixtures::pkg("newdep", "1.0-1", &["virt", "gone"]), 10, 5),
+                provider,
+            ],
+        ),
+    ])
+}
+
+#[test]
+fn test_install_plan() {
+    let dbs = install_dbs();
+    let preview = Op::Install {
+        targets: vec!["app".into(), "core/missing".into()],
+        as_deps: false,
+    }
+    .preview(&dbs);
+    let names: Vec<&str> = preview
+        .install
+        .iter()
+        .filter_map(|pkg_ref| dbs.resolve(*pkg_ref).1)
+        .map(|pkg| pkg.desc.name.as_str())
+        .collect();
+    // `lib` is already installed, so it isn't upgraded
+    assert_eq!(names, ["app", "newdep", "provider"]);
+    assert!(preview.upgrade.is_empty());
+    assert_eq!(preview.unresolved, ["core/missing", "gone"]);
+    assert_eq!(preview.download_size(&dbs), 56);
+    assert_eq!(preview.installed_size_delta(&dbs), 111);
+}
+
+#[test]
+fn test_sysupgrade_plan() {
+    let dbs = install_dbs();
+    let preview = Op::SysUpgrade.preview(&dbs);
+    assert!(preview.install.is_empty());
+    let upgraded: Vec<(&str, &str)> = preview
+        .upgrade
+        .iter()
+        .filter_map(|(local, remote)| {
+            let local = dbs.resolve_local(*local)?;
+            let remote = dbs.resolve(*remote).1?;
+            Some((local.desc.version.as_str(), remote.desc.version.as_str()))
+        })
+        .collect();
+    assert_eq!(upgraded, [("1.0-1", "1.1-1")]);
+    assert_eq!(preview.download_size(&dbs), 15);
+    assert_eq!(preview.installed_size_delta(&dbs), 10);
+}
@@ -13,6 +13,58 @@ pub fn deduped_files(list: &[Box<str>]) -> impl Iterator<Item = &str> {
         .chain(list.last().map(|s| &**s))
 }
 
+/// Format a unix timestamp as a local date and time
+pub fn format_timestamp(timestamp: i64) -> String {
+    match chrono::DateTime::from_timestamp(timestamp, 0) {
+        Some(date) => date
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        None => format!("<invalid timestamp {timestamp}>"),
+    }
+}
+
 fn path_contains_other_path(haystack: &Path, needle: &Path) -> bool {
     haystack.parent() == Some(needle)
 }
+
+/// Match `text` against a shell-style wildcard pattern, supporting `*` and `?`
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pat: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen, and the text position it's currently matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pat.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || text.get(t) == Some(&c) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pat.get(p..)
+        .is_some_and(|rest| rest.iter().all(|&c| c == '*'))
+}
+
+#[test]
+fn test_wildcard_match() {
+    assert!(wildcard_match("*.conf", "foo.conf"));
+    assert!(wildcard_match("*", ""));
+    assert!(wildcard_match("f?o*", "foobar"));
+    assert!(wildcard_match("*bar*baz", "foobarquxbaz"));
+    assert!(!wildcard_match("*.conf", "foo.conf.bak"));
+    assert!(!wildcard_match("?", ""));
+}